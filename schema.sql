@@ -1,6 +1,24 @@
 CREATE DATABASE IF NOT EXISTS inventory_db;
 USE inventory_db;
 
+-- One row per confirmed bulk upload; its ledger rows and new items point back here
+CREATE TABLE IF NOT EXISTS import_batch (
+    batch_id INT AUTO_INCREMENT PRIMARY KEY,
+    file_name VARCHAR(255),
+    file_hash CHAR(64),
+    row_count INT NOT NULL DEFAULT 0,
+    new_item_count INT NOT NULL DEFAULT 0,
+    merged_count INT NOT NULL DEFAULT 0,
+    duplicate_count INT NOT NULL DEFAULT 0,
+    total_quantity DECIMAL(18, 4) NOT NULL DEFAULT 0,
+    status ENUM('POSTED', 'REVERTED') NOT NULL DEFAULT 'POSTED',
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    reverted_by VARCHAR(255),
+    reverted_at DATETIME,
+    INDEX idx_batch_hash (file_hash)
+);
+
 CREATE TABLE IF NOT EXISTS stock_master (
     stock_id VARCHAR(36) PRIMARY KEY,
     project VARCHAR(100) NOT NULL,
@@ -13,7 +31,25 @@ CREATE TABLE IF NOT EXISTS stock_master (
     location VARCHAR(100) NOT NULL,
     remarks TEXT,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-    INDEX idx_identity (project, supplier_name, invoice, po_no, part_name, uom, location)
+    batch_id INT,
+    -- Set once the item has been merged into another; hidden from lists and matching
+    merged_into VARCHAR(36),
+    -- Archived items have no stock and are hidden from the inventory until restored
+    status ENUM('ACTIVE', 'ARCHIVED') NOT NULL DEFAULT 'ACTIVE',
+    INDEX idx_identity (project, supplier_name, invoice, po_no, part_name, uom, location),
+    FOREIGN KEY (batch_id) REFERENCES import_batch(batch_id),
+    FOREIGN KEY (merged_into) REFERENCES stock_master(stock_id)
+);
+
+CREATE TABLE IF NOT EXISTS goods_receipt (
+    grn_id INT AUTO_INCREMENT PRIMARY KEY,
+    grn_no VARCHAR(30) NOT NULL UNIQUE,
+    supplier_name VARCHAR(100) NOT NULL,
+    invoice VARCHAR(100) NOT NULL,
+    po_no VARCHAR(100) NOT NULL,
+    receipt_date DATETIME,
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
 CREATE TABLE IF NOT EXISTS stock_ledger (
@@ -26,7 +62,17 @@ CREATE TABLE IF NOT EXISTS stock_ledger (
     optional_reason TEXT,
     created_by VARCHAR(255),
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id)
+    grn_id INT,
+    unit_cost DECIMAL(15, 4),
+    batch_id INT,
+    -- Purchase order line the receipt was booked against (table is defined below)
+    po_line_id INT,
+    -- Entry a REVERSAL row undoes
+    reverses_ledger_id INT,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    FOREIGN KEY (grn_id) REFERENCES goods_receipt(grn_id),
+    FOREIGN KEY (batch_id) REFERENCES import_batch(batch_id),
+    FOREIGN KEY (reverses_ledger_id) REFERENCES stock_ledger(ledger_id)
 );
 
 CREATE TABLE IF NOT EXISTS stock_threshold (
@@ -34,9 +80,258 @@ CREATE TABLE IF NOT EXISTS stock_threshold (
     min_quantity DECIMAL(15, 4) DEFAULT 0,
     slow_moving_days INT DEFAULT 30,
     dead_stock_days INT DEFAULT 90,
+    reorder_point DECIMAL(15, 4) DEFAULT 0,
+    reorder_quantity DECIMAL(15, 4) DEFAULT 0,
+    updated_by VARCHAR(255),
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id)
+);
+
+CREATE TABLE IF NOT EXISTS purchase_order_line (
+    po_line_id INT AUTO_INCREMENT PRIMARY KEY,
+    po_no VARCHAR(100) NOT NULL,
+    supplier_name VARCHAR(100) NOT NULL,
+    stock_id VARCHAR(36) NOT NULL,
+    ordered_qty DECIMAL(15, 4) NOT NULL,
+    received_qty DECIMAL(15, 4) NOT NULL DEFAULT 0,
+    expected_date DATE,
+    status ENUM('OPEN', 'CLOSED') NOT NULL DEFAULT 'OPEN',
+    -- How a CLOSED line was closed: fully received, or closed by hand
+    closed_by ENUM('RECEIPT', 'MANUAL'),
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    INDEX idx_po_status (status, po_no)
+);
+
+CREATE TABLE IF NOT EXISTS document_sequence (
+    doc_type VARCHAR(20) PRIMARY KEY,
+    prefix VARCHAR(10) NOT NULL,
+    next_value INT NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS requisition (
+    requisition_id INT AUTO_INCREMENT PRIMARY KEY,
+    requisition_no VARCHAR(30) NOT NULL UNIQUE,
+    project VARCHAR(100) NOT NULL,
+    requested_by VARCHAR(255) NOT NULL,
+    required_by DATE,
+    remarks TEXT,
+    status ENUM('PENDING', 'APPROVED', 'PARTIALLY_APPROVED', 'REJECTED', 'CANCELLED', 'PARTIALLY_ISSUED', 'FULFILLED') NOT NULL DEFAULT 'PENDING',
+    reviewed_by VARCHAR(255),
+    reviewed_at DATETIME,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_requisition_status (status)
+);
+
+CREATE TABLE IF NOT EXISTS requisition_line (
+    line_id INT AUTO_INCREMENT PRIMARY KEY,
+    requisition_id INT NOT NULL,
+    line_no INT NOT NULL,
+    stock_id VARCHAR(36) NOT NULL,
+    requested_qty DECIMAL(15, 4) NOT NULL,
+    approved_qty DECIMAL(15, 4),
+    issued_qty DECIMAL(15, 4) NOT NULL DEFAULT 0,
+    FOREIGN KEY (requisition_id) REFERENCES requisition(requisition_id),
     FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id)
 );
 
+CREATE TABLE IF NOT EXISTS requisition_history (
+    history_id INT AUTO_INCREMENT PRIMARY KEY,
+    requisition_id INT NOT NULL,
+    action VARCHAR(30) NOT NULL,
+    from_status VARCHAR(30),
+    to_status VARCHAR(30) NOT NULL,
+    comment TEXT,
+    performed_by VARCHAR(255),
+    performed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (requisition_id) REFERENCES requisition(requisition_id)
+);
+
+CREATE TABLE IF NOT EXISTS issue_slip (
+    slip_id INT AUTO_INCREMENT PRIMARY KEY,
+    slip_no VARCHAR(30) NOT NULL UNIQUE,
+    issued_to VARCHAR(100) NOT NULL,
+    project VARCHAR(100) NOT NULL,
+    work_order VARCHAR(100),
+    reference VARCHAR(255),
+    remarks TEXT,
+    requisition_id INT,
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (requisition_id) REFERENCES requisition(requisition_id)
+);
+
+CREATE TABLE IF NOT EXISTS issue_slip_line (
+    line_id INT AUTO_INCREMENT PRIMARY KEY,
+    slip_id INT NOT NULL,
+    line_no INT NOT NULL,
+    stock_id VARCHAR(36) NOT NULL,
+    quantity DECIMAL(15, 4) NOT NULL,
+    ledger_id INT NOT NULL,
+    FOREIGN KEY (slip_id) REFERENCES issue_slip(slip_id),
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    FOREIGN KEY (ledger_id) REFERENCES stock_ledger(ledger_id)
+);
+
+CREATE TABLE IF NOT EXISTS app_setting (
+    setting_key VARCHAR(100) PRIMARY KEY,
+    setting_value TEXT,
+    updated_by VARCHAR(255),
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS stock_reservation (
+    reservation_id INT AUTO_INCREMENT PRIMARY KEY,
+    stock_id VARCHAR(36) NOT NULL,
+    project VARCHAR(100) NOT NULL,
+    quantity DECIMAL(15, 4) NOT NULL,
+    consumed_qty DECIMAL(15, 4) NOT NULL DEFAULT 0,
+    status ENUM('ACTIVE', 'CONSUMED', 'EXPIRED', 'RELEASED') NOT NULL DEFAULT 'ACTIVE',
+    expires_at DATETIME,
+    reference VARCHAR(255),
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    INDEX idx_reservation_status (status, expires_at)
+);
+
+CREATE TABLE IF NOT EXISTS item_classification (
+    stock_id VARCHAR(36) PRIMARY KEY,
+    abc_class CHAR(1) NOT NULL,
+    xyz_class CHAR(1) NOT NULL,
+    consumption_qty DECIMAL(15, 4) NOT NULL DEFAULT 0,
+    consumption_value DECIMAL(18, 4) NOT NULL DEFAULT 0,
+    demand_cv DECIMAL(10, 4),
+    count_interval_days INT NOT NULL,
+    window_days INT NOT NULL,
+    computed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    INDEX idx_item_class (abc_class, xyz_class)
+);
+
+-- Each row closes the books through a date; postings dated on or before the
+-- latest closed_through are rejected
+CREATE TABLE IF NOT EXISTS period_close (
+    close_id INT AUTO_INCREMENT PRIMARY KEY,
+    closed_through DATE NOT NULL,
+    closed_by VARCHAR(255),
+    closed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Receipts imported despite matching an earlier receipt of the same supplier
+-- invoice and item
+CREATE TABLE IF NOT EXISTS import_duplicate_override (
+    override_id INT AUTO_INCREMENT PRIMARY KEY,
+    ledger_id INT NOT NULL,
+    duplicate_of_ledger_id INT NOT NULL,
+    supplier_name VARCHAR(100) NOT NULL,
+    invoice VARCHAR(100) NOT NULL,
+    reason TEXT,
+    overridden_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (ledger_id) REFERENCES stock_ledger(ledger_id),
+    FOREIGN KEY (duplicate_of_ledger_id) REFERENCES stock_ledger(ledger_id)
+);
+
+-- Corrections to item master fields, one row per record and field changed
+CREATE TABLE IF NOT EXISTS item_history (
+    history_id INT AUTO_INCREMENT PRIMARY KEY,
+    stock_id VARCHAR(36) NOT NULL,
+    field_name VARCHAR(50) NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    changed_by VARCHAR(255),
+    changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    INDEX idx_item_history_stock (stock_id)
+);
+
+-- Duplicate items folded into one; report is the merge report as JSON
+CREATE TABLE IF NOT EXISTS item_merge (
+    merge_id INT AUTO_INCREMENT PRIMARY KEY,
+    target_stock_id VARCHAR(36) NOT NULL,
+    report TEXT NOT NULL,
+    merged_by VARCHAR(255),
+    merged_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    undone_by VARCHAR(255),
+    undone_at DATETIME,
+    FOREIGN KEY (target_stock_id) REFERENCES stock_master(stock_id)
+);
+
+-- Every row a merge repointed and where it pointed before, so the merge can be
+-- undone. row_id is the table's primary key; for stock_master, the merged item.
+CREATE TABLE IF NOT EXISTS item_merge_row (
+    merge_id INT NOT NULL,
+    table_name VARCHAR(50) NOT NULL,
+    row_id VARCHAR(36) NOT NULL,
+    from_stock_id VARCHAR(36) NOT NULL,
+    PRIMARY KEY (merge_id, table_name, row_id),
+    FOREIGN KEY (merge_id) REFERENCES item_merge(merge_id)
+);
+
+-- Spellings of merged items, so imports still find the item they were merged into
+CREATE TABLE IF NOT EXISTS item_alias (
+    alias_id INT AUTO_INCREMENT PRIMARY KEY,
+    merge_id INT NOT NULL,
+    project VARCHAR(100) NOT NULL,
+    part_name VARCHAR(100) NOT NULL,
+    description TEXT NOT NULL,
+    uom VARCHAR(50) NOT NULL,
+    location VARCHAR(100) NOT NULL,
+    target_stock_id VARCHAR(36) NOT NULL,
+    FOREIGN KEY (merge_id) REFERENCES item_merge(merge_id),
+    FOREIGN KEY (target_stock_id) REFERENCES stock_master(stock_id)
+);
+
+-- Saved column layouts for supplier spreadsheets; definition is the profile as JSON
+CREATE TABLE IF NOT EXISTS import_profile (
+    profile_id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(100) UNIQUE NOT NULL,
+    header_fingerprint CHAR(64),
+    definition TEXT NOT NULL,
+    created_by VARCHAR(255),
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    INDEX idx_profile_fingerprint (header_fingerprint)
+);
+
+CREATE TABLE IF NOT EXISTS attribute_definition (
+    attribute_id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(40) UNIQUE NOT NULL,
+    data_type ENUM('TEXT', 'NUMBER', 'DATE', 'BOOLEAN', 'LIST') NOT NULL DEFAULT 'TEXT',
+    required BOOLEAN NOT NULL DEFAULT FALSE,
+    allowed_values TEXT,
+    sort_order INT NOT NULL DEFAULT 0,
+    created_by VARCHAR(255),
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS item_attribute_value (
+    stock_id VARCHAR(36) NOT NULL,
+    attribute_id INT NOT NULL,
+    value VARCHAR(255) NOT NULL,
+    PRIMARY KEY (stock_id, attribute_id),
+    FOREIGN KEY (stock_id) REFERENCES stock_master(stock_id),
+    FOREIGN KEY (attribute_id) REFERENCES attribute_definition(attribute_id) ON DELETE CASCADE,
+    INDEX idx_attribute_value (attribute_id, value)
+);
+
+-- Files are stored by content hash in the attachment folder; rows only point at them
+CREATE TABLE IF NOT EXISTS attachment (
+    attachment_id INT AUTO_INCREMENT PRIMARY KEY,
+    entity_type ENUM('ITEM', 'GRN', 'ISSUE_SLIP') NOT NULL,
+    entity_id VARCHAR(36) NOT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    file_hash CHAR(64) NOT NULL,
+    mime_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    description VARCHAR(255),
+    uploaded_by VARCHAR(255),
+    uploaded_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_attachment_entity (entity_type, entity_id),
+    INDEX idx_attachment_hash (file_hash)
+);
+
 CREATE TABLE IF NOT EXISTS users (
     user_id INT AUTO_INCREMENT PRIMARY KEY,
     username VARCHAR(100) UNIQUE NOT NULL,
@@ -47,3 +342,21 @@ CREATE TABLE IF NOT EXISTS users (
 -- Insert a default user (password: admin123)
 -- In a real app, we should use a proper hash, but for local LAN app with single login, we'll handle this in Rust.
 INSERT IGNORE INTO users (username, password_hash) VALUES ('admin', 'admin123');
+
+-- Document number sequences (slip numbers are allocated inside the posting transaction)
+INSERT IGNORE INTO document_sequence (doc_type, prefix, next_value) VALUES ('ISSUE_SLIP', 'IS', 1);
+INSERT IGNORE INTO document_sequence (doc_type, prefix, next_value) VALUES ('GRN', 'GRN', 1);
+INSERT IGNORE INTO document_sequence (doc_type, prefix, next_value) VALUES ('REQUISITION', 'MR', 1);
+
+-- Letterhead used on printed documents
+INSERT IGNORE INTO app_setting (setting_key, setting_value) VALUES ('company_name', 'InvPro Stores');
+
+-- Reservations without an explicit expiry lapse after this many days
+INSERT IGNORE INTO app_setting (setting_key, setting_value) VALUES ('reservation_expiry_days', '30');
+
+-- Movement aging limits for items without their own stock_threshold row
+INSERT IGNORE INTO app_setting (setting_key, setting_value) VALUES ('slow_moving_days', '30');
+INSERT IGNORE INTO app_setting (setting_key, setting_value) VALUES ('dead_stock_days', '90');
+
+-- Shared folder for attachments; empty keeps them in the app data directory
+INSERT IGNORE INTO app_setting (setting_key, setting_value) VALUES ('attachment_folder', '');
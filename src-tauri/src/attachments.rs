@@ -0,0 +1,226 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the file with `hash` is kept under `root`, in a folder per first two hex
+/// digits so no single folder grows too large.
+pub fn stored_path(root: &Path, hash: &str) -> PathBuf {
+    root.join(&hash[..2]).join(hash)
+}
+
+/// Copies `source` into the store under `root`, hashing it on the way, and returns
+/// its SHA-256 and size. Content that is already stored is not written twice.
+pub fn store(root: &Path, source: &Path) -> Result<(String, u64), String> {
+    let mut input = File::open(source).map_err(|e| format!("Could not open file: {}", e))?;
+    fs::create_dir_all(root).map_err(|e| format!("Could not create attachment folder {}: {}", root.display(), e))?;
+
+    // Written under a temporary name and renamed once complete, so a failed copy
+    // never sits where the hash says a whole file is
+    let partial = root.join(format!("{}.partial", uuid::Uuid::new_v4()));
+    let copied = (|| {
+        let mut output = File::create(&partial)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let n = input.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            output.write_all(&buffer[..n])?;
+            size += n as u64;
+        }
+        output.sync_all()?;
+        Ok::<_, std::io::Error>((format!("{:x}", hasher.finalize()), size))
+    })();
+    let (hash, size) = match copied {
+        Ok(copied) => copied,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(format!("Could not copy file: {}", e));
+        }
+    };
+
+    let target = stored_path(root, &hash);
+    let placed = if target.exists() {
+        fs::remove_file(&partial)
+    } else {
+        fs::create_dir_all(target.parent().unwrap_or(root)).and_then(|_| fs::rename(&partial, &target))
+    };
+    placed.map_err(|e| {
+        let _ = fs::remove_file(&partial);
+        format!("Could not store file: {}", e)
+    })?;
+    Ok((hash, size))
+}
+
+/// Deletes the stored file with `hash`, if present.
+pub fn remove(root: &Path, hash: &str) -> Result<(), String> {
+    let path = stored_path(root, hash);
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            // Only succeeds once the folder is empty
+            let _ = path.parent().map(fs::remove_dir);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Could not delete {}: {}", path.display(), e)),
+    }
+}
+
+/// Copies a stored file out under its original name, for opening in another
+/// program, which goes by the extension. The copy is made in the temp folder.
+pub fn checkout(root: &Path, hash: &str, file_name: &str) -> Result<PathBuf, String> {
+    let source = stored_path(root, hash);
+    if !source.exists() {
+        return Err(format!("'{}' is missing from the attachment folder {}", file_name, root.display()));
+    }
+    let name = Path::new(file_name)
+        .file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_else(|| hash.into());
+    let folder = std::env::temp_dir().join("invpro-attachments").join(hash);
+    fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+    let target = folder.join(name);
+    if !target.exists() {
+        fs::copy(&source, &target).map_err(|e| format!("Could not copy '{}' out: {}", file_name, e))?;
+    }
+    Ok(target)
+}
+
+/// MIME type by file extension, for the kinds of file we expect to see attached.
+pub fn mime_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "eml" => "message/rfc822",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty folder of its own under the temp folder, so tests can run side by side.
+    fn scratch() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("invpro-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn partials(root: &Path) -> Vec<PathBuf> {
+        fs::read_dir(root)
+            .map(|entries| {
+                entries
+                    .map(|e| e.unwrap().path())
+                    .filter(|p| p.extension().is_some_and(|e| e == "partial"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn store_keeps_one_copy_per_hash() {
+        let dir = scratch();
+        let root = dir.join("store");
+        fs::write(dir.join("a.txt"), b"packing slip").unwrap();
+        fs::write(dir.join("b.txt"), b"packing slip").unwrap();
+
+        let (hash, size) = store(&root, &dir.join("a.txt")).unwrap();
+        assert_eq!(hash, format!("{:x}", Sha256::digest(b"packing slip")));
+        assert_eq!(size, 12);
+        assert_eq!(fs::read(stored_path(&root, &hash)).unwrap(), b"packing slip");
+
+        // Same bytes under another name land on the same file
+        assert_eq!(store(&root, &dir.join("b.txt")).unwrap(), (hash.clone(), 12));
+        assert_eq!(fs::read_dir(stored_path(&root, &hash).parent().unwrap()).unwrap().count(), 1);
+        assert!(partials(&root).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_copy_leaves_no_partial_file() {
+        let dir = scratch();
+        let root = dir.join("store");
+
+        let missing = store(&root, &dir.join("missing.pdf")).unwrap_err();
+        assert!(missing.starts_with("Could not open file"), "{}", missing);
+
+        // A folder opens but cannot be read, so the copy fails part way
+        let unreadable = store(&root, &dir).unwrap_err();
+        assert!(unreadable.starts_with("Could not copy file"), "{}", unreadable);
+        assert!(partials(&root).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_deletes_the_file_and_its_empty_folder() {
+        let dir = scratch();
+        let root = dir.join("store");
+        fs::write(dir.join("a.txt"), b"invoice").unwrap();
+        let (hash, _) = store(&root, &dir.join("a.txt")).unwrap();
+        let path = stored_path(&root, &hash);
+
+        remove(&root, &hash).unwrap();
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+        // Already gone is not an error
+        remove(&root, &hash).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checkout_copies_out_under_the_file_name() {
+        let dir = scratch();
+        let root = dir.join("store");
+        // Unique content, since checkouts share one folder per hash
+        let content = format!("drawing {}", uuid::Uuid::new_v4());
+        fs::write(dir.join("a.bin"), &content).unwrap();
+        let (hash, _) = store(&root, &dir.join("a.bin")).unwrap();
+
+        // Only the name is kept from a path
+        let copy = checkout(&root, &hash, "../drawings/bracket.pdf").unwrap();
+        assert_eq!(copy.file_name().unwrap(), "bracket.pdf");
+        assert!(copy.starts_with(std::env::temp_dir().join("invpro-attachments").join(&hash)));
+        assert_eq!(fs::read_to_string(&copy).unwrap(), content);
+
+        remove(&root, &hash).unwrap();
+        let missing = checkout(&root, &hash, "bracket.pdf").unwrap_err();
+        assert!(missing.starts_with("'bracket.pdf' is missing"), "{}", missing);
+
+        fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mime_type_goes_by_extension() {
+        assert_eq!(mime_type("Scan.PDF"), "application/pdf");
+        assert_eq!(mime_type("photo.jpeg"), "image/jpeg");
+        assert_eq!(mime_type("notes"), "application/octet-stream");
+    }
+}
@@ -0,0 +1,257 @@
+use crate::importer;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Names an attribute where a field name is expected, e.g. `attribute:Grade` in
+/// `item_history.field_name` or an import profile column.
+pub const FIELD_PREFIX: &str = "attribute:";
+/// Leaves room for `FIELD_PREFIX` in `item_history.field_name`.
+pub const MAX_NAME_LENGTH: usize = 40;
+pub const MAX_VALUE_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AttributeType {
+    Text,
+    Number,
+    Date,
+    Boolean,
+    List,
+}
+
+impl AttributeType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttributeType::Text => "TEXT",
+            AttributeType::Number => "NUMBER",
+            AttributeType::Date => "DATE",
+            AttributeType::Boolean => "BOOLEAN",
+            AttributeType::List => "LIST",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AttributeType> {
+        [
+            AttributeType::Text,
+            AttributeType::Number,
+            AttributeType::Date,
+            AttributeType::Boolean,
+            AttributeType::List,
+        ]
+        .into_iter()
+        .find(|t| t.as_str().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// An admin-defined item property, e.g. "Grade" (LIST of A/B/C) or "Shelf life
+/// (days)" (NUMBER). Values are stored per item as text in their normalized form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeDefinition {
+    #[serde(default)]
+    pub attribute_id: Option<i32>,
+    pub name: String,
+    pub data_type: AttributeType,
+    /// New items can't be created without a value
+    #[serde(default)]
+    pub required: bool,
+    /// The choices of a LIST attribute
+    #[serde(default)]
+    pub allowed_values: Vec<String>,
+    #[serde(default)]
+    pub sort_order: i32,
+}
+
+impl AttributeDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Attribute name is required".to_string());
+        }
+        if name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!("Attribute name is longer than {} characters", MAX_NAME_LENGTH));
+        }
+        if self.data_type == AttributeType::List {
+            if self.allowed_values.iter().all(|v| v.trim().is_empty()) {
+                return Err(format!("List attribute '{}' needs at least one allowed value", name));
+            }
+            for (i, value) in self.allowed_values.iter().enumerate() {
+                if value.trim().chars().count() > MAX_VALUE_LENGTH {
+                    return Err(format!("Allowed value '{}' is longer than {} characters", value.trim(), MAX_VALUE_LENGTH));
+                }
+                if self.allowed_values[..i].iter().any(|v| v.trim().eq_ignore_ascii_case(value.trim())) {
+                    return Err(format!("Allowed value '{}' is listed twice", value.trim()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks one value and returns it as stored: numbers without trailing zeros,
+    /// dates as YYYY-MM-DD, booleans as Yes/No and list values in their defined case.
+    pub fn normalize_value(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        match self.data_type {
+            AttributeType::Text if value.chars().count() > MAX_VALUE_LENGTH => {
+                Err(format!("Longer than {} characters", MAX_VALUE_LENGTH))
+            }
+            AttributeType::Text => Ok(value.to_string()),
+            AttributeType::Number => Decimal::from_str(&value.replace(',', ""))
+                .map(|n| n.normalize().to_string())
+                .map_err(|_| "Not a number".to_string()),
+            AttributeType::Date => {
+                importer::parse_receipt_date(value).map(|date| date.date().format("%Y-%m-%d").to_string())
+            }
+            AttributeType::Boolean => match value.to_lowercase().as_str() {
+                "yes" | "y" | "true" | "1" => Ok("Yes".to_string()),
+                "no" | "n" | "false" | "0" => Ok("No".to_string()),
+                _ => Err("Expected Yes or No".to_string()),
+            },
+            AttributeType::List => self
+                .allowed_values
+                .iter()
+                .map(|v| v.trim())
+                .find(|v| v.eq_ignore_ascii_case(value))
+                .map(str::to_string)
+                .ok_or_else(|| format!("Expected one of: {}", self.allowed_values.join(", "))),
+        }
+    }
+}
+
+/// Looks up a definition by name, ignoring case.
+pub fn find<'a>(definitions: &'a [AttributeDefinition], name: &str) -> Option<&'a AttributeDefinition> {
+    definitions.iter().find(|d| d.name.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// Normalizes `values`, keyed by attribute name in any case, into a map keyed by the
+/// defined names. Blank values stay blank. Errors name the attribute.
+pub fn normalize_values(
+    definitions: &[AttributeDefinition],
+    values: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut normalized = BTreeMap::new();
+    for (name, value) in values {
+        let definition = find(definitions, name).ok_or_else(|| format!("Unknown attribute '{}'", name))?;
+        let value = if value.trim().is_empty() {
+            String::new()
+        } else {
+            definition
+                .normalize_value(value)
+                .map_err(|e| format!("{}: {}", definition.name, e))?
+        };
+        normalized.insert(definition.name.clone(), value);
+    }
+    Ok(normalized)
+}
+
+/// Names of required attributes without a value in `values`.
+pub fn missing_required<'a>(definitions: &'a [AttributeDefinition], values: &BTreeMap<String, String>) -> Vec<&'a str> {
+    definitions
+        .iter()
+        .filter(|d| d.required && values.get(&d.name).is_none_or(|v| v.trim().is_empty()))
+        .map(|d| d.name.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(name: &str, data_type: AttributeType) -> AttributeDefinition {
+        AttributeDefinition {
+            attribute_id: None,
+            name: name.to_string(),
+            data_type,
+            required: false,
+            allowed_values: Vec::new(),
+            sort_order: 0,
+        }
+    }
+
+    fn grade() -> AttributeDefinition {
+        AttributeDefinition {
+            allowed_values: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            ..definition("Grade", AttributeType::List)
+        }
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn numbers_drop_separators_and_trailing_zeros() {
+        let shelf_life = definition("Shelf life", AttributeType::Number);
+        assert_eq!(shelf_life.normalize_value(" 1,250.50 ").unwrap(), "1250.5");
+        assert_eq!(shelf_life.normalize_value("365").unwrap(), "365");
+        assert_eq!(shelf_life.normalize_value("abc").unwrap_err(), "Not a number");
+    }
+
+    #[test]
+    fn dates_are_stored_as_iso_dates() {
+        let expiry = definition("Expiry", AttributeType::Date);
+        assert_eq!(expiry.normalize_value("31/03/2024").unwrap(), "2024-03-31");
+        assert_eq!(expiry.normalize_value("2024-03-31").unwrap(), "2024-03-31");
+        assert!(expiry.normalize_value("next week").is_err());
+    }
+
+    #[test]
+    fn booleans_become_yes_or_no() {
+        let hazardous = definition("Hazardous", AttributeType::Boolean);
+        assert_eq!(hazardous.normalize_value("y").unwrap(), "Yes");
+        assert_eq!(hazardous.normalize_value("TRUE").unwrap(), "Yes");
+        assert_eq!(hazardous.normalize_value("0").unwrap(), "No");
+        assert_eq!(hazardous.normalize_value("maybe").unwrap_err(), "Expected Yes or No");
+    }
+
+    #[test]
+    fn list_values_take_the_defined_case() {
+        let grade = grade();
+        assert_eq!(grade.normalize_value(" b ").unwrap(), "B");
+        assert_eq!(grade.normalize_value("D").unwrap_err(), "Expected one of: A, B, C");
+    }
+
+    #[test]
+    fn text_is_trimmed_and_limited_in_length() {
+        let colour = definition("Colour", AttributeType::Text);
+        assert_eq!(colour.normalize_value("  Blue ").unwrap(), "Blue");
+        assert!(colour.normalize_value(&"x".repeat(MAX_VALUE_LENGTH)).is_ok());
+        assert!(colour.normalize_value(&"x".repeat(MAX_VALUE_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn normalize_values_keys_by_defined_name() {
+        let definitions = vec![grade(), definition("Shelf life", AttributeType::Number)];
+        let normalized = normalize_values(&definitions, &values(&[("grade", "a"), ("SHELF LIFE", " ")])).unwrap();
+        assert_eq!(normalized, values(&[("Grade", "A"), ("Shelf life", "")]));
+
+        let error = normalize_values(&definitions, &values(&[("Shelf life", "long")])).unwrap_err();
+        assert_eq!(error, "Shelf life: Not a number");
+        let error = normalize_values(&definitions, &values(&[("Colour", "Blue")])).unwrap_err();
+        assert_eq!(error, "Unknown attribute 'Colour'");
+    }
+
+    #[test]
+    fn missing_required_counts_blank_values_as_missing() {
+        let definitions = vec![
+            AttributeDefinition { required: true, ..grade() },
+            AttributeDefinition { required: true, ..definition("Expiry", AttributeType::Date) },
+            definition("Colour", AttributeType::Text),
+        ];
+        assert_eq!(missing_required(&definitions, &values(&[])), vec!["Grade", "Expiry"]);
+        assert_eq!(missing_required(&definitions, &values(&[("Grade", "A"), ("Expiry", "  ")])), vec!["Expiry"]);
+        assert!(missing_required(&definitions, &values(&[("Grade", "A"), ("Expiry", "2024-03-31")])).is_empty());
+    }
+
+    #[test]
+    fn list_definitions_need_distinct_values() {
+        let mut grade = grade();
+        assert!(grade.validate().is_ok());
+        grade.allowed_values.push(" a ".to_string());
+        assert_eq!(grade.validate().unwrap_err(), "Allowed value 'a' is listed twice");
+        grade.allowed_values = vec![" ".to_string()];
+        assert!(grade.validate().is_err());
+        assert!(definition(" ", AttributeType::Text).validate().is_err());
+    }
+}
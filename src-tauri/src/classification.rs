@@ -0,0 +1,132 @@
+use rust_decimal::Decimal;
+
+/// Ranks items by consumption value into A/B/C. An item's class is decided by the
+/// cumulative share of total value held by the items ranked above it, so the top
+/// item is always an A. Items with no consumption are always C.
+pub fn abc_classes(values: &[Decimal], a_percent: Decimal, b_percent: Decimal) -> Vec<char> {
+    let mut classes = vec!['C'; values.len()];
+    let total: Decimal = values.iter().filter(|v| v.is_sign_positive()).sum();
+    if total.is_zero() {
+        return classes;
+    }
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*b].cmp(&values[*a]).then(a.cmp(b)));
+
+    let hundred = Decimal::ONE_HUNDRED;
+    let mut cumulative = Decimal::ZERO;
+    for idx in order {
+        let value = values[idx];
+        if value <= Decimal::ZERO {
+            break;
+        }
+        let share_before = cumulative * hundred / total;
+        classes[idx] = if share_before < a_percent {
+            'A'
+        } else if share_before < b_percent {
+            'B'
+        } else {
+            'C'
+        };
+        cumulative += value;
+    }
+
+    classes
+}
+
+/// Coefficient of variation (population standard deviation over mean) of a demand
+/// series. `None` when there was no demand at all.
+pub fn coefficient_of_variation(series: &[f64]) -> Option<f64> {
+    if series.is_empty() {
+        return None;
+    }
+    let n = series.len() as f64;
+    let mean = series.iter().sum::<f64>() / n;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = series.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    Some(variance.sqrt() / mean)
+}
+
+/// X = steady demand, Y = fluctuating, Z = erratic or none.
+pub fn xyz_class(cv: Option<f64>, x_limit: f64, y_limit: f64) -> char {
+    match cv {
+        Some(cv) if cv <= x_limit => 'X',
+        Some(cv) if cv <= y_limit => 'Y',
+        _ => 'Z',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimals(values: &[i64]) -> Vec<Decimal> {
+        values.iter().map(|v| Decimal::from(*v)).collect()
+    }
+
+    #[test]
+    fn abc_class_goes_by_share_ranked_above() {
+        let classes = abc_classes(&decimals(&[10, 70, 15, 5]), Decimal::from(80), Decimal::from(95));
+        // 70 has 0% above it, 15 has 70%, 10 has 85%, 5 has 95%
+        assert_eq!(classes, vec!['B', 'A', 'A', 'C']);
+    }
+
+    #[test]
+    fn abc_cut_off_share_belongs_to_next_class() {
+        // The second item starts exactly at 80%, the third exactly at 95%
+        let classes = abc_classes(&decimals(&[80, 15, 5]), Decimal::from(80), Decimal::from(95));
+        assert_eq!(classes, vec!['A', 'B', 'C']);
+    }
+
+    #[test]
+    fn abc_top_item_is_always_a() {
+        // Even when it alone holds more than the A share
+        let classes = abc_classes(&decimals(&[1, 99]), Decimal::ONE, Decimal::from(95));
+        assert_eq!(classes, vec!['C', 'A']);
+    }
+
+    #[test]
+    fn abc_unconsumed_items_are_c() {
+        assert_eq!(abc_classes(&decimals(&[0, 0]), Decimal::from(80), Decimal::from(95)), vec!['C', 'C']);
+        assert_eq!(abc_classes(&decimals(&[5, 0, -3]), Decimal::from(80), Decimal::from(95)), vec!['A', 'C', 'C']);
+        assert!(abc_classes(&[], Decimal::from(80), Decimal::from(95)).is_empty());
+    }
+
+    #[test]
+    fn abc_ties_keep_input_order() {
+        let classes = abc_classes(&decimals(&[50, 50]), Decimal::from(50), Decimal::from(95));
+        assert_eq!(classes, vec!['A', 'B']);
+    }
+
+    #[test]
+    fn cv_of_no_demand_is_none() {
+        assert_eq!(coefficient_of_variation(&[]), None);
+        assert_eq!(coefficient_of_variation(&[0.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn cv_of_steady_demand_is_zero() {
+        assert_eq!(coefficient_of_variation(&[4.0, 4.0, 4.0]), Some(0.0));
+    }
+
+    #[test]
+    fn cv_uses_population_deviation() {
+        // Mean 5, deviation 3
+        let cv = coefficient_of_variation(&[2.0, 8.0]).unwrap();
+        assert!((cv - 0.6).abs() < 1e-12);
+        // One month of demand in four: mean 1, deviation sqrt(3)
+        let cv = coefficient_of_variation(&[0.0, 0.0, 0.0, 4.0]).unwrap();
+        assert!((cv - 3f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn xyz_limits_are_inclusive() {
+        assert_eq!(xyz_class(Some(0.5), 0.5, 1.0), 'X');
+        assert_eq!(xyz_class(Some(0.50001), 0.5, 1.0), 'Y');
+        assert_eq!(xyz_class(Some(1.0), 0.5, 1.0), 'Y');
+        assert_eq!(xyz_class(Some(1.2), 0.5, 1.0), 'Z');
+        assert_eq!(xyz_class(None, 0.5, 1.0), 'Z');
+    }
+}
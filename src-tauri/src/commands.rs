@@ -1,8 +1,22 @@
-use crate::db::{StockLedger, StockSummary, Stats, HistoryEntry};
-use sqlx::{mysql::MySqlPool, Row};
+use crate::db::{StockLedger, StockSummary, Stats, HistoryEntry, IssueSlip, IssueSlipLine, GoodsReceipt, GoodsReceiptLine, Requisition, RequisitionLine, RequisitionHistory, OutstandingRequisition, StockReservation, PurchaseOrderLine, ReorderSuggestion, MovementAgingItem, StockMaster, StockCardEntry, PeriodClose, DuplicateOverride, ImportBatch, ImportProfileRecord, ItemHistory, ItemMerge, ArchiveCandidate, AttributeDefinitionRecord, Attachment};
+use crate::attributes::{self, AttributeDefinition, AttributeType};
+use crate::attachments;
+use crate::classification;
+use crate::forecast;
+use crate::matching;
+use crate::importer::{self, CellError, MappingProfile};
+use crate::export::{self, Cell, ColumnKind, ExportColumn, TableWriter};
+use futures::TryStreamExt;
+use crate::pdf::{self, Column, CompanyHeader, PrintableDocument};
+use sqlx::{mysql::MySqlPool, FromRow, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
 
 pub struct AppState {
     pub pool: MySqlPool,
@@ -40,26 +54,232 @@ pub struct InventoryResponse {
     pub total_count: i64,
 }
 
+/// Filters shared by the live and point-in-time inventory views.
+#[derive(Debug, Default)]
+struct InventoryFilters {
+    search: Option<String>,
+    abc_class: Option<String>,
+    xyz_class: Option<String>,
+    include_archived: bool,
+    /// Attribute name to value; text attributes match on a substring, others exactly
+    attributes: BTreeMap<String, String>,
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_inventory(
     state: tauri::State<'_, AppState>,
     page: i32,
     page_size: i32,
     search: Option<String>,
+    abc_class: Option<String>,
+    xyz_class: Option<String>,
+    include_archived: Option<bool>,
+    attributes: Option<BTreeMap<String, String>>,
+) -> Result<InventoryResponse, String> {
+    let include_archived = include_archived.unwrap_or(false);
+    let attributes = attributes.unwrap_or_default();
+    let filters = InventoryFilters { search, abc_class, xyz_class, include_archived, attributes };
+    query_inventory(&state.pool, &filters, None, Some((page, page_size))).await
+}
+
+/// Inventory as it stood at the end of `as_of` (a date, or a date and time), rebuilt
+/// from the ledger. Reversals count from the moment they were posted, so a
+/// transaction reversed after the cut-off is still included. Omitting `page` returns
+/// every row for export. Archived items are included, since they had stock then.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_inventory_as_of(
+    state: tauri::State<'_, AppState>,
+    as_of: String,
+    page: Option<i32>,
+    page_size: Option<i32>,
+    search: Option<String>,
+    abc_class: Option<String>,
+    xyz_class: Option<String>,
+    attributes: Option<BTreeMap<String, String>>,
+) -> Result<InventoryResponse, String> {
+    let cutoff = parse_as_of(&as_of)?;
+    let attributes = attributes.unwrap_or_default();
+    let filters = InventoryFilters { search, abc_class, xyz_class, include_archived: true, attributes };
+    let paging = page.map(|p| (p, page_size.unwrap_or(50)));
+    query_inventory(&state.pool, &filters, Some(&cutoff), paging).await
+}
+
+/// Writes the live inventory, or the inventory as of `as_of` when given, to
+/// `file_path`. Returns the rows written.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_inventory(
+    state: tauri::State<'_, AppState>,
+    file_path: String,
+    as_of: Option<String>,
+    search: Option<String>,
+    abc_class: Option<String>,
+    xyz_class: Option<String>,
+    include_archived: Option<bool>,
+    attributes: Option<BTreeMap<String, String>>,
+) -> Result<usize, String> {
+    let cutoff = match as_of.filter(|d| !d.trim().is_empty()) {
+        Some(d) => Some(parse_as_of(&d)?),
+        None => None,
+    };
+    let include_archived = include_archived.unwrap_or(false) || cutoff.is_some();
+    let attributes = attributes.unwrap_or_default();
+    let filters = InventoryFilters { search, abc_class, xyz_class, include_archived, attributes };
+    let inventory = query_inventory(&state.pool, &filters, cutoff.as_deref(), None).await?;
+    let definitions = attribute_definitions(&state.pool).await?;
+
+    let sheet_name = match &cutoff {
+        Some(c) => format!("Inventory {}", &c[..10]),
+        None => "Inventory".to_string(),
+    };
+    let mut columns = vec![
+        ExportColumn::new("Project", ColumnKind::Text),
+        ExportColumn::new("Part Name", ColumnKind::Text),
+        ExportColumn::new("Description", ColumnKind::Text),
+        ExportColumn::new("UOM", ColumnKind::Text),
+        ExportColumn::new("Location", ColumnKind::Text),
+        ExportColumn::new("Supplier", ColumnKind::Text),
+        ExportColumn::new("Invoice", ColumnKind::Text),
+        ExportColumn::new("PO No", ColumnKind::Text),
+        ExportColumn::new("On Hand", ColumnKind::Quantity),
+        ExportColumn::new("Reserved", ColumnKind::Quantity),
+        ExportColumn::new("Available", ColumnKind::Quantity),
+        ExportColumn::new("Min Qty", ColumnKind::Quantity),
+        ExportColumn::new("ABC", ColumnKind::Text),
+        ExportColumn::new("XYZ", ColumnKind::Text),
+        ExportColumn::new("Last Movement", ColumnKind::Date),
+    ];
+    columns.extend(definitions.iter().map(|d| ExportColumn::new(d.name.clone(), attribute_column_kind(d.data_type))));
+    let mut writer = TableWriter::create(std::path::Path::new(&file_path), &sheet_name, &columns)?;
+    for mut item in inventory.items {
+        let attribute_cells: Vec<Cell> = definitions
+            .iter()
+            .map(|d| attribute_cell(d.data_type, item.attributes.remove(&d.name)))
+            .collect();
+        let m = item.master;
+        let mut cells: Vec<Cell> = vec![
+            m.project.into(),
+            m.part_name.into(),
+            m.description.into(),
+            m.uom.into(),
+            m.location.into(),
+            m.supplier_name.into(),
+            m.invoice.into(),
+            m.po_no.into(),
+            item.on_hand_quantity.into(),
+            item.reserved_quantity.into(),
+            item.available_quantity.into(),
+            item.min_quantity.into(),
+            item.abc_class.into(),
+            item.xyz_class.into(),
+            item.last_movement.into(),
+        ];
+        cells.extend(attribute_cells);
+        writer.write_row(cells)?;
+    }
+    writer.finish()
+}
+
+fn attribute_column_kind(data_type: AttributeType) -> ColumnKind {
+    match data_type {
+        AttributeType::Number => ColumnKind::Number,
+        AttributeType::Date => ColumnKind::Day,
+        AttributeType::Text | AttributeType::Boolean | AttributeType::List => ColumnKind::Text,
+    }
+}
+
+/// Values are stored normalized, so numbers and dates parse back. One saved before
+/// its attribute changed type is written as text.
+fn attribute_cell(data_type: AttributeType, value: Option<String>) -> Cell {
+    let Some(value) = value else {
+        return Cell::Empty;
+    };
+    match data_type {
+        AttributeType::Number => match value.parse::<Decimal>() {
+            Ok(n) => n.into(),
+            Err(_) => value.into(),
+        },
+        AttributeType::Date => match NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+            Ok(d) => d.into(),
+            Err(_) => value.into(),
+        },
+        _ => value.into(),
+    }
+}
+
+/// Normalizes an as-of input to an inclusive `YYYY-MM-DD HH:MM:SS` cut-off. A bare
+/// date means the end of that day.
+fn parse_as_of(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(format!("{} 23:59:59", date.format("%Y-%m-%d")));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(ts) = chrono::NaiveDateTime::parse_from_str(value, format) {
+            return Ok(ts.format("%Y-%m-%d %H:%M:%S").to_string());
+        }
+    }
+    Err(format!("Invalid as-of date '{}'. Use YYYY-MM-DD", value))
+}
+
+/// Grouped inventory query behind `get_inventory` and `get_inventory_as_of`. With an
+/// `as_of` cut-off, balances and last movement only consider ledger rows up to it,
+/// items with no movement by then are left out, and nothing is shown as reserved.
+async fn query_inventory(
+    pool: &MySqlPool,
+    filters: &InventoryFilters,
+    as_of: Option<&str>,
+    paging: Option<(i32, i32)>,
 ) -> Result<InventoryResponse, String> {
-    let offset = (page - 1) * page_size;
-    
     // 1. Build Base Filter Clause
-    let mut where_clause = String::new();
-    if let Some(ref s) = search {
+    let mut conditions: Vec<String> = vec!["m.merged_into IS NULL".to_string()];
+    let mut binds: Vec<String> = Vec::new();
+    if !filters.include_archived {
+        conditions.push("m.status = 'ACTIVE'".to_string());
+    }
+    if let Some(ref s) = filters.search {
         if !s.is_empty() {
-            let s_lower = s.to_lowercase();
-             where_clause.push_str(&format!(
-                " WHERE LOWER(m.part_name) LIKE '%{}%' OR LOWER(m.project) LIKE '%{}%' OR LOWER(m.supplier_name) LIKE '%{}%' OR LOWER(m.invoice) LIKE '%{}%'",
-                s_lower, s_lower, s_lower, s_lower
+            let pattern = format!("%{}%", s.to_lowercase());
+            conditions.push("(LOWER(m.part_name) LIKE ? OR LOWER(m.project) LIKE ? OR LOWER(m.supplier_name) LIKE ? OR LOWER(m.invoice) LIKE ?
+                OR EXISTS (SELECT 1 FROM item_attribute_value av WHERE av.stock_id = m.stock_id AND LOWER(av.value) LIKE ?))".to_string());
+            binds.extend(std::iter::repeat_n(pattern, 5));
+        }
+    }
+    let wanted: Vec<(&String, &String)> = filters.attributes.iter().filter(|(_, v)| !v.trim().is_empty()).collect();
+    if !wanted.is_empty() {
+        let definitions = attribute_definitions(pool).await?;
+        for (name, value) in wanted {
+            let definition = attributes::find(&definitions, name).ok_or_else(|| format!("Unknown attribute '{}'", name))?;
+            let (operator, pattern) = match definition.data_type {
+                AttributeType::Text => ("LIKE", format!("%{}%", value.trim().to_lowercase())),
+                _ => ("=", definition.normalize_value(value).map_err(|e| format!("{}: {}", definition.name, e))?.to_lowercase()),
+            };
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM item_attribute_value av WHERE av.stock_id = m.stock_id AND av.attribute_id = {} AND LOWER(av.value) {} ?)",
+                definition.attribute_id.unwrap_or_default(),
+                operator
             ));
+            binds.push(pattern);
         }
     }
+    if let Some(class) = filters.abc_class.as_ref().filter(|c| !c.is_empty() && *c != "All") {
+        conditions.push("c.abc_class = ?".to_string());
+        binds.push(class.to_uppercase());
+    }
+    if let Some(class) = filters.xyz_class.as_ref().filter(|c| !c.is_empty() && *c != "All") {
+        conditions.push("c.xyz_class = ?".to_string());
+        binds.push(class.to_uppercase());
+    }
+    if let Some(cutoff) = as_of {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM stock_ledger lx WHERE lx.stock_id = m.stock_id AND lx.transaction_date <= ?)"
+                .to_string(),
+        );
+        binds.push(cutoff.to_string());
+    }
+    let where_clause = format!(" WHERE {}", conditions.join(" AND "));
 
     // 2. Count Total Matching Rows (Grouped)
     // We need to count the number of groups that match the criteria
@@ -67,20 +287,47 @@ pub async fn get_inventory(
         "SELECT COUNT(*) FROM (
             SELECT 1 
             FROM stock_master m 
+            LEFT JOIN item_classification c ON m.stock_id = c.stock_id
             {}
             GROUP BY m.project, m.part_name, m.uom, m.location, m.description
         ) as count_table",
         where_clause
     );
 
-    let total_count: i64 = sqlx::query_scalar(&count_query)
-        .fetch_one(&state.pool)
+    let mut count = sqlx::query_scalar(&count_query);
+    for value in &binds {
+        count = count.bind(value);
+    }
+    let total_count: i64 = count
+        .fetch_one(pool)
         .await
         .map_err(|e| format!("Failed to count items: {}", e))?;
 
-    // 3. Fetch Data
-    let mut query_str = String::from(
-        "SELECT 
+    // 3. Fetch Data (available = on hand minus active reservations)
+    let ledger_cutoff = |alias: &str| match as_of {
+        Some(_) => format!(" AND {}.transaction_date <= ?", alias),
+        None => String::new(),
+    };
+    let reserved_quantity = if as_of.is_some() {
+        // Reservations are not historised, so a past snapshot shows nothing held back
+        "CAST(0 AS DECIMAL(15,4))".to_string()
+    } else {
+        "(
+                SELECT COALESCE(SUM(r.quantity - r.consumed_qty), 0)
+                FROM stock_reservation r
+                JOIN stock_master m4 ON r.stock_id = m4.stock_id
+                WHERE LOWER(TRIM(m4.project)) = LOWER(TRIM(m.project)) 
+                  AND LOWER(TRIM(m4.part_name)) = LOWER(TRIM(m.part_name)) 
+                  AND LOWER(TRIM(m4.uom)) = LOWER(TRIM(m.uom)) 
+                  AND LOWER(TRIM(m4.location)) = LOWER(TRIM(m.location))
+                  AND LOWER(TRIM(m4.description)) = LOWER(TRIM(m.description))
+                  AND r.status = 'ACTIVE' AND (r.expires_at IS NULL OR r.expires_at > NOW())
+            )"
+        .to_string()
+    };
+    let mut query_str = format!(
+        "SELECT inv.*, inv.on_hand_quantity - inv.reserved_quantity as available_quantity FROM (
+        SELECT 
             MAX(m.stock_id) as stock_id, 
             MAX(m.project) as project, 
             MAX(m.part_name) as part_name, 
@@ -100,9 +347,13 @@ pub async fn get_inventory(
                   AND LOWER(TRIM(m2.part_name)) = LOWER(TRIM(m.part_name)) 
                   AND LOWER(TRIM(m2.uom)) = LOWER(TRIM(m.uom)) 
                   AND LOWER(TRIM(m2.location)) = LOWER(TRIM(m.location))
-                  AND LOWER(TRIM(m2.description)) = LOWER(TRIM(m.description))
-            ) as available_quantity,
+                  AND LOWER(TRIM(m2.description)) = LOWER(TRIM(m.description)){on_hand_cutoff}
+            ) as on_hand_quantity,
+            {reserved_quantity} as reserved_quantity,
             MAX(COALESCE(t.min_quantity, 0)) as min_quantity,
+            MAX(c.abc_class) as abc_class,
+            MAX(c.xyz_class) as xyz_class,
+            MAX(c.count_interval_days) as count_interval_days,
             (
                 SELECT MAX(l3.transaction_date)
                 FROM stock_ledger l3
@@ -111,38 +362,108 @@ pub async fn get_inventory(
                   AND LOWER(TRIM(m3.part_name)) = LOWER(TRIM(m.part_name)) 
                   AND LOWER(TRIM(m3.uom)) = LOWER(TRIM(m.uom)) 
                   AND LOWER(TRIM(m3.location)) = LOWER(TRIM(m.location))
-                  AND LOWER(TRIM(m3.description)) = LOWER(TRIM(m.description))
-            ) as last_movement
+                  AND LOWER(TRIM(m3.description)) = LOWER(TRIM(m.description)){movement_cutoff}
+            ) as last_movement,
+            MIN(m.status) as status
         FROM stock_master m
         LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
-        "
+        LEFT JOIN item_classification c ON m.stock_id = c.stock_id
+        ",
+        on_hand_cutoff = ledger_cutoff("l2"),
+        reserved_quantity = reserved_quantity,
+        movement_cutoff = ledger_cutoff("l3"),
     );
 
     query_str.push_str(&where_clause);
     query_str.push_str(" GROUP BY m.project, m.part_name, m.uom, m.location, m.description");
-    query_str.push_str(" ORDER BY MAX(m.created_at) DESC LIMIT ? OFFSET ?");
+    query_str.push_str(" ORDER BY MAX(m.created_at) DESC");
+    if paging.is_some() {
+        query_str.push_str(" LIMIT ? OFFSET ?");
+    }
+    query_str.push_str(") as inv ORDER BY inv.created_at DESC");
 
-    let items = sqlx::query_as::<sqlx::MySql, StockSummary>(&query_str)
-        .bind(page_size)
-        .bind(offset)
-        .fetch_all(&state.pool)
+    // Select-list placeholders come before the WHERE clause ones
+    let mut query = sqlx::query_as::<sqlx::MySql, StockSummary>(&query_str);
+    if let Some(cutoff) = as_of {
+        query = query.bind(cutoff).bind(cutoff);
+    }
+    for value in &binds {
+        query = query.bind(value);
+    }
+    if let Some((page, page_size)) = paging {
+        query = query.bind(page_size).bind((page - 1) * page_size);
+    }
+    let mut items = query
+        .fetch_all(pool)
         .await
         .map_err(|e| e.to_string())?;
 
+    let stock_ids: Vec<&str> = items.iter().map(|i| i.master.stock_id.as_str()).collect();
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let mut values = item_attribute_values(&mut conn, &stock_ids).await?;
+    for item in &mut items {
+        item.attributes = values.remove(&item.master.stock_id).unwrap_or_default();
+    }
+
     Ok(InventoryResponse { items, total_count })
 }
 
-#[tauri::command]
-pub async fn issue_stock(
-    state: tauri::State<'_, AppState>,
-    stock_id: String,
-    quantity: Decimal,
-    reference: String,
-    reason: Option<String>,
-    user: String,
-) -> Result<(), String> {
-    // 1. Check if enough shared stock is available for this item's identity set
-    let available: Decimal = sqlx::query_scalar(
+type IdentityKey = (String, String, String, String, String);
+
+const IDENTITY_COLUMNS: [&str; 5] = ["project", "part_name", "description", "uom", "location"];
+
+/// SQL condition joining two stock_master aliases on the normalized identity set.
+fn identity_match(a: &str, b: &str) -> String {
+    IDENTITY_COLUMNS
+        .iter()
+        .map(|col| format!("LOWER(TRIM({a}.{col})) = LOWER(TRIM({b}.{col}))"))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// SQL condition joining the identity columns of `table` to a key table pushed by
+/// `push_key_table`, whose values are already normalized.
+fn identity_match_key(table: &str) -> String {
+    IDENTITY_COLUMNS
+        .iter()
+        .map(|col| format!("LOWER(TRIM({table}.{col})) = k.{col}"))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn identity_values(identity: &IdentityKey) -> [&str; 5] {
+    let (project, part_name, description, uom, location) = identity;
+    [project, part_name, description, uom, location]
+}
+
+/// Pushes `keys` as a derived table `k`, with each key's position in `keys` as
+/// `k.idx`. Joining on it rather than filtering with `IN` tells which key a row
+/// matched. The columns compare under their collation, which takes "Café" and
+/// "cafe" as equal, so matches must be mapped back by `idx`, not by comparing the
+/// row's values to the keys in Rust.
+fn push_key_table<'a, const N: usize>(
+    builder: &mut sqlx::QueryBuilder<'a, sqlx::MySql>,
+    columns: [&str; N],
+    keys: &[[&'a str; N]],
+) {
+    builder.push("(");
+    for (idx, key) in keys.iter().enumerate() {
+        builder.push(if idx == 0 { "SELECT " } else { " UNION ALL SELECT " });
+        builder.push(format!("{} AS idx", idx));
+        for (column, value) in columns.iter().zip(key) {
+            builder.push(", ").push_bind(*value).push(format!(" AS {}", column));
+        }
+    }
+    builder.push(") k");
+}
+
+/// Sums the ledger across every stock_master row sharing `stock_id`'s identity set.
+/// With `for_update` the scanned rows are locked until the surrounding transaction ends.
+async fn available_for_identity<'e, E>(executor: E, stock_id: &str, for_update: bool) -> Result<Decimal, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let mut query = String::from(
         "SELECT COALESCE(SUM(l.quantity_change), 0)
          FROM stock_ledger l
          JOIN stock_master m ON l.stock_id = m.stock_id
@@ -153,230 +474,3671 @@ pub async fn issue_stock(
             LOWER(TRIM(m.uom)) = LOWER(TRIM(target.uom)) AND 
             LOWER(TRIM(m.location)) = LOWER(TRIM(target.location))
          WHERE target.stock_id = ?"
-    )
-    .bind(&stock_id)
-    .fetch_one(&state.pool)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    if available < quantity {
-        return Err("Insufficient stock".to_string());
+    );
+    if for_update {
+        query.push_str(" FOR UPDATE");
     }
 
-    // 2. Insert negative ledger entry
-    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by) VALUES (?, 'OUT', ?, NOW(), ?, ?, ?)")
+    sqlx::query_scalar(&query)
         .bind(stock_id)
-        .bind(-quantity)
-        .bind(reference)
-        .bind(reason)
-        .bind(user)
-        .execute(&state.pool)
+        .fetch_one(executor)
         .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(())
+        .map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-pub async fn get_stats(state: tauri::State<'_, AppState>) -> Result<Stats, String> {
-    let total_unique: i64 = sqlx::query_scalar(
-        "SELECT COUNT(DISTINCT project, part_name, uom, location) FROM stock_master"
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    let total_received: Decimal = sqlx::query_scalar(
-        "SELECT COALESCE(SUM(l.quantity_change), 0) FROM stock_ledger l 
-         WHERE l.transaction_type = 'IN' 
-         AND NOT EXISTS (SELECT 1 FROM stock_ledger WHERE reference LIKE CONCAT('%Ledger ID: ', l.ledger_id, '%') AND transaction_type = 'REVERSAL')"
+/// Outstanding, unexpired reservations against `stock_id`'s identity set. Reservations
+/// held for `for_project` are left out, since an issue to that project may draw on them.
+async fn reserved_for_identity<'e, E>(executor: E, stock_id: &str, for_project: Option<&str>) -> Result<Decimal, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    sqlx::query_scalar(
+        "SELECT COALESCE(SUM(r.quantity - r.consumed_qty), 0)
+         FROM stock_reservation r
+         JOIN stock_master m ON r.stock_id = m.stock_id
+         JOIN stock_master target ON 
+            LOWER(TRIM(m.project)) = LOWER(TRIM(target.project)) AND 
+            LOWER(TRIM(m.part_name)) = LOWER(TRIM(target.part_name)) AND 
+            LOWER(TRIM(m.description)) = LOWER(TRIM(target.description)) AND 
+            LOWER(TRIM(m.uom)) = LOWER(TRIM(target.uom)) AND 
+            LOWER(TRIM(m.location)) = LOWER(TRIM(target.location))
+         WHERE target.stock_id = ?
+           AND r.status = 'ACTIVE' AND (r.expires_at IS NULL OR r.expires_at > NOW())
+           AND (? IS NULL OR LOWER(TRIM(r.project)) <> LOWER(TRIM(?)))"
     )
-    .fetch_one(&state.pool)
+    .bind(stock_id)
+    .bind(for_project)
+    .bind(for_project)
+    .fetch_one(executor)
     .await
-    .map_err(|e| e.to_string())?;
+    .map_err(|e| e.to_string())
+}
 
-    let total_issued: Decimal = sqlx::query_scalar(
-        "SELECT ABS(COALESCE(SUM(l.quantity_change), 0)) FROM stock_ledger l 
-         WHERE l.transaction_type = 'OUT' 
-         AND NOT EXISTS (SELECT 1 FROM stock_ledger WHERE reference LIKE CONCAT('%Ledger ID: ', l.ledger_id, '%') AND transaction_type = 'REVERSAL')"
+/// Draws down `project`'s active reservations on the identity of `stock_id`, oldest
+/// first, by up to `quantity`. Fully used reservations are marked CONSUMED.
+async fn consume_reservations(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    stock_id: &str,
+    project: &str,
+    quantity: Decimal,
+) -> Result<(), String> {
+    let reservations = sqlx::query(
+        "SELECT r.reservation_id, r.quantity - r.consumed_qty as remaining
+         FROM stock_reservation r
+         JOIN stock_master m ON r.stock_id = m.stock_id
+         JOIN stock_master target ON 
+            LOWER(TRIM(m.project)) = LOWER(TRIM(target.project)) AND 
+            LOWER(TRIM(m.part_name)) = LOWER(TRIM(target.part_name)) AND 
+            LOWER(TRIM(m.description)) = LOWER(TRIM(target.description)) AND 
+            LOWER(TRIM(m.uom)) = LOWER(TRIM(target.uom)) AND 
+            LOWER(TRIM(m.location)) = LOWER(TRIM(target.location))
+         WHERE target.stock_id = ? AND LOWER(TRIM(r.project)) = LOWER(TRIM(?))
+           AND r.status = 'ACTIVE' AND (r.expires_at IS NULL OR r.expires_at > NOW())
+         ORDER BY r.created_at, r.reservation_id
+         FOR UPDATE"
     )
-    .fetch_one(&state.pool)
+    .bind(stock_id)
+    .bind(project)
+    .fetch_all(&mut **tx)
     .await
     .map_err(|e| e.to_string())?;
 
-    let low_stock_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM (
-            SELECT 
-                MAX(m.project) as project, 
-                MAX(m.part_name) as part_name, 
-                MAX(m.uom) as uom, 
-                MAX(m.location) as location,
-                COALESCE(SUM(l.quantity_change), 0) as available_grouped,
-                MAX(COALESCE(t.min_quantity, 0)) as max_min_qty
-            FROM stock_master m
-            LEFT JOIN stock_ledger l ON m.stock_id = l.stock_id
-            LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
-            GROUP BY LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)), LOWER(TRIM(m.description))
-        ) as grouped_inventory WHERE available_grouped < max_min_qty"
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    let mut left = quantity;
+    for reservation in reservations {
+        if left <= Decimal::ZERO {
+            break;
+        }
+        let reservation_id: i32 = reservation.get("reservation_id");
+        let remaining: Decimal = reservation.get("remaining");
+        let take = remaining.min(left);
+        left -= take;
 
-    Ok(Stats {
-        total_unique_items: total_unique,
-        total_received,
-        total_issued,
-        low_stock_count,
-    })
-}
+        sqlx::query("UPDATE stock_reservation SET consumed_qty = consumed_qty + ?, status = IF(consumed_qty >= quantity, 'CONSUMED', status) WHERE reservation_id = ?")
+            .bind(take)
+            .bind(reservation_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
 
-fn normalize_string(s: &str) -> String {
-    s.trim()
-        .to_lowercase()
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ImportRow {
-    pub project: String,
-    pub supplier_name: String,
-    pub invoice: String,
-    pub po_no: String,
-    pub part_name: String,
-    pub description: String,
-    pub quantity: Decimal,
-    pub uom: String,
-    pub location: String,
-    #[serde(default)]
-    pub remarks: Option<String>,
-    #[serde(default)]
-    pub rec_date: Option<String>,
-}
+/// Returns the normalized identity set of a stock row together with its display part name.
+async fn identity_of<'e, E>(executor: E, stock_id: &str) -> Result<Option<(IdentityKey, String)>, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let row = sqlx::query(
+        "SELECT LOWER(TRIM(project)), LOWER(TRIM(part_name)), LOWER(TRIM(description)), LOWER(TRIM(uom)), LOWER(TRIM(location)), part_name
+         FROM stock_master WHERE stock_id = ?"
+    )
+    .bind(stock_id)
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| e.to_string())?;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ImportPreview {
-    pub row: ImportRow,
-    pub status: String, // "NEW" | "MERGED"
-    #[serde(default)]
-    pub diff_reason: Option<String>,
-    #[serde(default)]
-    pub existing_stock_id: Option<String>,
+    Ok(row.map(|r| ((r.get(0), r.get(1), r.get(2), r.get(3), r.get(4)), r.get(5))))
 }
 
-#[tauri::command]
-pub async fn bulk_upload_preview(
-    state: tauri::State<'_, AppState>,
-    rows: Vec<ImportRow>,
-) -> Result<Vec<ImportPreview>, String> {
-    println!("Bulk upload preview requested for {} rows", rows.len());
-    let mut previews = Vec::new();
+/// Allocates the next number for `doc_type`, e.g. `IS-000042`. The sequence row stays
+/// locked until `tx` commits, so numbers are gap-free and never handed out twice.
+async fn next_document_number(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    doc_type: &str,
+) -> Result<String, String> {
+    let row = sqlx::query("SELECT prefix, next_value FROM document_sequence WHERE doc_type = ? FOR UPDATE")
+        .bind(doc_type)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No document sequence configured for {}", doc_type))?;
 
-    for row in rows {
-        let norm_project = normalize_string(&row.project);
-        let norm_supplier = normalize_string(&row.supplier_name);
-        let norm_invoice = normalize_string(&row.invoice);
-        let norm_po = normalize_string(&row.po_no);
-        let norm_part = normalize_string(&row.part_name);
-        let norm_description = normalize_string(&row.description);
-        let norm_uom = normalize_string(&row.uom);
-        let norm_location = normalize_string(&row.location);
-
-        // Find match in DB - Matching ONLY on the core Identity Set
-        let existing = sqlx::query("SELECT stock_id FROM stock_master WHERE 
-            LOWER(TRIM(project)) = ? AND 
-            LOWER(TRIM(part_name)) = ? AND 
-            LOWER(TRIM(uom)) = ? AND 
-            LOWER(TRIM(description)) = ? AND 
-            LOWER(TRIM(location)) = ?")
-            .bind(&norm_project)
-            .bind(&norm_part)
-            .bind(&norm_uom)
-            .bind(&norm_description)
-            .bind(&norm_location)
-            .fetch_optional(&state.pool)
-            .await
-            .map_err(|e| e.to_string())?;
+    let prefix: String = row.get("prefix");
+    let next_value: i32 = row.get("next_value");
 
-        if let Some(r) = existing {
-            let stock_id: String = r.get(0);
-            previews.push(ImportPreview {
-                row,
-                status: "MERGED".to_string(),
-                diff_reason: None,
-                existing_stock_id: Some(stock_id),
-            });
-        } else {
-            previews.push(ImportPreview {
-                row,
-                status: "NEW".to_string(),
-                diff_reason: None,
-                existing_stock_id: None,
-            });
-        }
-    }
+    sqlx::query("UPDATE document_sequence SET next_value = next_value + 1 WHERE doc_type = ?")
+        .bind(doc_type)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    Ok(previews)
+    Ok(format!("{}-{:06}", prefix, next_value))
 }
 
 #[tauri::command]
-pub async fn confirm_bulk_upload(
+pub async fn issue_stock(
     state: tauri::State<'_, AppState>,
-    previews: Vec<ImportPreview>,
+    stock_id: String,
+    quantity: Decimal,
+    reference: String,
+    reason: Option<String>,
+    project: Option<String>,
     user: String,
 ) -> Result<(), String> {
+    let project = project.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
     let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
 
-    for preview in previews {
-        let stock_id = if let Some(id) = preview.existing_stock_id.as_ref() {
-            id.clone()
-        } else {
-            let new_id = Uuid::new_v4().to_string();
-            sqlx::query("INSERT INTO stock_master (stock_id, project, supplier_name, invoice, po_no, part_name, description, uom, location, remarks) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
-                .bind(&new_id)
-                .bind(&preview.row.project)
-                .bind(&preview.row.supplier_name)
-                .bind(&preview.row.invoice)
-                .bind(&preview.row.po_no)
-                .bind(&preview.row.part_name)
-                .bind(&preview.row.description)
-                .bind(&preview.row.uom)
-                .bind(&preview.row.location)
-                .bind(&preview.row.remarks)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| e.to_string())?;
-            new_id
-        };
+    // 1. Check if enough shared stock is available for this item's identity set,
+    //    less whatever is reserved for other projects
+    let on_hand = available_for_identity(&mut *tx, &stock_id, true).await?;
+    let reserved = reserved_for_identity(&mut *tx, &stock_id, project.as_deref()).await?;
 
-        sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, created_by) VALUES (?, 'IN', ?, NOW(), ?, ?)")
-            .bind(stock_id)
-            .bind(preview.row.quantity)
-            .bind(format!("Excel Import: {} | Supplier: {}", preview.row.invoice, preview.row.supplier_name))
-            .bind(&user)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| e.to_string())?;
+    if on_hand - reserved < quantity {
+        return Err("Insufficient stock".to_string());
+    }
+
+    // 2. Insert negative ledger entry
+    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by) VALUES (?, 'OUT', ?, NOW(), ?, ?, ?)")
+        .bind(&stock_id)
+        .bind(-quantity)
+        .bind(reference)
+        .bind(reason)
+        .bind(user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 3. An issue to a project uses up that project's reservations first
+    if let Some(project) = project.as_deref() {
+        consume_reservations(&mut tx, &stock_id, project, quantity).await?;
     }
 
     tx.commit().await.map_err(|e| e.to_string())?;
     Ok(())
 }
 
+#[derive(Debug, Deserialize)]
+pub struct IssueSlipHeader {
+    pub issued_to: String,
+    pub project: String,
+    #[serde(default)]
+    pub work_order: Option<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub remarks: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueLineInput {
+    pub stock_id: String,
+    pub quantity: Decimal,
+}
+
 #[derive(Serialize)]
-pub struct HistoryResponse {
-    pub items: Vec<HistoryEntry>,
-    pub total_count: i64,
+pub struct IssueSlipDocument {
+    pub slip: IssueSlip,
+    pub lines: Vec<IssueSlipLine>,
 }
 
-#[tauri::command]
-pub async fn get_history(
-    state: tauri::State<'_, AppState>,
-    page: i32,
-    page_size: i32,
-    search: Option<String>,
+async fn load_issue_slip(pool: &MySqlPool, slip_id: i32) -> Result<IssueSlipDocument, String> {
+    let slip = sqlx::query_as::<sqlx::MySql, IssueSlip>("SELECT * FROM issue_slip WHERE slip_id = ?")
+        .bind(slip_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Issue slip {} not found", slip_id))?;
+
+    let lines = sqlx::query_as::<sqlx::MySql, IssueSlipLine>(
+        "SELECT sl.*, m.part_name, m.description, m.uom, m.location
+         FROM issue_slip_line sl
+         JOIN stock_master m ON sl.stock_id = m.stock_id
+         WHERE sl.slip_id = ?
+         ORDER BY sl.line_no"
+    )
+    .bind(slip_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(IssueSlipDocument { slip, lines })
+}
+
+/// Validates and posts an issue slip inside `tx`, returning its id and number.
+/// Every line is checked before anything is written; the caller commits.
+async fn post_issue_slip(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    header: &IssueSlipHeader,
+    lines: &[IssueLineInput],
+    user: &str,
+    requisition_id: Option<i32>,
+) -> Result<(i32, String), String> {
+    if header.issued_to.trim().is_empty() {
+        return Err("Issued to is required".to_string());
+    }
+    if header.project.trim().is_empty() {
+        return Err("Project is required".to_string());
+    }
+    if lines.is_empty() {
+        return Err("An issue slip needs at least one line".to_string());
+    }
+
+    let mut errors = Vec::new();
+
+    // 1. Validate lines and total the requested quantity per identity set,
+    //    since two lines may draw on the same shared balance. Kept sorted so
+    //    every slip locks identities in the same order and two can't deadlock.
+    let mut requested: BTreeMap<IdentityKey, (usize, String, String, Decimal)> = BTreeMap::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let line_no = idx + 1;
+        if line.quantity <= Decimal::ZERO {
+            errors.push(format!("Line {}: quantity must be greater than zero", line_no));
+            continue;
+        }
+        match identity_of(&mut **tx, &line.stock_id).await? {
+            Some((key, part_name)) => {
+                let entry = requested
+                    .entry(key)
+                    .or_insert((line_no, line.stock_id.clone(), part_name, Decimal::ZERO));
+                entry.3 += line.quantity;
+            }
+            None => errors.push(format!("Line {}: unknown stock item {}", line_no, line.stock_id)),
+        }
+    }
+
+    // 2. Check (and lock) the shared balance of every identity touched, net of
+    //    stock reserved for other projects
+    let mut shortages: Vec<(usize, String)> = Vec::new();
+    for (first_line, stock_id, part_name, total) in requested.values() {
+        let on_hand = available_for_identity(&mut **tx, stock_id, true).await?;
+        let reserved = reserved_for_identity(&mut **tx, stock_id, Some(header.project.trim())).await?;
+        let available = on_hand - reserved;
+        if available < *total {
+            shortages.push((
+                *first_line,
+                format!("Line {}: insufficient stock for {} (available {}, requested {})", first_line, part_name, available, total),
+            ));
+        }
+    }
+    shortages.sort();
+    errors.extend(shortages.into_iter().map(|(_, msg)| msg));
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    // 3. Allocate the slip number and post the header
+    let slip_no = next_document_number(tx, "ISSUE_SLIP").await?;
+
+    let slip_id = sqlx::query("INSERT INTO issue_slip (slip_no, issued_to, project, work_order, reference, remarks, requisition_id, created_by) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+        .bind(&slip_no)
+        .bind(header.issued_to.trim())
+        .bind(header.project.trim())
+        .bind(&header.work_order)
+        .bind(&header.reference)
+        .bind(&header.remarks)
+        .bind(requisition_id)
+        .bind(user)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .last_insert_id() as i32;
+
+    let ledger_reference = match header.reference.as_deref().map(str::trim) {
+        Some(r) if !r.is_empty() => format!("Issue Slip: {} | Ref: {}", slip_no, r),
+        _ => format!("Issue Slip: {}", slip_no),
+    };
+
+    // 4. Post one OUT ledger entry per line
+    for (idx, line) in lines.iter().enumerate() {
+        let ledger_id = sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by) VALUES (?, 'OUT', ?, NOW(), ?, ?, ?)")
+            .bind(&line.stock_id)
+            .bind(-line.quantity)
+            .bind(&ledger_reference)
+            .bind(&header.remarks)
+            .bind(user)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .last_insert_id() as i32;
+
+        sqlx::query("INSERT INTO issue_slip_line (slip_id, line_no, stock_id, quantity, ledger_id) VALUES (?, ?, ?, ?, ?)")
+            .bind(slip_id)
+            .bind((idx + 1) as i32)
+            .bind(&line.stock_id)
+            .bind(line.quantity)
+            .bind(ledger_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        consume_reservations(tx, &line.stock_id, header.project.trim(), line.quantity).await?;
+    }
+
+    Ok((slip_id, slip_no))
+}
+
+/// Posts a multi-line issue slip. Every line is validated first and all ledger
+/// entries are written in one transaction, so either the whole slip goes out or nothing does.
+#[tauri::command]
+pub async fn create_issue_slip(
+    state: tauri::State<'_, AppState>,
+    header: IssueSlipHeader,
+    lines: Vec<IssueLineInput>,
+    user: String,
+) -> Result<IssueSlipDocument, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let (slip_id, _) = post_issue_slip(&mut tx, &header, &lines, &user, None).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_issue_slip(&state.pool, slip_id).await
+}
+
+#[tauri::command]
+pub async fn get_issue_slip(
+    state: tauri::State<'_, AppState>,
+    slip_no: String,
+) -> Result<IssueSlipDocument, String> {
+    let slip_id: i32 = sqlx::query_scalar("SELECT slip_id FROM issue_slip WHERE slip_no = ?")
+        .bind(slip_no.trim())
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Issue slip {} not found", slip_no))?;
+
+    load_issue_slip(&state.pool, slip_id).await
+}
+
+#[derive(Serialize)]
+pub struct IssueSlipListResponse {
+    pub items: Vec<IssueSlip>,
+    pub total_count: i64,
+}
+
+#[tauri::command]
+pub async fn list_issue_slips(
+    state: tauri::State<'_, AppState>,
+    page: i32,
+    page_size: i32,
+    search: Option<String>,
+) -> Result<IssueSlipListResponse, String> {
+    let offset = (page - 1) * page_size;
+    let pattern = format!("%{}%", search.unwrap_or_default().trim().to_lowercase());
+    let where_clause = "WHERE LOWER(slip_no) LIKE ? OR LOWER(issued_to) LIKE ? OR LOWER(project) LIKE ? OR LOWER(COALESCE(work_order, '')) LIKE ? OR LOWER(COALESCE(reference, '')) LIKE ?";
+
+    let total_count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM issue_slip {}", where_clause))
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let items = sqlx::query_as::<sqlx::MySql, IssueSlip>(&format!(
+        "SELECT * FROM issue_slip {} ORDER BY slip_id DESC LIMIT ? OFFSET ?",
+        where_clause
+    ))
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(IssueSlipListResponse { items, total_count })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequisitionLineInput {
+    pub stock_id: String,
+    pub quantity: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequisitionDecision {
+    pub line_id: i32,
+    pub approved_quantity: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FulfilmentLine {
+    pub line_id: i32,
+    pub quantity: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct RequisitionDocument {
+    pub requisition: Requisition,
+    pub lines: Vec<RequisitionLine>,
+    pub history: Vec<RequisitionHistory>,
+}
+
+async fn load_requisition(pool: &MySqlPool, requisition_id: i32) -> Result<RequisitionDocument, String> {
+    let requisition = sqlx::query_as::<sqlx::MySql, Requisition>("SELECT * FROM requisition WHERE requisition_id = ?")
+        .bind(requisition_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Requisition {} not found", requisition_id))?;
+
+    let lines = sqlx::query_as::<sqlx::MySql, RequisitionLine>(
+        "SELECT rl.*, m.part_name, m.description, m.uom, m.location
+         FROM requisition_line rl
+         JOIN stock_master m ON rl.stock_id = m.stock_id
+         WHERE rl.requisition_id = ?
+         ORDER BY rl.line_no"
+    )
+    .bind(requisition_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let history = sqlx::query_as::<sqlx::MySql, RequisitionHistory>(
+        "SELECT * FROM requisition_history WHERE requisition_id = ? ORDER BY history_id"
+    )
+    .bind(requisition_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(RequisitionDocument { requisition, lines, history })
+}
+
+/// Locks the requisition row for the rest of `tx` and returns its current status.
+async fn lock_requisition(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    requisition_id: i32,
+) -> Result<Requisition, String> {
+    sqlx::query_as::<sqlx::MySql, Requisition>("SELECT * FROM requisition WHERE requisition_id = ? FOR UPDATE")
+        .bind(requisition_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Requisition {} not found", requisition_id))
+}
+
+async fn set_requisition_status(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    requisition: &Requisition,
+    action: &str,
+    to_status: &str,
+    comment: Option<&str>,
+    user: &str,
+) -> Result<(), String> {
+    sqlx::query("UPDATE requisition SET status = ? WHERE requisition_id = ?")
+        .bind(to_status)
+        .bind(requisition.requisition_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query("INSERT INTO requisition_history (requisition_id, action, from_status, to_status, comment, performed_by) VALUES (?, ?, ?, ?, ?, ?)")
+        .bind(requisition.requisition_id)
+        .bind(action)
+        .bind(&requisition.status)
+        .bind(to_status)
+        .bind(comment)
+        .bind(user)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_requisition(
+    state: tauri::State<'_, AppState>,
+    project: String,
+    required_by: Option<chrono::NaiveDate>,
+    remarks: Option<String>,
+    lines: Vec<RequisitionLineInput>,
+    user: String,
+) -> Result<RequisitionDocument, String> {
+    if project.trim().is_empty() {
+        return Err("Project is required".to_string());
+    }
+    if lines.is_empty() {
+        return Err("A requisition needs at least one line".to_string());
+    }
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+
+    let mut errors = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if line.quantity <= Decimal::ZERO {
+            errors.push(format!("Line {}: quantity must be greater than zero", idx + 1));
+        } else if identity_of(&mut *tx, &line.stock_id).await?.is_none() {
+            errors.push(format!("Line {}: unknown stock item {}", idx + 1, line.stock_id));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    let requisition_no = next_document_number(&mut tx, "REQUISITION").await?;
+    let requisition_id = sqlx::query("INSERT INTO requisition (requisition_no, project, requested_by, required_by, remarks, status) VALUES (?, ?, ?, ?, ?, 'PENDING')")
+        .bind(&requisition_no)
+        .bind(project.trim())
+        .bind(&user)
+        .bind(required_by)
+        .bind(&remarks)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .last_insert_id() as i32;
+
+    for (idx, line) in lines.iter().enumerate() {
+        sqlx::query("INSERT INTO requisition_line (requisition_id, line_no, stock_id, requested_qty) VALUES (?, ?, ?, ?)")
+            .bind(requisition_id)
+            .bind((idx + 1) as i32)
+            .bind(&line.stock_id)
+            .bind(line.quantity)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    sqlx::query("INSERT INTO requisition_history (requisition_id, action, to_status, comment, performed_by) VALUES (?, 'RAISED', 'PENDING', ?, ?)")
+        .bind(requisition_id)
+        .bind(&remarks)
+        .bind(&user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_requisition(&state.pool, requisition_id).await
+}
+
+/// Supervisor decision on a pending requisition. Every line needs an approved
+/// quantity between zero and the requested quantity; the resulting status is
+/// APPROVED, PARTIALLY_APPROVED or REJECTED depending on what was granted.
+#[tauri::command]
+pub async fn review_requisition(
+    state: tauri::State<'_, AppState>,
+    requisition_id: i32,
+    decisions: Vec<RequisitionDecision>,
+    comment: Option<String>,
+    user: String,
+) -> Result<RequisitionDocument, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let requisition = lock_requisition(&mut tx, requisition_id).await?;
+    if requisition.status != "PENDING" {
+        return Err(format!("Requisition {} is {} and can no longer be reviewed", requisition.requisition_no, requisition.status));
+    }
+
+    let lines = sqlx::query("SELECT line_id, line_no, requested_qty FROM requisition_line WHERE requisition_id = ? ORDER BY line_no")
+        .bind(requisition_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let decided: HashMap<i32, Decimal> = decisions.iter().map(|d| (d.line_id, d.approved_quantity)).collect();
+    let mut errors = Vec::new();
+    let mut approved_lines = Vec::new();
+    for line in &lines {
+        let line_id: i32 = line.get("line_id");
+        let line_no: i32 = line.get("line_no");
+        let requested: Decimal = line.get("requested_qty");
+        match decided.get(&line_id) {
+            Some(qty) if *qty < Decimal::ZERO || *qty > requested => {
+                errors.push(format!("Line {}: approved quantity must be between 0 and {}", line_no, requested));
+            }
+            Some(qty) => approved_lines.push((line_id, requested, *qty)),
+            None => errors.push(format!("Line {}: no decision given", line_no)),
+        }
+    }
+    if decided.len() != lines.len() {
+        errors.push("Decisions include lines that are not on this requisition".to_string());
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    for (line_id, _, qty) in &approved_lines {
+        sqlx::query("UPDATE requisition_line SET approved_qty = ? WHERE line_id = ?")
+            .bind(qty)
+            .bind(line_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let to_status = if approved_lines.iter().all(|(_, _, qty)| qty.is_zero()) {
+        "REJECTED"
+    } else if approved_lines.iter().all(|(_, requested, qty)| qty == requested) {
+        "APPROVED"
+    } else {
+        "PARTIALLY_APPROVED"
+    };
+
+    sqlx::query("UPDATE requisition SET reviewed_by = ?, reviewed_at = NOW() WHERE requisition_id = ?")
+        .bind(&user)
+        .bind(requisition_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    set_requisition_status(&mut tx, &requisition, to_status, to_status, comment.as_deref(), &user).await?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_requisition(&state.pool, requisition_id).await
+}
+
+#[tauri::command]
+pub async fn reject_requisition(
+    state: tauri::State<'_, AppState>,
+    requisition_id: i32,
+    comment: Option<String>,
+    user: String,
+) -> Result<RequisitionDocument, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let requisition = lock_requisition(&mut tx, requisition_id).await?;
+    if requisition.status != "PENDING" {
+        return Err(format!("Requisition {} is {} and can no longer be rejected", requisition.requisition_no, requisition.status));
+    }
+
+    sqlx::query("UPDATE requisition_line SET approved_qty = 0 WHERE requisition_id = ?")
+        .bind(requisition_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE requisition SET reviewed_by = ?, reviewed_at = NOW() WHERE requisition_id = ?")
+        .bind(&user)
+        .bind(requisition_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    set_requisition_status(&mut tx, &requisition, "REJECTED", "REJECTED", comment.as_deref(), &user).await?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_requisition(&state.pool, requisition_id).await
+}
+
+#[tauri::command]
+pub async fn cancel_requisition(
+    state: tauri::State<'_, AppState>,
+    requisition_id: i32,
+    comment: Option<String>,
+    user: String,
+) -> Result<RequisitionDocument, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let requisition = lock_requisition(&mut tx, requisition_id).await?;
+    if !matches!(requisition.status.as_str(), "PENDING" | "APPROVED" | "PARTIALLY_APPROVED") {
+        return Err(format!("Requisition {} is {} and can no longer be cancelled", requisition.requisition_no, requisition.status));
+    }
+
+    set_requisition_status(&mut tx, &requisition, "CANCELLED", "CANCELLED", comment.as_deref(), &user).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_requisition(&state.pool, requisition_id).await
+}
+
+/// Issues approved requisition lines through the normal issue-slip path. A
+/// requisition can be fulfilled over several slips until every approved
+/// quantity has gone out.
+#[tauri::command]
+pub async fn fulfil_requisition(
+    state: tauri::State<'_, AppState>,
+    requisition_id: i32,
+    issued_to: String,
+    work_order: Option<String>,
+    reference: Option<String>,
+    lines: Vec<FulfilmentLine>,
+    user: String,
+) -> Result<IssueSlipDocument, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let requisition = lock_requisition(&mut tx, requisition_id).await?;
+    if !matches!(requisition.status.as_str(), "APPROVED" | "PARTIALLY_APPROVED" | "PARTIALLY_ISSUED") {
+        return Err(format!("Requisition {} is {} and cannot be issued against", requisition.requisition_no, requisition.status));
+    }
+
+    let rows = sqlx::query("SELECT line_id, line_no, stock_id, COALESCE(approved_qty, 0) as approved_qty, issued_qty FROM requisition_line WHERE requisition_id = ? FOR UPDATE")
+        .bind(requisition_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut open_lines: HashMap<i32, (i32, String, Decimal)> = HashMap::new();
+    for row in rows {
+        let approved: Decimal = row.get("approved_qty");
+        let issued: Decimal = row.get("issued_qty");
+        open_lines.insert(row.get("line_id"), (row.get("line_no"), row.get("stock_id"), approved - issued));
+    }
+
+    let mut errors = Vec::new();
+    let mut issue_lines = Vec::new();
+    for line in &lines {
+        match open_lines.get_mut(&line.line_id) {
+            Some((line_no, stock_id, outstanding)) => {
+                if line.quantity <= Decimal::ZERO {
+                    errors.push(format!("Requisition line {}: quantity must be greater than zero", line_no));
+                } else if line.quantity > *outstanding {
+                    errors.push(format!("Requisition line {}: only {} outstanding", line_no, outstanding));
+                } else {
+                    *outstanding -= line.quantity;
+                    issue_lines.push(IssueLineInput { stock_id: stock_id.clone(), quantity: line.quantity });
+                }
+            }
+            None => errors.push(format!("Line {} is not on requisition {}", line.line_id, requisition.requisition_no)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    let header = IssueSlipHeader {
+        issued_to,
+        project: requisition.project.clone(),
+        work_order,
+        reference,
+        remarks: Some(format!("Against requisition {}", requisition.requisition_no)),
+    };
+    let (slip_id, slip_no) = post_issue_slip(&mut tx, &header, &issue_lines, &user, Some(requisition_id)).await?;
+
+    for line in &lines {
+        sqlx::query("UPDATE requisition_line SET issued_qty = issued_qty + ? WHERE line_id = ?")
+            .bind(line.quantity)
+            .bind(line.line_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let fully_issued = open_lines.values().all(|(_, _, outstanding)| *outstanding <= Decimal::ZERO);
+    let to_status = if fully_issued { "FULFILLED" } else { "PARTIALLY_ISSUED" };
+    set_requisition_status(&mut tx, &requisition, "ISSUED", to_status, Some(&format!("Issue slip {}", slip_no)), &user).await?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    load_issue_slip(&state.pool, slip_id).await
+}
+
+#[tauri::command]
+pub async fn get_requisition(
+    state: tauri::State<'_, AppState>,
+    requisition_no: String,
+) -> Result<RequisitionDocument, String> {
+    let requisition_id: i32 = sqlx::query_scalar("SELECT requisition_id FROM requisition WHERE requisition_no = ?")
+        .bind(requisition_no.trim())
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Requisition {} not found", requisition_no))?;
+
+    load_requisition(&state.pool, requisition_id).await
+}
+
+#[derive(Serialize)]
+pub struct OutstandingRequisitionResponse {
+    pub items: Vec<OutstandingRequisition>,
+    pub total_count: i64,
+}
+
+/// Requisitions still waiting on a review or an issue, oldest first.
+#[tauri::command]
+pub async fn get_outstanding_requisitions(
+    state: tauri::State<'_, AppState>,
+    page: i32,
+    page_size: i32,
+    search: Option<String>,
+) -> Result<OutstandingRequisitionResponse, String> {
+    let offset = (page - 1) * page_size;
+    let pattern = format!("%{}%", search.unwrap_or_default().trim().to_lowercase());
+    let where_clause = "WHERE r.status IN ('PENDING', 'APPROVED', 'PARTIALLY_APPROVED', 'PARTIALLY_ISSUED')
+        AND (LOWER(r.requisition_no) LIKE ? OR LOWER(r.project) LIKE ? OR LOWER(r.requested_by) LIKE ?)";
+
+    let total_count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM requisition r {}", where_clause))
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let items = sqlx::query_as::<sqlx::MySql, OutstandingRequisition>(&format!(
+        "SELECT r.*,
+            COUNT(rl.line_id) as line_count,
+            COALESCE(SUM(rl.requested_qty), 0) as requested_total,
+            COALESCE(SUM(COALESCE(rl.approved_qty, 0)), 0) as approved_total,
+            COALESCE(SUM(rl.issued_qty), 0) as issued_total
+        FROM requisition r
+        LEFT JOIN requisition_line rl ON rl.requisition_id = r.requisition_id
+        {}
+        GROUP BY r.requisition_id
+        ORDER BY r.created_at ASC LIMIT ? OFFSET ?",
+        where_clause
+    ))
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(OutstandingRequisitionResponse { items, total_count })
+}
+
+/// Flags reservations whose expiry has passed so they drop out of listings.
+async fn expire_reservations(pool: &MySqlPool) -> Result<(), String> {
+    sqlx::query("UPDATE stock_reservation SET status = 'EXPIRED' WHERE status = 'ACTIVE' AND expires_at IS NOT NULL AND expires_at <= NOW()")
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Earmarks stock for a project without issuing it. Without an explicit expiry
+/// the reservation lapses after the `reservation_expiry_days` setting.
+#[tauri::command]
+pub async fn create_reservation(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    project: String,
+    quantity: Decimal,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    reference: Option<String>,
+    user: String,
+) -> Result<StockReservation, String> {
+    if project.trim().is_empty() {
+        return Err("Project is required".to_string());
+    }
+    if quantity <= Decimal::ZERO {
+        return Err("Quantity must be greater than zero".to_string());
+    }
+    expire_reservations(&state.pool).await?;
+
+    let expires_at = match expires_at {
+        Some(at) => at,
+        None => {
+            let days = setting_days(&state.pool, "reservation_expiry_days", 30).await?;
+            chrono::Utc::now() + chrono::Duration::days(days)
+        }
+    };
+    if expires_at <= chrono::Utc::now() {
+        return Err("Expiry must be in the future".to_string());
+    }
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    if identity_of(&mut *tx, &stock_id).await?.is_none() {
+        return Err(format!("Unknown stock item {}", stock_id));
+    }
+    let on_hand = available_for_identity(&mut *tx, &stock_id, true).await?;
+    let reserved = reserved_for_identity(&mut *tx, &stock_id, None).await?;
+    if on_hand - reserved < quantity {
+        return Err(format!("Only {} available to reserve", on_hand - reserved));
+    }
+
+    let reservation_id = sqlx::query("INSERT INTO stock_reservation (stock_id, project, quantity, expires_at, reference, created_by) VALUES (?, ?, ?, ?, ?, ?)")
+        .bind(&stock_id)
+        .bind(project.trim())
+        .bind(quantity)
+        .bind(expires_at)
+        .bind(&reference)
+        .bind(&user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .last_insert_id() as i32;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    sqlx::query_as::<sqlx::MySql, StockReservation>(
+        "SELECT r.*, m.part_name, m.description, m.uom, m.location
+         FROM stock_reservation r JOIN stock_master m ON r.stock_id = m.stock_id
+         WHERE r.reservation_id = ?"
+    )
+    .bind(reservation_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn release_reservation(
+    state: tauri::State<'_, AppState>,
+    reservation_id: i32,
+    user: String,
+) -> Result<(), String> {
+    let result = sqlx::query("UPDATE stock_reservation SET status = 'RELEASED', reference = CONCAT(COALESCE(reference, ''), ' | Released by ', ?) WHERE reservation_id = ? AND status = 'ACTIVE'")
+        .bind(&user)
+        .bind(reservation_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.rows_affected() == 0 {
+        return Err("Reservation not found or no longer active".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_reservations(
+    state: tauri::State<'_, AppState>,
+    stock_id: Option<String>,
+    project: Option<String>,
+    include_closed: Option<bool>,
+) -> Result<Vec<StockReservation>, String> {
+    expire_reservations(&state.pool).await?;
+
+    let mut query = String::from(
+        "SELECT r.*, m.part_name, m.description, m.uom, m.location
+         FROM stock_reservation r JOIN stock_master m ON r.stock_id = m.stock_id
+         WHERE 1=1"
+    );
+    if !include_closed.unwrap_or(false) {
+        query.push_str(" AND r.status = 'ACTIVE'");
+    }
+    if stock_id.is_some() {
+        query.push_str(" AND r.stock_id = ?");
+    }
+    if project.is_some() {
+        query.push_str(" AND LOWER(TRIM(r.project)) = LOWER(TRIM(?))");
+    }
+    query.push_str(" ORDER BY r.created_at DESC");
+
+    let mut q = sqlx::query_as::<sqlx::MySql, StockReservation>(&query);
+    if let Some(id) = stock_id {
+        q = q.bind(id);
+    }
+    if let Some(p) = project {
+        q = q.bind(p);
+    }
+
+    q.fetch_all(&state.pool).await.map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThresholdInput {
+    pub min_quantity: Decimal,
+    #[serde(default)]
+    pub reorder_point: Decimal,
+    #[serde(default)]
+    pub reorder_quantity: Decimal,
+    #[serde(default)]
+    pub slow_moving_days: Option<i32>,
+    #[serde(default)]
+    pub dead_stock_days: Option<i32>,
+}
+
+/// One setting of a `ThresholdInput`, to say which one failed validation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdField {
+    MinQuantity,
+    ReorderPoint,
+    ReorderQuantity,
+    SlowMovingDays,
+    DeadStockDays,
+}
+
+impl ThresholdField {
+    /// Column heading in the threshold sheet.
+    pub const fn heading(self) -> &'static str {
+        match self {
+            ThresholdField::MinQuantity => "Min Qty",
+            ThresholdField::ReorderPoint => "Reorder Point",
+            ThresholdField::ReorderQuantity => "Reorder Qty",
+            ThresholdField::SlowMovingDays => "Slow Moving Days",
+            ThresholdField::DeadStockDays => "Dead Stock Days",
+        }
+    }
+
+    fn value(self, input: &ThresholdInput) -> String {
+        match self {
+            ThresholdField::MinQuantity => input.min_quantity.to_string(),
+            ThresholdField::ReorderPoint => input.reorder_point.to_string(),
+            ThresholdField::ReorderQuantity => input.reorder_quantity.to_string(),
+            ThresholdField::SlowMovingDays => input.slow_moving_days.map(|d| d.to_string()).unwrap_or_default(),
+            ThresholdField::DeadStockDays => input.dead_stock_days.map(|d| d.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+fn validate_threshold(input: &ThresholdInput) -> Result<(), (ThresholdField, String)> {
+    for (field, quantity) in [
+        (ThresholdField::MinQuantity, input.min_quantity),
+        (ThresholdField::ReorderPoint, input.reorder_point),
+        (ThresholdField::ReorderQuantity, input.reorder_quantity),
+    ] {
+        if quantity < Decimal::ZERO {
+            return Err((field, "Quantities cannot be negative".to_string()));
+        }
+    }
+    for (field, days) in [
+        (ThresholdField::SlowMovingDays, input.slow_moving_days),
+        (ThresholdField::DeadStockDays, input.dead_stock_days),
+    ] {
+        if days.is_some_and(|d| d <= 0) {
+            return Err((field, "Day limits must be greater than zero".to_string()));
+        }
+    }
+    if let (Some(slow), Some(dead)) = (input.slow_moving_days, input.dead_stock_days) {
+        if dead < slow {
+            return Err((
+                ThresholdField::DeadStockDays,
+                "Dead stock days cannot be less than slow moving days".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Writes the threshold onto every stock_master row of the item's identity set,
+/// since readers take the MAX across the group. Day limits left as `None` keep
+/// their current (or default) values.
+async fn upsert_threshold<'e, E>(executor: E, stock_id: &str, input: &ThresholdInput, user: &str) -> Result<(), String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let query = format!(
+        "INSERT INTO stock_threshold (stock_id, min_quantity, reorder_point, reorder_quantity, slow_moving_days, dead_stock_days, updated_by)
+         SELECT m.stock_id, ?, ?, ?, COALESCE(?, 30), COALESCE(?, 90), ?
+         FROM stock_master m
+         JOIN stock_master target ON {}
+         WHERE target.stock_id = ?
+         ON DUPLICATE KEY UPDATE
+            min_quantity = VALUES(min_quantity),
+            reorder_point = VALUES(reorder_point),
+            reorder_quantity = VALUES(reorder_quantity),
+            slow_moving_days = IF(? IS NULL, stock_threshold.slow_moving_days, VALUES(slow_moving_days)),
+            dead_stock_days = IF(? IS NULL, stock_threshold.dead_stock_days, VALUES(dead_stock_days)),
+            updated_by = VALUES(updated_by)",
+        identity_match("m", "target")
+    );
+
+    sqlx::query(&query)
+        .bind(input.min_quantity)
+        .bind(input.reorder_point)
+        .bind(input.reorder_quantity)
+        .bind(input.slow_moving_days)
+        .bind(input.dead_stock_days)
+        .bind(user)
+        .bind(stock_id)
+        .bind(input.slow_moving_days)
+        .bind(input.dead_stock_days)
+        .execute(executor)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_item_threshold(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    threshold: ThresholdInput,
+    user: String,
+) -> Result<(), String> {
+    validate_threshold(&threshold).map_err(|(_, message)| message)?;
+    if identity_of(&state.pool, &stock_id).await?.is_none() {
+        return Err(format!("Unknown stock item {}", stock_id));
+    }
+    upsert_threshold(&state.pool, &stock_id, &threshold, &user).await
+}
+
+/// One spreadsheet row of a bulk threshold update. The item is found by
+/// `stock_id` when given, otherwise by its identity fields.
+#[derive(Debug)]
+pub struct ThresholdImportRow {
+    pub stock_id: Option<String>,
+    pub project: Option<String>,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+    pub location: Option<String>,
+    pub threshold: ThresholdInput,
+}
+
+#[derive(Serialize)]
+pub struct ThresholdImportResult {
+    pub updated: usize,
+    pub errors: Vec<CellError>,
+}
+
+/// Applies thresholds from an xlsx/xls/ods/csv file on disk. Bad cells, and rows
+/// that match no item, are reported by sheet row and column; the rest are written
+/// in one transaction.
+#[tauri::command]
+pub async fn bulk_set_thresholds(
+    state: tauri::State<'_, AppState>,
+    file_path: String,
+    user: String,
+) -> Result<ThresholdImportResult, String> {
+    let parsed = tauri::async_runtime::spawn_blocking(move || {
+        importer::parse_threshold_sheet(&importer::read_sheet(std::path::Path::new(&file_path))?)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let mut updated = 0;
+    let mut errors = parsed.errors;
+
+    for (sheet_row, row) in &parsed.rows {
+        if let Err((field, message)) = validate_threshold(&row.threshold) {
+            errors.push(CellError {
+                sheet_row: *sheet_row,
+                column: field.heading().to_string(),
+                value: field.value(&row.threshold),
+                message,
+            });
+            continue;
+        }
+
+        let (column, value, stock_id) = match row.stock_id.as_deref() {
+            Some(id) => (
+                "Stock ID",
+                id.to_string(),
+                identity_of(&mut *tx, id).await?.map(|_| id.to_string()),
+            ),
+            None => (
+                "Part Name",
+                row.part_name.clone().unwrap_or_default(),
+                sqlx::query_scalar::<sqlx::MySql, String>(
+                    "SELECT stock_id FROM stock_master WHERE merged_into IS NULL AND
+                        LOWER(TRIM(project)) = ? AND 
+                        LOWER(TRIM(part_name)) = ? AND 
+                        LOWER(TRIM(uom)) = ? AND 
+                        LOWER(TRIM(description)) = ? AND 
+                        LOWER(TRIM(location)) = ?
+                     LIMIT 1"
+                )
+                .bind(normalize_string(row.project.as_deref().unwrap_or_default()))
+                .bind(normalize_string(row.part_name.as_deref().unwrap_or_default()))
+                .bind(normalize_string(row.uom.as_deref().unwrap_or_default()))
+                .bind(normalize_string(row.description.as_deref().unwrap_or_default()))
+                .bind(normalize_string(row.location.as_deref().unwrap_or_default()))
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?,
+            ),
+        };
+
+        let Some(stock_id) = stock_id else {
+            errors.push(CellError {
+                sheet_row: *sheet_row,
+                column: column.to_string(),
+                value,
+                message: "No matching item".to_string(),
+            });
+            continue;
+        };
+        upsert_threshold(&mut *tx, &stock_id, &row.threshold, &user).await?;
+        updated += 1;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    errors.sort_by_key(|e| e.sheet_row);
+    Ok(ThresholdImportResult { updated, errors })
+}
+
+/// Suggested order for an item whose inventory position (on hand, less reserved,
+/// plus open PO quantity) is at or below its reorder point: whole multiples of the
+/// reorder quantity until the position is back above the point. Without a reorder
+/// quantity, the gap plus one month of forecast demand.
+fn suggested_order_quantity(
+    position: Decimal,
+    reorder_point: Decimal,
+    reorder_quantity: Decimal,
+    monthly_forecast: Option<Decimal>,
+) -> Decimal {
+    if position > reorder_point {
+        return Decimal::ZERO;
+    }
+    let shortfall = reorder_point - position;
+    if reorder_quantity <= Decimal::ZERO {
+        return (shortfall + monthly_forecast.unwrap_or_default()).ceil().max(Decimal::ONE);
+    }
+    let lots = (shortfall / reorder_quantity).floor() + Decimal::ONE;
+    lots * reorder_quantity
+}
+
+/// Items at or below their reorder point with a suggested order quantity.
+/// Open purchase order quantities count towards the position, so an item already
+/// on order may show a suggestion of zero.
+#[tauri::command]
+pub async fn get_reorder_suggestions(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ReorderSuggestion>, String> {
+    let query = format!(
+        "SELECT 
+            MAX(m.stock_id) as stock_id,
+            MAX(m.project) as project,
+            MAX(m.part_name) as part_name,
+            MAX(m.description) as description,
+            MAX(m.uom) as uom,
+            MAX(m.location) as location,
+            (
+                SELECT COALESCE(SUM(l2.quantity_change), 0)
+                FROM stock_ledger l2
+                JOIN stock_master m2 ON l2.stock_id = m2.stock_id
+                WHERE {}
+            ) as on_hand_quantity,
+            (
+                SELECT COALESCE(SUM(r.quantity - r.consumed_qty), 0)
+                FROM stock_reservation r
+                JOIN stock_master m3 ON r.stock_id = m3.stock_id
+                WHERE {} AND r.status = 'ACTIVE' AND (r.expires_at IS NULL OR r.expires_at > NOW())
+            ) as reserved_quantity,
+            (
+                SELECT COALESCE(SUM(GREATEST(p.ordered_qty - p.received_qty, 0)), 0)
+                FROM purchase_order_line p
+                JOIN stock_master m4 ON p.stock_id = m4.stock_id
+                WHERE {} AND p.status = 'OPEN'
+            ) as on_order_quantity,
+            MAX(COALESCE(t.min_quantity, 0)) as min_quantity,
+            MAX(COALESCE(t.reorder_point, 0)) as reorder_point,
+            MAX(COALESCE(t.reorder_quantity, 0)) as reorder_quantity,
+            LOWER(TRIM(MAX(m.project))) as key_project,
+            LOWER(TRIM(MAX(m.part_name))) as key_part_name,
+            LOWER(TRIM(MAX(m.description))) as key_description,
+            LOWER(TRIM(MAX(m.uom))) as key_uom,
+            LOWER(TRIM(MAX(m.location))) as key_location
+        FROM stock_master m
+        LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
+        WHERE m.merged_into IS NULL AND m.status = 'ACTIVE'
+        GROUP BY m.project, m.part_name, m.uom, m.location, m.description
+        HAVING reorder_point > 0 AND on_hand_quantity - reserved_quantity <= reorder_point
+        ORDER BY MAX(m.part_name)",
+        identity_match("m2", "m"),
+        identity_match("m3", "m"),
+        identity_match("m4", "m"),
+    );
+
+    let rows = sqlx::query(&query)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (since, months) = forecast_months(FORECAST_HISTORY_MONTHS);
+    let history = monthly_issues(&state.pool, since, None, None).await?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut item = ReorderSuggestion::from_row(&row).map_err(|e| e.to_string())?;
+        let key: IdentityKey = (
+            row.get("key_project"),
+            row.get("key_part_name"),
+            row.get("key_description"),
+            row.get("key_uom"),
+            row.get("key_location"),
+        );
+        let model = forecast::best_forecast(&demand_series(&months, history.get(&key)));
+        let monthly = Decimal::from_f64_retain(model.next).unwrap_or_default().round_dp(4);
+        let available = item.on_hand_quantity - item.reserved_quantity;
+
+        item.monthly_forecast = Some(monthly);
+        item.days_of_cover = days_of_cover(available, monthly);
+        let position = available + item.on_order_quantity;
+        item.suggested_quantity = suggested_order_quantity(position, item.reorder_point, item.reorder_quantity, Some(monthly));
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurchaseOrderLineInput {
+    pub stock_id: String,
+    pub quantity: Decimal,
+}
+
+/// Records an open purchase order so its quantity counts as on order. Lines are
+/// received automatically when an import row carries the same PO number.
+#[tauri::command]
+pub async fn create_purchase_order(
+    state: tauri::State<'_, AppState>,
+    po_no: String,
+    supplier_name: String,
+    expected_date: Option<chrono::NaiveDate>,
+    lines: Vec<PurchaseOrderLineInput>,
+    user: String,
+) -> Result<(), String> {
+    if po_no.trim().is_empty() || supplier_name.trim().is_empty() {
+        return Err("PO number and supplier are required".to_string());
+    }
+    if lines.is_empty() {
+        return Err("A purchase order needs at least one line".to_string());
+    }
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    for (idx, line) in lines.iter().enumerate() {
+        if line.quantity <= Decimal::ZERO {
+            return Err(format!("Line {}: quantity must be greater than zero", idx + 1));
+        }
+        if identity_of(&mut *tx, &line.stock_id).await?.is_none() {
+            return Err(format!("Line {}: unknown stock item {}", idx + 1, line.stock_id));
+        }
+        sqlx::query("INSERT INTO purchase_order_line (po_no, supplier_name, stock_id, ordered_qty, expected_date, created_by) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(po_no.trim())
+            .bind(supplier_name.trim())
+            .bind(&line.stock_id)
+            .bind(line.quantity)
+            .bind(expected_date)
+            .bind(&user)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn close_purchase_order_line(
+    state: tauri::State<'_, AppState>,
+    po_line_id: i32,
+) -> Result<(), String> {
+    let result = sqlx::query("UPDATE purchase_order_line SET status = 'CLOSED', closed_by = 'MANUAL' WHERE po_line_id = ? AND status = 'OPEN'")
+        .bind(po_line_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.rows_affected() == 0 {
+        return Err("Purchase order line not found or already closed".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_open_purchase_orders(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PurchaseOrderLine>, String> {
+    sqlx::query_as::<sqlx::MySql, PurchaseOrderLine>(
+        "SELECT p.*, m.part_name, m.description, m.uom
+         FROM purchase_order_line p JOIN stock_master m ON p.stock_id = m.stock_id
+         WHERE p.status = 'OPEN'
+         ORDER BY p.expected_date IS NULL, p.expected_date, p.po_no"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Books receipts against the oldest open PO line for the same PO number and item
+/// identity, closing each line once the ordered quantity has arrived. `receipts` are
+/// (identity, PO number, quantity) in posting order; returns the PO line each one
+/// was booked against.
+async fn receive_against_purchase_orders(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    receipts: &[(IdentityKey, String, Decimal)],
+) -> Result<Vec<Option<i32>>, String> {
+    let mut po_numbers: Vec<String> = receipts
+        .iter()
+        .map(|(_, po_no, _)| normalize_string(po_no))
+        .filter(|po_no| !po_no.is_empty())
+        .collect();
+    po_numbers.sort();
+    po_numbers.dedup();
+
+    // (po_line_id, identity, PO number, still to receive, open)
+    let mut open_lines: Vec<(i32, IdentityKey, String, Decimal, bool)> = Vec::new();
+    for chunk in po_numbers.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT p.po_line_id, LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.description)),
+                    LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)), LOWER(TRIM(p.po_no)), p.ordered_qty - p.received_qty
+             FROM purchase_order_line p
+             JOIN stock_master m ON p.stock_id = m.stock_id
+             WHERE p.status = 'OPEN' AND LOWER(TRIM(p.po_no)) IN "
+        );
+        builder.push_tuples(chunk, |mut b, po_no| {
+            b.push_bind(po_no);
+        });
+        builder.push(" FOR UPDATE");
+        let rows = builder.build().fetch_all(&mut **tx).await.map_err(|e| e.to_string())?;
+        for row in rows {
+            let identity: IdentityKey = (row.get(1), row.get(2), row.get(3), row.get(4), row.get(5));
+            open_lines.push((row.get(0), identity, row.get(6), row.get(7), true));
+        }
+    }
+    open_lines.sort_by_key(|line| line.0);
+
+    // Allocate in order, as posting one receipt at a time would
+    let mut received: HashMap<i32, Decimal> = HashMap::new();
+    let mut booked = Vec::with_capacity(receipts.len());
+    for (identity, po_no, quantity) in receipts {
+        let po_no = normalize_string(po_no);
+        let line = open_lines
+            .iter_mut()
+            .find(|line| line.4 && line.1 == *identity && line.2 == po_no);
+        booked.push(line.map(|line| {
+            line.3 -= *quantity;
+            line.4 = line.3 > Decimal::ZERO;
+            *received.entry(line.0).or_default() += *quantity;
+            line.0
+        }));
+    }
+
+    let received: Vec<(i32, Decimal)> = received.into_iter().collect();
+    for chunk in received.chunks(IMPORT_CHUNK_SIZE) {
+        // MySQL applies the assignments left to right, so status sees the new received_qty
+        // and closed_by the new status
+        let mut builder =
+            sqlx::QueryBuilder::<sqlx::MySql>::new("UPDATE purchase_order_line SET received_qty = received_qty + CASE po_line_id");
+        for (po_line_id, quantity) in chunk {
+            builder.push(" WHEN ").push_bind(*po_line_id).push(" THEN ").push_bind(*quantity);
+        }
+        builder.push(
+            " END, status = IF(received_qty >= ordered_qty, 'CLOSED', status),
+             closed_by = IF(status = 'CLOSED', COALESCE(closed_by, 'RECEIPT'), closed_by)
+             WHERE po_line_id IN "
+        );
+        builder.push_tuples(chunk, |mut b, (po_line_id, _)| {
+            b.push_bind(*po_line_id);
+        });
+        builder.build().execute(&mut **tx).await.map_err(|e| e.to_string())?;
+    }
+
+    Ok(booked)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClassificationParams {
+    /// Consumption window in days before the current month (default 365), widened
+    /// to start on the first of a month
+    #[serde(default)]
+    pub window_days: Option<i64>,
+    /// Cumulative value share that closes class A, in percent (default 80)
+    #[serde(default)]
+    pub a_percent: Option<Decimal>,
+    /// Cumulative value share that closes class B, in percent (default 95)
+    #[serde(default)]
+    pub b_percent: Option<Decimal>,
+    /// Highest coefficient of variation still counted as X (default 0.5)
+    #[serde(default)]
+    pub x_cv: Option<f64>,
+    /// Highest coefficient of variation still counted as Y (default 1.0)
+    #[serde(default)]
+    pub y_cv: Option<f64>,
+    /// Cycle-count interval in days for A, B and C items (default 30/90/180)
+    #[serde(default)]
+    pub count_interval_days: Option<[i32; 3]>,
+}
+
+#[derive(Serialize)]
+pub struct ClassificationSummary {
+    pub item_count: usize,
+    pub abc_counts: HashMap<String, usize>,
+    pub xyz_counts: HashMap<String, usize>,
+    pub window_days: i64,
+    /// False when no receipt in the window had a unit cost, so items were ranked by quantity
+    pub valued_by_cost: bool,
+}
+
+/// Issued quantity per item identity and calendar month (`YYYY-MM`) since `since`,
+/// ignoring reversed issues. Optionally limited to one item's identity and/or to
+/// the consuming project (the issue slip's project, else the item's own project).
+async fn monthly_issues(
+    pool: &MySqlPool,
+    since: chrono::DateTime<chrono::Utc>,
+    stock_id: Option<&str>,
+    project: Option<&str>,
+) -> Result<HashMap<IdentityKey, HashMap<String, Decimal>>, String> {
+    let mut query = String::from(
+        "SELECT LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.description)), LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)),
+                DATE_FORMAT(l.transaction_date, '%Y-%m'), SUM(-l.quantity_change)
+         FROM stock_ledger l
+         JOIN stock_master m ON l.stock_id = m.stock_id
+         LEFT JOIN issue_slip_line sl ON sl.ledger_id = l.ledger_id
+         LEFT JOIN issue_slip s ON s.slip_id = sl.slip_id"
+    );
+    if stock_id.is_some() {
+        query.push_str(&format!(" JOIN stock_master target ON {}", identity_match("m", "target")));
+    }
+    query.push_str(
+        " WHERE l.transaction_type = 'OUT' AND l.transaction_date >= ?
+           AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)"
+    );
+    if stock_id.is_some() {
+        query.push_str(" AND target.stock_id = ?");
+    }
+    if project.is_some() {
+        query.push_str(" AND LOWER(TRIM(COALESCE(s.project, m.project))) = LOWER(TRIM(?))");
+    }
+    query.push_str(" GROUP BY 1, 2, 3, 4, 5, 6");
+
+    let mut q = sqlx::query(&query).bind(since);
+    if let Some(id) = stock_id {
+        q = q.bind(id);
+    }
+    if let Some(p) = project {
+        q = q.bind(p);
+    }
+    let rows = q.fetch_all(pool).await.map_err(|e| e.to_string())?;
+
+    let mut monthly: HashMap<IdentityKey, HashMap<String, Decimal>> = HashMap::new();
+    for row in rows {
+        let key: IdentityKey = (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4));
+        monthly.entry(key).or_default().insert(row.get(5), row.get(6));
+    }
+    Ok(monthly)
+}
+
+/// Weighted average unit cost of costed receipts, per identity.
+async fn average_receipt_costs(pool: &MySqlPool) -> Result<HashMap<IdentityKey, Decimal>, String> {
+    let rows = sqlx::query(
+        "SELECT LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.description)), LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)),
+                SUM(l.quantity_change * l.unit_cost) / NULLIF(SUM(l.quantity_change), 0)
+         FROM stock_ledger l
+         JOIN stock_master m ON l.stock_id = m.stock_id
+         WHERE l.transaction_type = 'IN' AND l.unit_cost IS NOT NULL
+         GROUP BY 1, 2, 3, 4, 5"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut costs: HashMap<IdentityKey, Decimal> = HashMap::new();
+    for row in rows {
+        let cost: Option<Decimal> = row.get(5);
+        if let Some(cost) = cost {
+            costs.insert((row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)), cost);
+        }
+    }
+    Ok(costs)
+}
+
+/// The last `months` complete calendar months, oldest first, with the instant the
+/// first one starts. The current, partial month is left out so it can't drag the
+/// forecast down.
+fn forecast_months(months: u32) -> (chrono::DateTime<chrono::Utc>, Vec<String>) {
+    let today = chrono::Utc::now().date_naive();
+    let this_month = today - chrono::Duration::days(i64::from(today.day0()));
+    let start = this_month
+        .checked_sub_months(chrono::Months::new(months.max(1)))
+        .unwrap_or(this_month);
+    let last = this_month - chrono::Duration::days(1);
+    (start.and_time(chrono::NaiveTime::MIN).and_utc(), month_keys(start, last))
+}
+
+fn demand_series(months: &[String], history: Option<&HashMap<String, Decimal>>) -> Vec<f64> {
+    months
+        .iter()
+        .map(|month| history.and_then(|h| h.get(month)).and_then(|q| q.to_f64()).unwrap_or(0.0))
+        .collect()
+}
+
+/// Calendar months (`YYYY-MM`) from the month of `from` through the month of `to`.
+fn month_keys(from: chrono::NaiveDate, to: chrono::NaiveDate) -> Vec<String> {
+    let mut keys = Vec::new();
+    let (mut year, mut month) = (from.year(), from.month());
+    while (year, month) <= (to.year(), to.month()) {
+        keys.push(format!("{:04}-{:02}", year, month));
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    keys
+}
+
+/// Re-ranks every item by consumption value (ABC) and monthly demand variability
+/// (XYZ) over the window, replacing the stored classification. Consumption is
+/// valued at weighted average receipt cost; if no receipts carry a cost, items are
+/// ranked by quantity instead. Each class maps to a cycle-count interval.
+#[tauri::command]
+pub async fn recompute_item_classification(
+    state: tauri::State<'_, AppState>,
+    params: Option<ClassificationParams>,
+) -> Result<ClassificationSummary, String> {
+    let params = params.unwrap_or_default();
+    let window_days = params.window_days.filter(|d| *d > 0).unwrap_or(365);
+    let a_percent = params.a_percent.unwrap_or(Decimal::from(80));
+    let b_percent = params.b_percent.unwrap_or(Decimal::from(95));
+    if a_percent <= Decimal::ZERO || b_percent < a_percent || b_percent > Decimal::ONE_HUNDRED {
+        return Err("Class limits must satisfy 0 < A <= B <= 100".to_string());
+    }
+    let x_cv = params.x_cv.unwrap_or(0.5);
+    let y_cv = params.y_cv.unwrap_or(1.0);
+    if !(x_cv >= 0.0 && x_cv <= y_cv) {
+        return Err("Variability limits must satisfy 0 <= X <= Y".to_string());
+    }
+    let [interval_a, interval_b, interval_c] = params.count_interval_days.unwrap_or([30, 90, 180]);
+
+    // Complete calendar months only, as for forecasts: the window ends before the
+    // current month and starts on the first of the month it reaches back into
+    let today = chrono::Utc::now().date_naive();
+    let this_month = today - chrono::Duration::days(i64::from(today.day0()));
+    let reach = this_month - chrono::Duration::days(window_days);
+    let first_month = reach - chrono::Duration::days(i64::from(reach.day0()));
+    let window_start = first_month.and_time(chrono::NaiveTime::MIN).and_utc();
+    let months = month_keys(first_month, this_month - chrono::Duration::days(1));
+
+    // 1. Every item identity and the stock rows that make it up
+    let rows = sqlx::query(
+        "SELECT stock_id, LOWER(TRIM(project)), LOWER(TRIM(part_name)), LOWER(TRIM(description)), LOWER(TRIM(uom)), LOWER(TRIM(location))
+         FROM stock_master WHERE merged_into IS NULL"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut keys: Vec<IdentityKey> = Vec::new();
+    let mut members: HashMap<IdentityKey, Vec<String>> = HashMap::new();
+    for row in rows {
+        let key: IdentityKey = (row.get(1), row.get(2), row.get(3), row.get(4), row.get(5));
+        let entry = members.entry(key.clone()).or_default();
+        if entry.is_empty() {
+            keys.push(key);
+        }
+        entry.push(row.get(0));
+    }
+
+    // 2. Monthly issued quantity per identity within the window
+    let monthly = monthly_issues(&state.pool, window_start, None, None).await?;
+
+    // 3. Weighted average receipt cost per identity
+    let costs = average_receipt_costs(&state.pool).await?;
+
+    // 4. Rank, leaving out issues made so far this month
+    let quantities: Vec<Decimal> = keys
+        .iter()
+        .map(|k| {
+            let history = monthly.get(k);
+            months.iter().filter_map(|month| history.and_then(|h| h.get(month))).sum()
+        })
+        .collect();
+    let valued_by_cost = keys.iter().zip(&quantities).any(|(k, q)| !q.is_zero() && costs.contains_key(k));
+    let values: Vec<Decimal> = keys
+        .iter()
+        .zip(&quantities)
+        .map(|(k, q)| if valued_by_cost { *q * costs.get(k).copied().unwrap_or_default() } else { *q })
+        .collect();
+    let abc = classification::abc_classes(&values, a_percent, b_percent);
+
+    let mut abc_counts: HashMap<String, usize> = HashMap::new();
+    let mut xyz_counts: HashMap<String, usize> = HashMap::new();
+    let mut records = Vec::new();
+    for (idx, key) in keys.iter().enumerate() {
+        let series = demand_series(&months, monthly.get(key));
+        let cv = classification::coefficient_of_variation(&series);
+        let xyz = classification::xyz_class(cv, x_cv, y_cv);
+        let interval = match abc[idx] {
+            'A' => interval_a,
+            'B' => interval_b,
+            _ => interval_c,
+        };
+        *abc_counts.entry(abc[idx].to_string()).or_default() += 1;
+        *xyz_counts.entry(xyz.to_string()).or_default() += 1;
+
+        let cv = cv.and_then(Decimal::from_f64_retain).map(|c| c.round_dp(4));
+        for stock_id in &members[key] {
+            records.push((stock_id.clone(), abc[idx], xyz, quantities[idx], values[idx].round_dp(4), cv, interval));
+        }
+    }
+
+    // 5. Replace the stored classification
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM item_classification")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    for chunk in records.chunks(500) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO item_classification (stock_id, abc_class, xyz_class, consumption_qty, consumption_value, demand_cv, count_interval_days, window_days) "
+        );
+        builder.push_values(chunk, |mut b, (stock_id, abc, xyz, qty, value, cv, interval)| {
+            b.push_bind(stock_id)
+                .push_bind(abc.to_string())
+                .push_bind(xyz.to_string())
+                .push_bind(qty)
+                .push_bind(value)
+                .push_bind(cv)
+                .push_bind(interval)
+                .push_bind(window_days as i32);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(ClassificationSummary {
+        item_count: keys.len(),
+        abc_counts,
+        xyz_counts,
+        window_days,
+        valued_by_cost,
+    })
+}
+
+/// Complete months of issue history fed to the forecaster
+const FORECAST_HISTORY_MONTHS: u32 = 12;
+
+/// Days the available quantity lasts at the forecast rate; `None` when there is no demand.
+fn days_of_cover(available: Decimal, monthly_forecast: Decimal) -> Option<Decimal> {
+    if monthly_forecast <= Decimal::ZERO {
+        return None;
+    }
+    let daily = monthly_forecast / Decimal::from(30);
+    Some((available.max(Decimal::ZERO) / daily).round_dp(1))
+}
+
+#[derive(Serialize)]
+pub struct MonthlyQuantity {
+    pub month: String,
+    pub quantity: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct ItemForecast {
+    pub stock_id: String,
+    pub project: Option<String>,
+    pub history: Vec<MonthlyQuantity>,
+    /// MOVING_AVERAGE or EXPONENTIAL_SMOOTHING, whichever backtested best
+    pub method: String,
+    /// Window in months for a moving average, alpha for smoothing
+    pub parameter: f64,
+    pub backtest_mae: Option<Decimal>,
+    pub forecast: Vec<MonthlyQuantity>,
+    pub available_quantity: Decimal,
+    pub days_of_cover: Option<Decimal>,
+}
+
+/// Monthly demand forecast for one item from its issue history, optionally
+/// restricted to issues for one project.
+#[tauri::command]
+pub async fn get_forecast(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    project: Option<String>,
+    history_months: Option<u32>,
+    horizon_months: Option<u32>,
+) -> Result<ItemForecast, String> {
+    let (key, _) = identity_of(&state.pool, &stock_id)
+        .await?
+        .ok_or_else(|| format!("Unknown stock item {}", stock_id))?;
+    let project = project.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+
+    let (since, months) = forecast_months(history_months.unwrap_or(FORECAST_HISTORY_MONTHS));
+    let history = monthly_issues(&state.pool, since, Some(&stock_id), project.as_deref()).await?;
+    let item_history = history.get(&key);
+    let model = forecast::best_forecast(&demand_series(&months, item_history));
+    let monthly = Decimal::from_f64_retain(model.next).unwrap_or_default().round_dp(4);
+
+    let on_hand = available_for_identity(&state.pool, &stock_id, false).await?;
+    let reserved = reserved_for_identity(&state.pool, &stock_id, project.as_deref()).await?;
+    let available = on_hand - reserved;
+
+    // The forecast horizon starts with the current, still open month
+    let today = chrono::Utc::now().date_naive();
+    let horizon_start = today - chrono::Duration::days(i64::from(today.day0()));
+    let horizon_end = horizon_start
+        .checked_add_months(chrono::Months::new(horizon_months.unwrap_or(3).max(1) - 1))
+        .unwrap_or(horizon_start);
+
+    Ok(ItemForecast {
+        stock_id,
+        project,
+        history: months
+            .iter()
+            .map(|month| MonthlyQuantity {
+                month: month.clone(),
+                quantity: item_history.and_then(|h| h.get(month)).copied().unwrap_or_default(),
+            })
+            .collect(),
+        method: model.method.to_string(),
+        parameter: model.parameter,
+        backtest_mae: model.mae.and_then(Decimal::from_f64_retain).map(|m| m.round_dp(4)),
+        forecast: month_keys(horizon_start, horizon_end)
+            .into_iter()
+            .map(|month| MonthlyQuantity { month, quantity: monthly })
+            .collect(),
+        available_quantity: available,
+        days_of_cover: days_of_cover(available, monthly),
+    })
+}
+
+#[tauri::command]
+pub async fn get_stats(state: tauri::State<'_, AppState>) -> Result<Stats, String> {
+    let total_unique: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT project, part_name, uom, location) FROM stock_master WHERE merged_into IS NULL AND status = 'ACTIVE'"
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let total_received: Decimal = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(l.quantity_change), 0) FROM stock_ledger l 
+         WHERE l.transaction_type = 'IN' 
+         AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)"
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let total_issued: Decimal = sqlx::query_scalar(
+        "SELECT ABS(COALESCE(SUM(l.quantity_change), 0)) FROM stock_ledger l 
+         WHERE l.transaction_type = 'OUT' 
+         AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)"
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let low_stock_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM (
+            SELECT 
+                MAX(m.project) as project, 
+                MAX(m.part_name) as part_name, 
+                MAX(m.uom) as uom, 
+                MAX(m.location) as location,
+                COALESCE(SUM(l.quantity_change), 0) as available_grouped,
+                MAX(COALESCE(t.min_quantity, 0)) as max_min_qty
+            FROM stock_master m
+            LEFT JOIN stock_ledger l ON m.stock_id = l.stock_id
+            LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
+            WHERE m.merged_into IS NULL AND m.status = 'ACTIVE'
+            GROUP BY LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)), LOWER(TRIM(m.description))
+        ) as grouped_inventory WHERE available_grouped < max_min_qty"
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let (slow_moving_count, dead_stock_count) = movement_aging_counts(&state.pool).await?;
+
+    Ok(Stats {
+        total_unique_items: total_unique,
+        total_received,
+        total_issued,
+        low_stock_count,
+        slow_moving_count,
+        dead_stock_count,
+    })
+}
+
+async fn setting_days(pool: &MySqlPool, key: &str, default: i64) -> Result<i64, String> {
+    let value: Option<String> = sqlx::query_scalar("SELECT setting_value FROM app_setting WHERE setting_key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .flatten();
+    Ok(value.and_then(|v| v.trim().parse().ok()).unwrap_or(default))
+}
+
+/// Classifies every item with stock on hand by how long it has gone without an
+/// issue. Items never issued are aged from their first receipt. Per-item day
+/// limits come from stock_threshold, falling back to the global settings.
+async fn movement_aging(pool: &MySqlPool) -> Result<Vec<MovementAgingItem>, String> {
+    let default_slow = setting_days(pool, "slow_moving_days", 30).await?;
+    let default_dead = setting_days(pool, "dead_stock_days", 90).await?;
+
+    let query = format!(
+        "SELECT 
+            MAX(m.stock_id) as stock_id,
+            MAX(m.project) as project,
+            MAX(m.part_name) as part_name,
+            MAX(m.description) as description,
+            MAX(m.uom) as uom,
+            MAX(m.location) as location,
+            (
+                SELECT COALESCE(SUM(l2.quantity_change), 0)
+                FROM stock_ledger l2
+                JOIN stock_master m2 ON l2.stock_id = m2.stock_id
+                WHERE {}
+            ) as on_hand_quantity,
+            (
+                SELECT MAX(l3.transaction_date)
+                FROM stock_ledger l3
+                JOIN stock_master m3 ON l3.stock_id = m3.stock_id
+                WHERE {} AND l3.transaction_type = 'OUT'
+                  AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l3.ledger_id)
+            ) as last_issue_date,
+            (
+                SELECT MIN(l4.transaction_date)
+                FROM stock_ledger l4
+                JOIN stock_master m4 ON l4.stock_id = m4.stock_id
+                WHERE {} AND l4.transaction_type = 'IN'
+            ) as first_receipt_date,
+            (
+                SELECT SUM(l5.quantity_change * l5.unit_cost) / NULLIF(SUM(l5.quantity_change), 0)
+                FROM stock_ledger l5
+                JOIN stock_master m5 ON l5.stock_id = m5.stock_id
+                WHERE {} AND l5.transaction_type = 'IN' AND l5.unit_cost IS NOT NULL
+            ) as unit_cost,
+            MAX(t.slow_moving_days) as slow_moving_days,
+            MAX(t.dead_stock_days) as dead_stock_days
+        FROM stock_master m
+        LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
+        GROUP BY m.project, m.part_name, m.uom, m.location, m.description
+        HAVING on_hand_quantity > 0
+        ORDER BY MAX(m.part_name)",
+        identity_match("m2", "m"),
+        identity_match("m3", "m"),
+        identity_match("m4", "m"),
+        identity_match("m5", "m"),
+    );
+
+    let mut items = sqlx::query_as::<sqlx::MySql, MovementAgingItem>(&query)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let now = chrono::Utc::now();
+    for item in &mut items {
+        let slow = item.slow_moving_days.map(i64::from).unwrap_or(default_slow);
+        let dead = item.dead_stock_days.map(i64::from).unwrap_or(default_dead);
+        item.days_since_movement = item
+            .last_issue_date
+            .or(item.first_receipt_date)
+            .map(|d| (now - d).num_days())
+            .unwrap_or(0);
+        item.movement_class = if item.days_since_movement >= dead {
+            "DEAD"
+        } else if item.days_since_movement >= slow {
+            "SLOW_MOVING"
+        } else {
+            "ACTIVE"
+        }
+        .to_string();
+        item.stock_value = item.unit_cost.map(|c| (c * item.on_hand_quantity).round_dp(2));
+    }
+
+    Ok(items)
+}
+
+/// Number of slow moving and dead items, classified as `movement_aging` does but
+/// counted in the database, for the dashboard.
+async fn movement_aging_counts(pool: &MySqlPool) -> Result<(i64, i64), String> {
+    let default_slow = setting_days(pool, "slow_moving_days", 30).await?;
+    let default_dead = setting_days(pool, "dead_stock_days", 90).await?;
+
+    let (slow, dead): (Decimal, Decimal) = sqlx::query_as(
+        "SELECT
+            COALESCE(SUM(days_since_movement >= slow_days AND days_since_movement < dead_days), 0),
+            COALESCE(SUM(days_since_movement >= dead_days), 0)
+         FROM (
+            SELECT
+                COALESCE(TIMESTAMPDIFF(DAY, COALESCE(
+                    MAX(CASE WHEN l.transaction_type = 'OUT'
+                              AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)
+                             THEN l.transaction_date END),
+                    MIN(CASE WHEN l.transaction_type = 'IN' THEN l.transaction_date END)
+                ), NOW()), 0) as days_since_movement,
+                COALESCE(MAX(t.slow_moving_days), ?) as slow_days,
+                COALESCE(MAX(t.dead_stock_days), ?) as dead_days
+            FROM stock_master m
+            JOIN stock_ledger l ON l.stock_id = m.stock_id
+            LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
+            GROUP BY m.project, m.part_name, m.uom, m.location, m.description
+            HAVING SUM(l.quantity_change) > 0
+         ) as aged"
+    )
+    .bind(default_slow)
+    .bind(default_dead)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok((slow.to_i64().unwrap_or(0), dead.to_i64().unwrap_or(0)))
+}
+
+#[derive(Serialize)]
+pub struct AgingClassSummary {
+    pub movement_class: String,
+    pub item_count: i64,
+    pub quantity: Decimal,
+    pub value: Decimal,
+    /// Items in the class with no receipt cost, whose stock is missing from `value`
+    pub unvalued_count: i64,
+}
+
+#[derive(Serialize)]
+pub struct MovementAgingReport {
+    pub items: Vec<MovementAgingItem>,
+    pub summary: Vec<AgingClassSummary>,
+}
+
+#[tauri::command]
+pub async fn get_movement_aging_report(
+    state: tauri::State<'_, AppState>,
+    movement_class: Option<String>,
+) -> Result<MovementAgingReport, String> {
+    let mut items = movement_aging(&state.pool).await?;
+
+    let summary = ["ACTIVE", "SLOW_MOVING", "DEAD"]
+        .iter()
+        .map(|class| {
+            let in_class = items.iter().filter(|i| i.movement_class == *class);
+            let mut entry = AgingClassSummary {
+                movement_class: class.to_string(),
+                item_count: 0,
+                quantity: Decimal::ZERO,
+                value: Decimal::ZERO,
+                unvalued_count: 0,
+            };
+            for item in in_class {
+                entry.item_count += 1;
+                entry.quantity += item.on_hand_quantity;
+                match item.stock_value {
+                    Some(v) => entry.value += v,
+                    None => entry.unvalued_count += 1,
+                }
+            }
+            entry
+        })
+        .collect();
+
+    if let Some(class) = movement_class.filter(|c| !c.is_empty() && c != "All") {
+        items.retain(|i| i.movement_class == class);
+    }
+
+    Ok(MovementAgingReport { items, summary })
+}
+
+/// Writes the movement aging report to `file_path`. Returns the rows written.
+#[tauri::command]
+pub async fn export_movement_aging_report(
+    state: tauri::State<'_, AppState>,
+    movement_class: Option<String>,
+    file_path: String,
+) -> Result<usize, String> {
+    let mut items = movement_aging(&state.pool).await?;
+    if let Some(class) = movement_class.filter(|c| !c.is_empty() && c != "All") {
+        items.retain(|i| i.movement_class == class);
+    }
+
+    let columns = [
+        ExportColumn::new("Project", ColumnKind::Text),
+        ExportColumn::new("Part Name", ColumnKind::Text),
+        ExportColumn::new("Description", ColumnKind::Text),
+        ExportColumn::new("UOM", ColumnKind::Text),
+        ExportColumn::new("Location", ColumnKind::Text),
+        ExportColumn::new("On Hand", ColumnKind::Quantity),
+        ExportColumn::new("Last Issue", ColumnKind::Date),
+        ExportColumn::new("First Receipt", ColumnKind::Date),
+        ExportColumn::new("Days Idle", ColumnKind::Integer),
+        ExportColumn::new("Class", ColumnKind::Text),
+        ExportColumn::new("Unit Cost", ColumnKind::Money),
+        ExportColumn::new("Value", ColumnKind::Money),
+    ];
+    let rows = items.into_iter().map(|i| {
+        vec![
+            i.project.into(),
+            i.part_name.into(),
+            i.description.into(),
+            i.uom.into(),
+            i.location.into(),
+            i.on_hand_quantity.into(),
+            i.last_issue_date.into(),
+            i.first_receipt_date.into(),
+            i.days_since_movement.into(),
+            i.movement_class.into(),
+            i.unit_cost.into(),
+            i.stock_value.into(),
+        ]
+    });
+    export::write_table(std::path::Path::new(&file_path), &columns, rows)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConsumptionFilters {
+    #[serde(default)]
+    pub date_from: Option<String>,
+    #[serde(default)]
+    pub date_to: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub by_part: bool,
+    #[serde(default)]
+    pub by_month: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProjectConsumptionRow {
+    pub project: String,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+    pub month: Option<String>,
+    pub issued_quantity: Decimal,
+    pub reversed_quantity: Decimal,
+    pub net_quantity: Decimal,
+    /// Net quantity valued at weighted average receipt cost, where costs exist
+    pub value: Option<Decimal>,
+    /// Net quantity of items with no receipt cost, left out of `value`
+    pub unvalued_quantity: Decimal,
+}
+
+/// Issues per consuming project, with reversals of those issues netted off in the
+/// period the reversal was posted. The consuming project is the issue slip's project
+/// where there is one, otherwise the item's own project.
+async fn project_consumption(
+    pool: &MySqlPool,
+    filters: &ConsumptionFilters,
+) -> Result<Vec<ProjectConsumptionRow>, String> {
+    let date_from = parse_report_date(filters.date_from.clone())?;
+    let date_to = parse_report_date(filters.date_to.clone())?;
+
+    let mut query = String::from(
+        "SELECT LOWER(TRIM(m.project)), LOWER(TRIM(m.part_name)), LOWER(TRIM(m.description)), LOWER(TRIM(m.uom)), LOWER(TRIM(m.location)),
+                LOWER(TRIM(COALESCE(s.project, m.project))), DATE_FORMAT(l.transaction_date, '%Y-%m'),
+                MAX(TRIM(COALESCE(s.project, m.project))), MAX(m.part_name), MAX(m.description), MAX(m.uom),
+                COALESCE(SUM(CASE WHEN l.transaction_type = 'OUT' THEN -l.quantity_change ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN l.transaction_type = 'REVERSAL' THEN l.quantity_change ELSE 0 END), 0)
+         FROM stock_ledger l
+         JOIN stock_master m ON l.stock_id = m.stock_id
+         LEFT JOIN stock_ledger o ON l.transaction_type = 'REVERSAL' AND o.ledger_id = l.reverses_ledger_id
+         LEFT JOIN issue_slip_line sl ON sl.ledger_id = COALESCE(o.ledger_id, l.ledger_id)
+         LEFT JOIN issue_slip s ON s.slip_id = sl.slip_id
+         WHERE (l.transaction_type = 'OUT' OR (l.transaction_type = 'REVERSAL' AND o.transaction_type = 'OUT'))"
+    );
+    let mut binds: Vec<String> = Vec::new();
+    if let Some(from) = date_from {
+        query.push_str(" AND l.transaction_date >= ?");
+        binds.push(from);
+    }
+    if let Some(to) = date_to {
+        query.push_str(" AND l.transaction_date <= ?");
+        binds.push(format!("{} 23:59:59", to));
+    }
+    if let Some(project) = filters.project.as_ref().filter(|p| !p.trim().is_empty() && *p != "All") {
+        query.push_str(" AND LOWER(TRIM(COALESCE(s.project, m.project))) = LOWER(TRIM(?))");
+        binds.push(project.clone());
+    }
+    query.push_str(" GROUP BY 1, 2, 3, 4, 5, 6, 7");
+
+    let mut q = sqlx::query(&query);
+    for value in &binds {
+        q = q.bind(value);
+    }
+    let rows = q.fetch_all(pool).await.map_err(|e| e.to_string())?;
+    let costs = average_receipt_costs(pool).await?;
+
+    // Roll identity/month rows up to the requested grouping, keeping first-seen order
+    let mut order: Vec<(String, Option<IdentityKey>, Option<String>)> = Vec::new();
+    let mut groups: HashMap<(String, Option<IdentityKey>, Option<String>), ProjectConsumptionRow> = HashMap::new();
+    for row in rows {
+        let identity: IdentityKey = (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4));
+        let project_key: String = row.get(5);
+        let month: String = row.get(6);
+        let issued: Decimal = row.get(11);
+        let reversed: Decimal = row.get(12);
+        let net = issued - reversed;
+
+        let key = (
+            project_key,
+            filters.by_part.then(|| identity.clone()),
+            filters.by_month.then(|| month.clone()),
+        );
+        let entry = groups.entry(key.clone()).or_insert_with(|| {
+            order.push(key);
+            ProjectConsumptionRow {
+                project: row.get(7),
+                part_name: filters.by_part.then(|| row.get(8)),
+                description: filters.by_part.then(|| row.get(9)),
+                uom: filters.by_part.then(|| row.get(10)),
+                month: filters.by_month.then(|| month.clone()),
+                issued_quantity: Decimal::ZERO,
+                reversed_quantity: Decimal::ZERO,
+                net_quantity: Decimal::ZERO,
+                value: None,
+                unvalued_quantity: Decimal::ZERO,
+            }
+        });
+        entry.issued_quantity += issued;
+        entry.reversed_quantity += reversed;
+        entry.net_quantity += net;
+        match costs.get(&identity) {
+            Some(cost) => {
+                entry.value = Some(entry.value.unwrap_or_default() + (net * cost).round_dp(2));
+            }
+            None => entry.unvalued_quantity += net,
+        }
+    }
+
+    let mut report: Vec<ProjectConsumptionRow> = order
+        .into_iter()
+        .filter_map(|key| groups.remove(&key))
+        .collect();
+    report.sort_by(|a, b| {
+        a.project
+            .to_lowercase()
+            .cmp(&b.project.to_lowercase())
+            .then_with(|| a.month.cmp(&b.month))
+            .then_with(|| a.part_name.cmp(&b.part_name))
+    });
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn get_project_consumption(
+    state: tauri::State<'_, AppState>,
+    filters: ConsumptionFilters,
+) -> Result<Vec<ProjectConsumptionRow>, String> {
+    project_consumption(&state.pool, &filters).await
+}
+
+/// Writes the project consumption report to `file_path`. Returns the rows written.
+#[tauri::command]
+pub async fn export_project_consumption(
+    state: tauri::State<'_, AppState>,
+    filters: ConsumptionFilters,
+    file_path: String,
+) -> Result<usize, String> {
+    let report = project_consumption(&state.pool, &filters).await?;
+
+    let mut columns = vec![ExportColumn::new("Project", ColumnKind::Text)];
+    if filters.by_month {
+        columns.push(ExportColumn::new("Month", ColumnKind::Text));
+    }
+    if filters.by_part {
+        columns.push(ExportColumn::new("Part Name", ColumnKind::Text));
+        columns.push(ExportColumn::new("Description", ColumnKind::Text));
+        columns.push(ExportColumn::new("UOM", ColumnKind::Text));
+    }
+    columns.extend([
+        ExportColumn::new("Issued", ColumnKind::Quantity),
+        ExportColumn::new("Reversed", ColumnKind::Quantity),
+        ExportColumn::new("Net Consumed", ColumnKind::Quantity),
+        ExportColumn::new("Value", ColumnKind::Money),
+        ExportColumn::new("Unvalued Qty", ColumnKind::Quantity),
+    ]);
+
+    let rows = report.into_iter().map(|r| {
+        let mut cells: Vec<Cell> = vec![r.project.into()];
+        if filters.by_month {
+            cells.push(r.month.into());
+        }
+        if filters.by_part {
+            cells.extend([r.part_name.into(), r.description.into(), r.uom.into()]);
+        }
+        cells.extend([
+            r.issued_quantity.into(),
+            r.reversed_quantity.into(),
+            r.net_quantity.into(),
+            r.value.into(),
+            r.unvalued_quantity.into(),
+        ]);
+        cells
+    });
+    export::write_table(std::path::Path::new(&file_path), &columns, rows)
+}
+
+fn normalize_string(s: &str) -> String {
+    s.trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportRow {
+    pub project: String,
+    pub supplier_name: String,
+    pub invoice: String,
+    pub po_no: String,
+    pub part_name: String,
+    pub description: String,
+    pub quantity: Decimal,
+    pub uom: String,
+    pub location: String,
+    #[serde(default)]
+    pub remarks: Option<String>,
+    #[serde(default)]
+    pub rec_date: Option<String>,
+    #[serde(default)]
+    pub unit_cost: Option<Decimal>,
+    /// Custom attribute values by attribute name
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub row: ImportRow,
+    pub status: String, // "NEW" | "MERGED" | "DUPLICATE" | "ERROR"
+    #[serde(default)]
+    pub diff_reason: Option<String>,
+    #[serde(default)]
+    pub existing_stock_id: Option<String>,
+    /// Spreadsheet row the preview came from, for file imports
+    #[serde(default)]
+    pub sheet_row: Option<u32>,
+    /// Every record sharing the row's identity, when there is more than one
+    #[serde(default)]
+    pub matched_stock_ids: Vec<String>,
+    /// Earlier row in the same upload with the same item and supplier invoice
+    #[serde(default)]
+    pub duplicate_of_row: Option<u32>,
+    /// Earlier receipt of the same supplier invoice and item
+    #[serde(default)]
+    pub duplicate_of_ledger_id: Option<i32>,
+    /// Set by the user to import a DUPLICATE row anyway
+    #[serde(default)]
+    pub override_duplicate: bool,
+    #[serde(default)]
+    pub override_reason: Option<String>,
+    /// Similar existing items, or earlier new rows of the same upload, proposed
+    /// for a NEW row, best first
+    #[serde(default)]
+    pub suggestions: Vec<MatchSuggestion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchSuggestion {
+    /// The existing item suggested; empty when it's a row of this upload
+    #[serde(default)]
+    pub stock_id: Option<String>,
+    /// Earlier new row of the same upload suggested instead of an existing item
+    #[serde(default)]
+    pub sheet_row: Option<u32>,
+    pub project: String,
+    pub part_name: String,
+    pub description: String,
+    pub uom: String,
+    pub location: String,
+    /// Similarity from 0 to 1
+    pub score: f64,
+}
+
+/// Suggestions below this similarity aren't shown
+const MATCH_THRESHOLD: f64 = 0.75;
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Letters of a part-name word an item is also filed under, so that a typo after
+/// them ("Gasket", "Gaskte") still finds it
+const WORD_PREFIX_LENGTH: usize = 3;
+
+/// Words of a part name, and their first letters, as `SuggestionIndex` files them.
+fn part_name_words(part_name: &str) -> BTreeSet<String> {
+    matching::tokens(part_name)
+        .into_iter()
+        .flat_map(|word| {
+            let prefix: String = word.chars().take(WORD_PREFIX_LENGTH).collect();
+            [word, prefix]
+        })
+        .collect()
+}
+
+/// What a NEW row may be suggested: existing items, and earlier new rows of the same
+/// upload, filed by project and by the words of their part name. A row is only
+/// scored against items in its project that share a part-name word or word prefix
+/// with it, so the edit distances computed stay few however many items there are.
+/// Part names differing in the first letters of every word are never suggested.
+#[derive(Default)]
+struct SuggestionIndex {
+    /// The suggestion each item makes, with the identity of upload rows
+    items: Vec<(MatchSuggestion, Option<IdentityKey>, matching::PreparedItem)>,
+    /// Item positions by normalized project, then by part-name word
+    by_word: HashMap<String, HashMap<String, Vec<usize>>>,
+}
+
+impl SuggestionIndex {
+    fn add(&mut self, suggestion: MatchSuggestion, identity: Option<IdentityKey>) {
+        let idx = self.items.len();
+        let words = self.by_word.entry(normalize_string(&suggestion.project)).or_default();
+        for word in part_name_words(&suggestion.part_name) {
+            words.entry(word).or_default().push(idx);
+        }
+        let prepared = matching::PreparedItem::new(&matching::ItemText {
+            part_name: &suggestion.part_name,
+            description: &suggestion.description,
+            uom: &suggestion.uom,
+            location: &suggestion.location,
+        });
+        self.items.push((suggestion, identity, prepared));
+    }
+
+    /// Items that look like the same thing as `row` spelt differently, best first.
+    fn suggest(&self, row: &ImportRow) -> Vec<MatchSuggestion> {
+        let Some(words) = self.by_word.get(&normalize_string(&row.project)) else {
+            return Vec::new();
+        };
+        let mut candidates: Vec<usize> = part_name_words(&row.part_name)
+            .iter()
+            .filter_map(|word| words.get(word))
+            .flatten()
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let incoming = matching::PreparedItem::new(&matching::ItemText {
+            part_name: &row.part_name,
+            description: &row.description,
+            uom: &row.uom,
+            location: &row.location,
+        });
+        let identity = row_identity(row);
+        let mut suggestions: Vec<MatchSuggestion> = candidates
+            .into_iter()
+            .map(|idx| &self.items[idx])
+            // A row with the very same identity becomes the same item anyway
+            .filter(|(_, item_identity, _)| item_identity.as_ref() != Some(&identity))
+            .filter_map(|(item, _, existing)| {
+                incoming.similarity_at_least(existing, MATCH_THRESHOLD).map(|score| MatchSuggestion {
+                    score: (score * 100.0).round() / 100.0,
+                    ..item.clone()
+                })
+            })
+            .collect();
+        suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.part_name.cmp(&b.part_name)));
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+}
+
+/// Fills in the suggestions of the NEW rows at `pending`, in upload order, from
+/// `candidates` and the rows before them. A row given a sheet row label is filed
+/// once scored, so later rows can be pointed at it.
+fn match_suggestions(previews: &mut [ImportPreview], pending: &[(usize, Option<u32>)], candidates: Vec<StockMaster>) {
+    let mut index = SuggestionIndex::default();
+    for m in candidates {
+        let suggestion = MatchSuggestion {
+            stock_id: Some(m.stock_id),
+            sheet_row: None,
+            project: m.project,
+            part_name: m.part_name,
+            description: m.description,
+            uom: m.uom,
+            location: m.location,
+            score: 0.0,
+        };
+        index.add(suggestion, None);
+    }
+    for &(idx, row_label) in pending {
+        let preview = &mut previews[idx];
+        preview.suggestions = index.suggest(&preview.row);
+        if let Some(row_label) = row_label {
+            let row = &preview.row;
+            let suggestion = MatchSuggestion {
+                stock_id: None,
+                sheet_row: Some(row_label),
+                project: row.project.clone(),
+                part_name: row.part_name.clone(),
+                description: row.description.clone(),
+                uom: row.uom.clone(),
+                location: row.location.clone(),
+                score: 0.0,
+            };
+            index.add(suggestion, Some(row_identity(row)));
+        }
+    }
+}
+
+/// Points a previewed row at one of its suggestions by taking over the suggested
+/// item's identity fields, then previews it again so duplicate and difference checks
+/// run against the item it will now merge into. For an existing item the fields are
+/// read again from the database; for an earlier row of the upload they are the
+/// suggestion's own, and both rows then create the one item.
+#[tauri::command]
+pub async fn accept_import_suggestion(
+    state: tauri::State<'_, AppState>,
+    preview: ImportPreview,
+    suggestion: MatchSuggestion,
+) -> Result<ImportPreview, String> {
+    let (project, part_name, description, uom, location) = match &suggestion.stock_id {
+        Some(stock_id) => {
+            let master = sqlx::query_as::<_, StockMaster>("SELECT * FROM stock_master WHERE stock_id = ?")
+                .bind(stock_id)
+                .fetch_optional(&state.pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Stock item {} not found", stock_id))?;
+            (master.project, master.part_name, master.description, master.uom, master.location)
+        }
+        None => (
+            suggestion.project,
+            suggestion.part_name,
+            suggestion.description,
+            suggestion.uom,
+            suggestion.location,
+        ),
+    };
+
+    let mut row = preview.row;
+    let original = format!("{} / {} / {}", row.part_name.trim(), row.description.trim(), row.uom.trim());
+    row.project = project;
+    row.part_name = part_name;
+    row.description = description;
+    row.uom = uom;
+    row.location = location;
+
+    let mut previews = preview_import_rows(&state.pool, vec![(preview.sheet_row, row)], None).await?;
+    let mut accepted = previews.pop().ok_or("Preview failed")?;
+    let note = format!("Matched by suggestion from '{}'", original);
+    accepted.diff_reason = Some(match accepted.diff_reason {
+        Some(reason) => format!("{}; {}", note, reason),
+        None => note,
+    });
+    accepted.override_duplicate = preview.override_duplicate;
+    accepted.override_reason = preview.override_reason;
+    Ok(accepted)
+}
+
+/// Previews rows parsed on the page. A row with a bad receipt date or attribute
+/// comes back as ERROR with the reason, without stopping the others.
+#[tauri::command]
+pub async fn bulk_upload_preview(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    rows: Vec<ImportRow>,
+) -> Result<Vec<ImportPreview>, String> {
+    preview_import_rows(&state.pool, rows.into_iter().map(|row| (None, row)).collect(), Some(&app)).await
+}
+
+#[derive(Serialize)]
+pub struct FileImportPreview {
+    pub file_name: String,
+    pub file_hash: String,
+    /// Earlier uploads of the identical file that haven't been reverted
+    pub previous_batches: Vec<ImportBatch>,
+    /// Mapping profile the file was read with; None for the standard template
+    pub profile_name: Option<String>,
+    pub total_rows: usize,
+    pub skipped_rows: usize,
+    pub previews: Vec<ImportPreview>,
+    pub errors: Vec<CellError>,
+}
+
+/// Reads and validates an xlsx/xls/ods/csv file on disk. Rows with bad cells are
+/// reported with their sheet row and column; the valid rows are previewed as usual.
+/// Without `profile_id` the columns are mapped by the profile saved for this header
+/// layout, then by the standard template, then by any profile that fits.
+#[tauri::command]
+pub async fn preview_import_file(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    file_path: String,
+    profile_id: Option<i32>,
+) -> Result<FileImportPreview, String> {
+    let path = std::path::PathBuf::from(&file_path);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.clone());
+
+    let (sheet, file_hash) = tauri::async_runtime::spawn_blocking(move || {
+        Ok::<_, String>((importer::read_sheet(&path)?, importer::file_sha256(&path)?))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let profile = match profile_id {
+        Some(id) => Some(import_profile(&state.pool, id).await?),
+        None => detect_import_profile(&state.pool, &sheet.headers).await?,
+    };
+    let mut mapping = match &profile {
+        Some(profile) => profile.resolve(&sheet.headers)?,
+        None => importer::template_columns(&sheet.headers)?,
+    };
+    let definitions = attribute_definitions(&state.pool).await?;
+    mapping.match_attributes(&sheet.headers, &definitions)?;
+    let parsed = tauri::async_runtime::spawn_blocking(move || importer::parse_sheet(&sheet, &mapping, &definitions))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let previous_batches = sqlx::query_as::<_, ImportBatch>(
+        "SELECT * FROM import_batch WHERE file_hash = ? AND status = 'POSTED' ORDER BY created_at DESC"
+    )
+    .bind(&file_hash)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Dates that parsed but fall in a closed period are cell errors too
+    let mut errors = parsed.errors;
+    let locked = locked_through(&state.pool).await?;
+    let mut rows: Vec<(Option<u32>, ImportRow)> = Vec::new();
+    for parsed_row in parsed.rows {
+        match receipt_date(&parsed_row.row, locked) {
+            Ok(_) => rows.push((Some(parsed_row.sheet_row), parsed_row.row)),
+            Err(message) => errors.push(CellError {
+                sheet_row: parsed_row.sheet_row,
+                column: "Rec Date".to_string(),
+                value: parsed_row.row.rec_date.clone().unwrap_or_default(),
+                message,
+            }),
+        }
+    }
+    errors.sort_by_key(|e| e.sheet_row);
+    let previews = preview_import_rows(&state.pool, rows, Some(&app)).await?;
+
+    Ok(FileImportPreview {
+        file_name,
+        file_hash,
+        previous_batches,
+        profile_name: profile.map(|p| p.name),
+        total_rows: parsed.total_rows,
+        skipped_rows: parsed.skipped_rows,
+        previews,
+        errors,
+    })
+}
+
+fn profile_from_record(record: ImportProfileRecord) -> Result<MappingProfile, String> {
+    let mut profile: MappingProfile = serde_json::from_str(&record.definition)
+        .map_err(|e| format!("Profile '{}' is corrupt: {}", record.name, e))?;
+    profile.profile_id = Some(record.profile_id);
+    profile.name = record.name;
+    profile.header_fingerprint = record.header_fingerprint;
+    Ok(profile)
+}
+
+async fn import_profile(pool: &MySqlPool, profile_id: i32) -> Result<MappingProfile, String> {
+    let record = sqlx::query_as::<_, ImportProfileRecord>("SELECT * FROM import_profile WHERE profile_id = ?")
+        .bind(profile_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Import profile {} not found", profile_id))?;
+    profile_from_record(record)
+}
+
+async fn import_profiles(pool: &MySqlPool) -> Result<Vec<MappingProfile>, String> {
+    let records = sqlx::query_as::<_, ImportProfileRecord>("SELECT * FROM import_profile ORDER BY name")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    records.into_iter().map(profile_from_record).collect()
+}
+
+/// Picks the profile for a sheet nobody chose one for: the one saved for exactly
+/// these headers, else none if the standard template fits, else the profile that
+/// resolves against the most columns.
+async fn detect_import_profile(pool: &MySqlPool, headers: &[String]) -> Result<Option<MappingProfile>, String> {
+    let profiles = import_profiles(pool).await?;
+    let fingerprint = importer::header_fingerprint(headers);
+    if let Some(profile) = profiles
+        .iter()
+        .find(|p| p.header_fingerprint.as_deref() == Some(fingerprint.as_str()) && p.resolve(headers).is_ok())
+    {
+        return Ok(Some(profile.clone()));
+    }
+    if importer::template_columns(headers).is_ok() {
+        return Ok(None);
+    }
+    Ok(profiles
+        .into_iter()
+        .filter(|p| p.resolve(headers).is_ok())
+        .max_by_key(|p| p.coverage(headers)))
+}
+
+/// Headers of an import file, for building a mapping profile against it.
+#[tauri::command]
+pub async fn read_import_headers(file_path: String) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || importer::read_sheet(std::path::Path::new(&file_path)))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|sheet| sheet.headers)
+}
+
+#[tauri::command]
+pub async fn list_import_profiles(state: tauri::State<'_, AppState>) -> Result<Vec<MappingProfile>, String> {
+    import_profiles(&state.pool).await
+}
+
+async fn store_import_profile<'e, E>(executor: E, profile: &MappingProfile, user: &str) -> Result<(), String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let definition = serde_json::to_string(profile).map_err(|e| e.to_string())?;
+    let result = match profile.profile_id {
+        Some(id) => sqlx::query(
+            "UPDATE import_profile SET name = ?, header_fingerprint = ?, definition = ?, created_by = ?
+             WHERE profile_id = ?"
+        )
+        .bind(profile.name.trim())
+        .bind(&profile.header_fingerprint)
+        .bind(&definition)
+        .bind(user)
+        .bind(id)
+        .execute(executor)
+        .await,
+        None => sqlx::query(
+            "INSERT INTO import_profile (name, header_fingerprint, definition, created_by) VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE header_fingerprint = VALUES(header_fingerprint),
+                 definition = VALUES(definition), created_by = VALUES(created_by)"
+        )
+        .bind(profile.name.trim())
+        .bind(&profile.header_fingerprint)
+        .bind(&definition)
+        .bind(user)
+        .execute(executor)
+        .await,
+    };
+    result.map_err(|e| match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            format!("An import profile named '{}' already exists", profile.name.trim())
+        }
+        e => e.to_string(),
+    })?;
+    Ok(())
+}
+
+/// Saves a mapping profile, replacing one of the same name. Pass the headers of the
+/// file it was built from to have it picked automatically for that layout.
+#[tauri::command]
+pub async fn save_import_profile(
+    state: tauri::State<'_, AppState>,
+    mut profile: MappingProfile,
+    headers: Option<Vec<String>>,
+    user: String,
+) -> Result<(), String> {
+    profile.validate()?;
+    if let Some(headers) = headers {
+        profile.resolve(&headers)?;
+        profile.header_fingerprint = Some(importer::header_fingerprint(&headers));
+    }
+    store_import_profile(&state.pool, &profile, &user).await
+}
+
+#[tauri::command]
+pub async fn delete_import_profile(state: tauri::State<'_, AppState>, profile_id: i32) -> Result<(), String> {
+    sqlx::query("DELETE FROM import_profile WHERE profile_id = ?")
+        .bind(profile_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Writes all mapping profiles to a JSON file, to share them with another install.
+#[tauri::command]
+pub async fn export_import_profiles(state: tauri::State<'_, AppState>, file_path: String) -> Result<usize, String> {
+    let mut profiles = import_profiles(&state.pool).await?;
+    for profile in &mut profiles {
+        profile.profile_id = None;
+    }
+    let json = serde_json::to_string_pretty(&profiles).map_err(|e| e.to_string())?;
+    tokio::fs::write(&file_path, json).await.map_err(|e| e.to_string())?;
+    Ok(profiles.len())
+}
+
+/// Loads profiles from a file written by `export_import_profiles`. Profiles with
+/// the name of an existing one replace it.
+#[tauri::command]
+pub async fn import_import_profiles(
+    state: tauri::State<'_, AppState>,
+    file_path: String,
+    user: String,
+) -> Result<usize, String> {
+    let json = tokio::fs::read_to_string(&file_path).await.map_err(|e| e.to_string())?;
+    let profiles: Vec<MappingProfile> =
+        serde_json::from_str(&json).map_err(|e| format!("Not an import profile file: {}", e))?;
+    for profile in &profiles {
+        profile.validate().map_err(|e| format!("Profile '{}': {}", profile.name, e))?;
+    }
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    for mut profile in profiles.iter().cloned() {
+        profile.profile_id = None;
+        store_import_profile(&mut *tx, &profile, &user).await?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(profiles.len())
+}
+
+/// Parses an import row's receipt date and checks it is neither in the future nor in
+/// a closed period. No date means the receipt is booked now.
+fn receipt_date(row: &ImportRow, locked: Option<NaiveDate>) -> Result<Option<NaiveDateTime>, String> {
+    let Some(value) = row.rec_date.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let date = importer::parse_receipt_date(value)?;
+    if date.date() > chrono::Local::now().date_naive() {
+        return Err(format!("Receipt date {} is in the future", date.date()));
+    }
+    if let Some(locked) = locked {
+        if date.date() <= locked {
+            return Err(format!(
+                "Receipt date {} falls in a closed period (closed through {})",
+                date.date(),
+                locked
+            ));
+        }
+    }
+    Ok(Some(date))
+}
+
+/// Differences between an incoming row and the record it merges into. Identity
+/// fields can only differ in case or spacing; the rest are receipt details.
+fn field_differences(row: &ImportRow, master: &StockMaster) -> Vec<String> {
+    [
+        ("Supplier", &row.supplier_name, &master.supplier_name),
+        ("Invoice", &row.invoice, &master.invoice),
+        ("PO No", &row.po_no, &master.po_no),
+        ("Project", &row.project, &master.project),
+        ("Part Name", &row.part_name, &master.part_name),
+        ("Description", &row.description, &master.description),
+        ("UOM", &row.uom, &master.uom),
+        ("Location", &row.location, &master.location),
+    ]
+    .iter()
+    .filter(|(_, incoming, existing)| incoming.trim() != existing.trim())
+    .map(|(label, incoming, existing)| format!("{}: '{}' -> '{}'", label, existing.trim(), incoming.trim()))
+    .collect()
+}
+
+/// Supplier, invoice and item identity of a receipt line, as the duplicate check
+/// compares them.
+type ReceiptKey = (String, String, IdentityKey);
+
+/// The row's `ReceiptKey`. Rows without an invoice number can't be checked. Preview
+/// and posting both go by this key, so they agree on what is a duplicate.
+fn receipt_key(row: &ImportRow) -> Option<ReceiptKey> {
+    if row.invoice.trim().is_empty() {
+        return None;
+    }
+    Some((normalize_string(&row.supplier_name), normalize_string(&row.invoice), row_identity(row)))
+}
+
+/// Earliest unreversed receipt for each of `keys`, looked up in chunks. Receipts
+/// booked before GRNs existed fall back to the item's own supplier and invoice.
+async fn prior_receipts(
+    conn: &mut sqlx::MySqlConnection,
+    keys: &[ReceiptKey],
+) -> Result<HashMap<ReceiptKey, (i32, chrono::DateTime<chrono::Utc>)>, String> {
+    let keys: Vec<&ReceiptKey> = keys.iter().collect::<BTreeSet<_>>().into_iter().collect();
+
+    let mut found = HashMap::new();
+    for chunk in keys.chunks(IMPORT_CHUNK_SIZE) {
+        let values: Vec<[&str; 7]> = chunk
+            .iter()
+            .map(|(supplier, invoice, identity)| {
+                let [project, part_name, description, uom, location] = identity_values(identity);
+                [supplier.as_str(), invoice.as_str(), project, part_name, description, uom, location]
+            })
+            .collect();
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT k.idx, l.ledger_id, l.transaction_date
+             FROM stock_ledger l
+             JOIN stock_master m ON l.stock_id = m.stock_id
+             LEFT JOIN goods_receipt g ON g.grn_id = l.grn_id
+             JOIN "
+        );
+        push_key_table(
+            &mut builder,
+            ["supplier_name", "invoice", "project", "part_name", "description", "uom", "location"],
+            &values,
+        );
+        builder.push(format!(
+            " ON LOWER(TRIM(COALESCE(g.supplier_name, m.supplier_name))) = k.supplier_name
+               AND LOWER(TRIM(COALESCE(g.invoice, m.invoice))) = k.invoice
+               AND {}
+             WHERE l.transaction_type = 'IN'
+               AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)
+             ORDER BY l.ledger_id ASC",
+            identity_match_key("m")
+        ));
+        let rows = builder.build().fetch_all(&mut *conn).await.map_err(|e| e.to_string())?;
+        for row in rows {
+            let key = chunk[row.get::<i64, _>("idx") as usize];
+            found.entry(key.clone()).or_insert((row.get("ledger_id"), row.get("transaction_date")));
+        }
+    }
+    Ok(found)
+}
+
+/// Existing records for each of the (normalized) identities, oldest first.
+async fn masters_by_identity(
+    pool: &MySqlPool,
+    identities: &[IdentityKey],
+    app: Option<&tauri::AppHandle>,
+) -> Result<HashMap<IdentityKey, Vec<StockMaster>>, String> {
+    let mut found: HashMap<IdentityKey, Vec<StockMaster>> = HashMap::new();
+    for (n, chunk) in identities.chunks(IMPORT_CHUNK_SIZE).enumerate() {
+        let values: Vec<[&str; 5]> = chunk.iter().map(identity_values).collect();
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("SELECT k.idx, m.* FROM stock_master m JOIN ");
+        push_key_table(&mut builder, IDENTITY_COLUMNS, &values);
+        builder.push(format!(
+            " ON {} WHERE m.merged_into IS NULL ORDER BY m.created_at ASC, m.stock_id ASC",
+            identity_match_key("m")
+        ));
+        let rows = builder.build().fetch_all(pool).await.map_err(|e| e.to_string())?;
+        for row in rows {
+            let master = StockMaster::from_row(&row).map_err(|e| e.to_string())?;
+            found.entry(chunk[row.get::<i64, _>("idx") as usize].clone()).or_default().push(master);
+        }
+        report_progress(app, "matching", ((n + 1) * IMPORT_CHUNK_SIZE).min(identities.len()), identities.len());
+    }
+    Ok(found)
+}
+
+/// One record per item identity; where several share it, the lowest stock_id.
+async fn distinct_items(pool: &MySqlPool) -> Result<Vec<StockMaster>, String> {
+    sqlx::query_as::<_, StockMaster>(
+        "SELECT m.* FROM stock_master m
+         WHERE m.stock_id IN (
+            SELECT MIN(stock_id) FROM stock_master
+            WHERE merged_into IS NULL
+            GROUP BY LOWER(TRIM(project)), LOWER(TRIM(part_name)), LOWER(TRIM(description)), LOWER(TRIM(uom)), LOWER(TRIM(location))
+         )"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Rows and lookups handled per statement by the bulk import
+const IMPORT_CHUNK_SIZE: usize = 500;
+
+/// Progress of a long-running import step, emitted as the `import-progress` event.
+#[derive(Clone, Serialize)]
+pub struct ImportProgress {
+    /// `matching`, `preview` or `posting`
+    pub stage: &'static str,
+    pub done: usize,
+    pub total: usize,
+}
+
+fn report_progress(app: Option<&tauri::AppHandle>, stage: &'static str, done: usize, total: usize) {
+    if let Some(app) = app {
+        // Progress is only cosmetic; a closed window must not fail the import
+        let _ = app.emit("import-progress", ImportProgress { stage, done, total });
+    }
+}
+
+fn row_identity(row: &ImportRow) -> IdentityKey {
+    (
+        normalize_string(&row.project),
+        normalize_string(&row.part_name),
+        normalize_string(&row.description),
+        normalize_string(&row.uom),
+        normalize_string(&row.location),
+    )
+}
+
+fn master_identity(item: &StockMaster) -> IdentityKey {
+    (
+        normalize_string(&item.project),
+        normalize_string(&item.part_name),
+        normalize_string(&item.description),
+        normalize_string(&item.uom),
+        normalize_string(&item.location),
+    )
+}
+
+/// Items that merged spellings among `identities` now resolve to.
+async fn alias_targets(pool: &MySqlPool, identities: &[IdentityKey]) -> Result<HashMap<IdentityKey, StockMaster>, String> {
+    let mut found = HashMap::new();
+    for chunk in identities.chunks(IMPORT_CHUNK_SIZE) {
+        let values: Vec<[&str; 5]> = chunk.iter().map(identity_values).collect();
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT k.idx, m.*
+             FROM item_alias a
+             JOIN stock_master m ON m.stock_id = a.target_stock_id
+             JOIN "
+        );
+        push_key_table(&mut builder, IDENTITY_COLUMNS, &values);
+        builder.push(format!(
+            " ON {} WHERE m.merged_into IS NULL ORDER BY a.alias_id DESC",
+            identity_match_key("a")
+        ));
+        let rows = builder.build().fetch_all(pool).await.map_err(|e| e.to_string())?;
+        for row in rows {
+            let master = StockMaster::from_row(&row).map_err(|e| e.to_string())?;
+            found.entry(chunk[row.get::<i64, _>("idx") as usize].clone()).or_insert(master);
+        }
+    }
+    Ok(found)
+}
+
+/// Previews upload rows, each with the spreadsheet row it came from when there is
+/// one: matches them to existing items, checks for duplicate receipts and suggests
+/// items for rows that match nothing.
+pub async fn preview_import_rows(
+    pool: &MySqlPool,
+    rows: Vec<(Option<u32>, ImportRow)>,
+    app: Option<&tauri::AppHandle>,
+) -> Result<Vec<ImportPreview>, String> {
+    let locked = locked_through(pool).await?;
+    let definitions = attribute_definitions(pool).await?;
+    let mut rows = rows;
+    // Rows with a bad date or attribute are previewed as ERROR and left out of matching
+    let mut row_errors: Vec<Option<String>> = Vec::with_capacity(rows.len());
+    for (_, row) in rows.iter_mut() {
+        let mut errors = Vec::new();
+        if let Err(e) = receipt_date(row, locked) {
+            errors.push(e);
+        }
+        match attributes::normalize_values(&definitions, &row.attributes) {
+            Ok(values) => row.attributes = values.into_iter().filter(|(_, v)| !v.is_empty()).collect(),
+            Err(e) => errors.push(e),
+        }
+        row_errors.push(if errors.is_empty() { None } else { Some(errors.join("; ")) });
+    }
+
+    // Match on the core identity set, all rows at once
+    let mut identities: Vec<IdentityKey> = rows.iter().map(|(_, row)| row_identity(row)).collect();
+    let mut distinct: Vec<IdentityKey> = identities
+        .iter()
+        .zip(&row_errors)
+        .filter(|(_, error)| error.is_none())
+        .map(|(identity, _)| identity.clone())
+        .collect();
+    distinct.sort();
+    distinct.dedup();
+    let mut existing = masters_by_identity(pool, &distinct, app).await?;
+
+    // Spellings of merged items are taken over by the item they were merged into
+    let unmatched: Vec<IdentityKey> = distinct.into_iter().filter(|i| !existing.contains_key(i)).collect();
+    let aliases = alias_targets(pool, &unmatched).await?;
+    let mut alias_notes: Vec<Option<String>> = vec![None; rows.len()];
+    for ((((_, row), identity), note), error) in rows
+        .iter_mut()
+        .zip(identities.iter_mut())
+        .zip(alias_notes.iter_mut())
+        .zip(&row_errors)
+    {
+        let Some(target) = aliases.get(identity).filter(|_| error.is_none()) else {
+            continue;
+        };
+        *note = Some(format!("'{}' was merged into '{}'", row.part_name.trim(), target.part_name));
+        row.project = target.project.clone();
+        row.part_name = target.part_name.clone();
+        row.description = target.description.clone();
+        row.uom = target.uom.clone();
+        row.location = target.location.clone();
+        *identity = row_identity(row);
+        existing.entry(identity.clone()).or_insert_with(|| vec![target.clone()]);
+    }
+
+    // Earlier receipts of the same invoice can only exist for rows matching an item
+    let receipt_keys: Vec<ReceiptKey> = rows
+        .iter()
+        .zip(&identities)
+        .zip(&row_errors)
+        .filter(|((_, identity), error)| error.is_none() && existing.contains_key(*identity))
+        .filter_map(|(((_, row), _), _)| receipt_key(row))
+        .collect();
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let prior = prior_receipts(&mut conn, &receipt_keys).await?;
+    drop(conn);
+
+    // Loaded only when some row matches nothing, for match suggestions
+    let candidates = if identities
+        .iter()
+        .zip(&row_errors)
+        .any(|(identity, error)| error.is_none() && !existing.contains_key(identity))
+    {
+        distinct_items(pool).await?
+    } else {
+        Vec::new()
+    };
+
+    let total = rows.len();
+    let mut previews = Vec::with_capacity(total);
+    // Receipt lines already seen in this upload
+    let mut seen: HashMap<ReceiptKey, u32> = HashMap::new();
+    // NEW rows to suggest matches for, with the row label of the first row of each
+    // identity, which later rows may be pointed at
+    let mut pending_suggestions: Vec<(usize, Option<u32>)> = Vec::new();
+    let mut new_identities: HashSet<IdentityKey> = HashSet::new();
+
+    for (idx, ((((sheet_row, row), identity), alias_note), error)) in
+        rows.into_iter().zip(identities).zip(alias_notes).zip(row_errors).enumerate()
+    {
+        let row_label = sheet_row.unwrap_or(idx as u32 + 1);
+        let mut preview = ImportPreview {
+            row,
+            status: "NEW".to_string(),
+            diff_reason: None,
+            existing_stock_id: None,
+            sheet_row,
+            matched_stock_ids: Vec::new(),
+            duplicate_of_row: None,
+            duplicate_of_ledger_id: None,
+            override_duplicate: false,
+            override_reason: None,
+            suggestions: Vec::new(),
+        };
+        if let Some(error) = error {
+            preview.status = "ERROR".to_string();
+            preview.diff_reason = Some(error);
+        } else {
+            let existing = existing.get(&identity).map(Vec::as_slice).unwrap_or_default();
+
+            let mut notes: Vec<String> = alias_note.into_iter().collect();
+            if let Some(key) = receipt_key(&preview.row) {
+                preview.duplicate_of_row = seen.get(&key).copied();
+                match preview.duplicate_of_row {
+                    Some(first) => notes.push(format!("Duplicate of row {} in this upload", first)),
+                    None => {
+                        seen.insert(key, row_label);
+                    }
+                }
+            }
+
+            // The oldest record wins; balances are shared across the identity either way
+            if let Some(master) = existing.first() {
+                if existing.len() > 1 {
+                    notes.push(format!("Matches {} existing records", existing.len()));
+                    preview.matched_stock_ids = existing.iter().map(|m| m.stock_id.clone()).collect();
+                }
+                notes.extend(field_differences(&preview.row, master));
+                preview.status = "MERGED".to_string();
+                preview.existing_stock_id = Some(master.stock_id.clone());
+
+                if let Some((ledger_id, received)) = receipt_key(&preview.row).and_then(|key| prior.get(&key)) {
+                    notes.insert(
+                        0,
+                        format!(
+                            "Invoice already received on {} (Ledger ID: {})",
+                            received.format("%Y-%m-%d"),
+                            ledger_id
+                        ),
+                    );
+                    preview.duplicate_of_ledger_id = Some(*ledger_id);
+                }
+            } else {
+                pending_suggestions.push((idx, new_identities.insert(identity).then_some(row_label)));
+                let missing = attributes::missing_required(&definitions, &preview.row.attributes);
+                if !missing.is_empty() {
+                    notes.push(format!("New item needs required attribute(s): {}", missing.join(", ")));
+                }
+            }
+            if preview.duplicate_of_ledger_id.is_some() || preview.duplicate_of_row.is_some() {
+                preview.status = "DUPLICATE".to_string();
+            }
+            if !notes.is_empty() {
+                preview.diff_reason = Some(notes.join("; "));
+            }
+        }
+        previews.push(preview);
+        if (idx + 1) % IMPORT_CHUNK_SIZE == 0 || idx + 1 == total {
+            report_progress(app, "preview", idx + 1, total);
+        }
+    }
+
+    // Scoring is CPU-bound, so it runs off the async runtime
+    if !pending_suggestions.is_empty() {
+        previews = tauri::async_runtime::spawn_blocking(move || {
+            match_suggestions(&mut previews, &pending_suggestions, candidates);
+            previews
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(previews)
+}
+
+/// Posts the previewed rows as one import batch and returns its id. `file_path` is
+/// the uploaded file, recorded by name and hash so re-uploads can be spotted.
+#[tauri::command]
+pub async fn confirm_bulk_upload(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    previews: Vec<ImportPreview>,
+    user: String,
+    file_path: Option<String>,
+) -> Result<i32, String> {
+    post_import(&state.pool, previews, &user, file_path, Some(&app)).await
+}
+
+/// What an upload row repeats, if anything
+enum DuplicateOf {
+    Ledger(i32),
+    /// Index of an earlier row in the same upload
+    Row(usize),
+}
+
+/// Posts previewed rows as one import batch and returns its id.
+pub async fn post_import(
+    pool: &MySqlPool,
+    previews: Vec<ImportPreview>,
+    user: &str,
+    file_path: Option<String>,
+    app: Option<&tauri::AppHandle>,
+) -> Result<i32, String> {
+    if previews.is_empty() {
+        return Err("There are no rows to import".to_string());
+    }
+    let (file_name, file_hash) = match file_path.filter(|p| !p.trim().is_empty()) {
+        Some(file_path) => {
+            let path = std::path::PathBuf::from(file_path);
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string());
+            let hash = tauri::async_runtime::spawn_blocking(move || importer::file_sha256(&path))
+                .await
+                .map_err(|e| e.to_string())??;
+            (name, Some(hash))
+        }
+        None => (None, None),
+    };
+    let total = previews.len();
+    let row_label = |idx: usize| previews[idx].sheet_row.unwrap_or(idx as u32 + 1);
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let locked = locked_through(&mut *tx).await?;
+
+    // 1. Check every row before writing anything
+    let rec_dates = previews
+        .iter()
+        .enumerate()
+        .map(|(idx, preview)| receipt_date(&preview.row, locked).map_err(|e| format!("Row {}: {}", row_label(idx), e)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let definitions = attribute_definitions(&mut *tx).await?;
+    let attribute_values = previews
+        .iter()
+        .enumerate()
+        .map(|(idx, preview)| {
+            attributes::normalize_values(&definitions, &preview.row.attributes)
+                .map(|values| values.into_iter().filter(|(_, v)| !v.is_empty()).collect::<BTreeMap<_, _>>())
+                .map_err(|e| format!("Row {}: {}", row_label(idx), e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // A new item takes the attributes given on any of its rows, and needs the
+    // required ones between them
+    let identities: Vec<IdentityKey> = previews.iter().map(|p| row_identity(&p.row)).collect();
+    let mut new_item_values: HashMap<&IdentityKey, (usize, BTreeMap<String, String>)> = HashMap::new();
+    for (idx, (preview, identity)) in previews.iter().zip(&identities).enumerate() {
+        if preview.existing_stock_id.is_none() {
+            let (_, values) = new_item_values.entry(identity).or_insert_with(|| (idx, BTreeMap::new()));
+            for (name, value) in &attribute_values[idx] {
+                values.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+    let mut new_items_in_order: Vec<&(usize, BTreeMap<String, String>)> = new_item_values.values().collect();
+    new_items_in_order.sort_by_key(|(first, _)| *first);
+    for (first, values) in new_items_in_order {
+        let missing = attributes::missing_required(&definitions, values);
+        if !missing.is_empty() {
+            return Err(format!(
+                "Row {}: '{}' is a new item and needs a value for {}",
+                row_label(*first),
+                previews[*first].row.part_name,
+                missing.join(", ")
+            ));
+        }
+    }
+
+    // Checked again here, and rows earlier in this upload count as receipts too
+    let keys: Vec<Option<ReceiptKey>> = previews.iter().map(|p| receipt_key(&p.row)).collect();
+    let wanted: Vec<ReceiptKey> = keys.iter().flatten().cloned().collect();
+    let prior = prior_receipts(&mut tx, &wanted).await?;
+    let mut first_in_upload: HashMap<&ReceiptKey, usize> = HashMap::new();
+    let mut duplicates: Vec<Option<DuplicateOf>> = Vec::with_capacity(total);
+    for (idx, key) in keys.iter().enumerate() {
+        let duplicate = key.as_ref().and_then(|key| match prior.get(key) {
+            Some((ledger_id, _)) => Some(DuplicateOf::Ledger(*ledger_id)),
+            None => match first_in_upload.get(key) {
+                Some(first) => Some(DuplicateOf::Row(*first)),
+                None => {
+                    first_in_upload.insert(key, idx);
+                    None
+                }
+            },
+        });
+        let row = &previews[idx].row;
+        match (&duplicate, previews[idx].override_duplicate) {
+            (Some(DuplicateOf::Ledger(ledger_id)), false) => return Err(format!(
+                "Row {}: invoice '{}' from '{}' for '{}' has already been received (Ledger ID: {}). Override the duplicate to import it again",
+                row_label(idx), row.invoice, row.supplier_name, row.part_name, ledger_id
+            )),
+            (Some(DuplicateOf::Row(first)), false) => return Err(format!(
+                "Row {}: invoice '{}' from '{}' for '{}' is already received in row {} of this upload. Override the duplicate to import it again",
+                row_label(idx), row.invoice, row.supplier_name, row.part_name, row_label(*first)
+            )),
+            _ => {}
+        }
+        duplicates.push(duplicate);
+    }
+
+    let batch_id = sqlx::query("INSERT INTO import_batch (file_name, file_hash, created_by) VALUES (?, ?, ?)")
+        .bind(&file_name)
+        .bind(&file_hash)
+        .bind(user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .last_insert_id() as i32;
+
+    // 2. One GRN per supplier invoice/PO and receipt date in the upload
+    let mut receipts: HashMap<(String, String, String, Option<NaiveDate>), i32> = HashMap::new();
+    let mut grn_ids = Vec::with_capacity(total);
+    for (preview, rec_date) in previews.iter().zip(&rec_dates) {
+        let receipt_key = (
+            normalize_string(&preview.row.supplier_name),
+            normalize_string(&preview.row.invoice),
+            normalize_string(&preview.row.po_no),
+            rec_date.map(|d| d.date()),
+        );
+        let grn_id = match receipts.get(&receipt_key) {
+            Some(id) => *id,
+            None => {
+                let grn_no = next_document_number(&mut tx, "GRN").await?;
+                let id = sqlx::query("INSERT INTO goods_receipt (grn_no, supplier_name, invoice, po_no, receipt_date, created_by) VALUES (?, ?, ?, ?, COALESCE(?, NOW()), ?)")
+                    .bind(&grn_no)
+                    .bind(&preview.row.supplier_name)
+                    .bind(&preview.row.invoice)
+                    .bind(&preview.row.po_no)
+                    .bind(rec_date)
+                    .bind(user)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .last_insert_id() as i32;
+                receipts.insert(receipt_key, id);
+                id
+            }
+        };
+        grn_ids.push(grn_id);
+    }
+
+    // 3. Items; a NEW item repeated in the same upload is created once
+    let mut created: HashMap<&IdentityKey, String> = HashMap::new();
+    let mut new_items: Vec<(String, &ImportRow)> = Vec::new();
+    let mut stock_ids = Vec::with_capacity(total);
+    for (preview, identity) in previews.iter().zip(&identities) {
+        let stock_id = match (&preview.existing_stock_id, created.get(identity)) {
+            (Some(id), _) | (None, Some(id)) => id.clone(),
+            (None, None) => {
+                let new_id = Uuid::new_v4().to_string();
+                created.insert(identity, new_id.clone());
+                new_items.push((new_id.clone(), &preview.row));
+                new_id
+            }
+        };
+        stock_ids.push(stock_id);
+    }
+    for chunk in new_items.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO stock_master (stock_id, project, supplier_name, invoice, po_no, part_name, description, uom, location, remarks, batch_id) "
+        );
+        builder.push_values(chunk, |mut b, (stock_id, row)| {
+            b.push_bind(stock_id)
+                .push_bind(&row.project)
+                .push_bind(&row.supplier_name)
+                .push_bind(&row.invoice)
+                .push_bind(&row.po_no)
+                .push_bind(&row.part_name)
+                .push_bind(&row.description)
+                .push_bind(&row.uom)
+                .push_bind(&row.location)
+                .push_bind(&row.remarks)
+                .push_bind(batch_id);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    }
+
+    // 4. Purchase order lines
+    let po_receipts: Vec<(IdentityKey, String, Decimal)> = previews
+        .iter()
+        .zip(&identities)
+        .map(|(p, identity)| (identity.clone(), p.row.po_no.clone(), p.row.quantity))
+        .collect();
+    let po_line_ids = receive_against_purchase_orders(&mut tx, &po_receipts).await?;
+
+    // 5. Ledger rows. Remarks stay with the receipt line, even when the row merges
+    // into an existing item
+    for (n, chunk) in previews.chunks(IMPORT_CHUNK_SIZE).enumerate() {
+        let offset = n * IMPORT_CHUNK_SIZE;
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by, grn_id, unit_cost, batch_id, po_line_id) "
+        );
+        builder.push_values(chunk.iter().enumerate(), |mut b, (i, preview)| {
+            let idx = offset + i;
+            let remarks = preview.row.remarks.as_deref().map(str::trim).filter(|r| !r.is_empty());
+            b.push_bind(&stock_ids[idx])
+                .push("'IN'")
+                .push_bind(preview.row.quantity)
+                .push("COALESCE(")
+                .push_bind_unseparated(rec_dates[idx])
+                .push_unseparated(", NOW())")
+                .push_bind(format!("Excel Import: {} | Supplier: {}", preview.row.invoice, preview.row.supplier_name))
+                .push_bind(remarks)
+                .push_bind(user)
+                .push_bind(grn_ids[idx])
+                .push_bind(preview.row.unit_cost)
+                .push_bind(batch_id)
+                .push_bind(po_line_ids[idx]);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+        report_progress(app, "posting", offset + chunk.len(), total);
+    }
+    // Ids rise in insert order, and only this transaction writes rows of the batch
+    let ledger_ids: Vec<i32> = sqlx::query_scalar("SELECT ledger_id FROM stock_ledger WHERE batch_id = ? ORDER BY ledger_id")
+        .bind(batch_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if ledger_ids.len() != total {
+        return Err(format!("Expected {} ledger rows for import batch {}, found {}", total, batch_id, ledger_ids.len()));
+    }
+
+    // Attribute values only fill in what an item doesn't have yet
+    let values: Vec<(&str, i32, &str)> = stock_ids
+        .iter()
+        .zip(&attribute_values)
+        .flat_map(|(stock_id, values)| {
+            values.iter().filter_map(|(name, value)| {
+                let attribute_id = attributes::find(&definitions, name)?.attribute_id?;
+                Some((stock_id.as_str(), attribute_id, value.as_str()))
+            })
+        })
+        .collect();
+    for chunk in values.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT IGNORE INTO item_attribute_value (stock_id, attribute_id, value) "
+        );
+        builder.push_values(chunk, |mut b, (stock_id, attribute_id, value)| {
+            b.push_bind(*stock_id).push_bind(*attribute_id).push_bind(*value);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    }
+
+    // Receipts bring archived items back into the inventory
+    let mut received: Vec<&str> = previews.iter().filter_map(|p| p.existing_stock_id.as_deref()).collect();
+    received.sort();
+    received.dedup();
+    set_items_status(&mut tx, &received, "ACTIVE", user).await?;
+
+    // 6. Overridden duplicates
+    let overrides: Vec<(i32, i32, &ImportPreview)> = duplicates
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, duplicate)| {
+            let original = match duplicate.as_ref()? {
+                DuplicateOf::Ledger(ledger_id) => *ledger_id,
+                DuplicateOf::Row(first) => ledger_ids[*first],
+            };
+            Some((ledger_ids[idx], original, &previews[idx]))
+        })
+        .collect();
+    for chunk in overrides.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO import_duplicate_override (ledger_id, duplicate_of_ledger_id, supplier_name, invoice, reason, overridden_by) "
+        );
+        builder.push_values(chunk, |mut b, (ledger_id, original, preview)| {
+            b.push_bind(*ledger_id)
+                .push_bind(*original)
+                .push_bind(&preview.row.supplier_name)
+                .push_bind(&preview.row.invoice)
+                .push_bind(preview.override_reason.as_deref().map(str::trim).filter(|r| !r.is_empty()))
+                .push_bind(user);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    }
+
+    let merged_count = previews.iter().filter(|p| p.existing_stock_id.is_some()).count();
+    let total_quantity: Decimal = previews.iter().map(|p| p.row.quantity).sum();
+    sqlx::query("UPDATE import_batch SET row_count = ?, new_item_count = ?, merged_count = ?, duplicate_count = ?, total_quantity = ? WHERE batch_id = ?")
+        .bind(total as i32)
+        .bind(new_items.len() as i32)
+        .bind(merged_count as i32)
+        .bind(overrides.len() as i32)
+        .bind(total_quantity)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(batch_id)
+}
+
+#[derive(Serialize)]
+pub struct ImportBatchResponse {
+    pub items: Vec<ImportBatch>,
+    pub total_count: i64,
+}
+
+#[tauri::command]
+pub async fn get_import_batches(
+    state: tauri::State<'_, AppState>,
+    page: i32,
+    page_size: i32,
+) -> Result<ImportBatchResponse, String> {
+    let total_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM import_batch")
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let items = sqlx::query_as::<_, ImportBatch>("SELECT * FROM import_batch ORDER BY created_at DESC, batch_id DESC LIMIT ? OFFSET ?")
+        .bind(page_size)
+        .bind((page - 1) * page_size)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ImportBatchResponse { items, total_count })
+}
+
+/// Reverses every receipt in an import batch in one transaction, including what it
+/// booked against purchase orders. Refused once any of the items received has been
+/// issued after the batch was posted. Items the batch created are left in place with
+/// a zero balance so the audit trail stays intact.
+#[tauri::command]
+pub async fn revert_import_batch(
+    state: tauri::State<'_, AppState>,
+    batch_id: i32,
+    user: String,
+) -> Result<(), String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+
+    let status: Option<String> = sqlx::query_scalar("SELECT status FROM import_batch WHERE batch_id = ? FOR UPDATE")
+        .bind(batch_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    match status.as_deref() {
+        None => return Err(format!("Import batch {} not found", batch_id)),
+        Some("REVERTED") => return Err(format!("Import batch {} has already been reverted", batch_id)),
+        _ => {}
+    }
+
+    // Any unreversed issue of a received item posted after the batch's first row
+    let issued: Option<String> = sqlx::query_scalar(&format!(
+        "SELECT MAX(om.part_name)
+         FROM stock_ledger b
+         JOIN stock_master bm ON b.stock_id = bm.stock_id
+         JOIN stock_master om ON {}
+         JOIN stock_ledger o ON o.stock_id = om.stock_id
+         WHERE b.batch_id = ?
+           AND o.transaction_type = 'OUT'
+           AND o.ledger_id > (SELECT MIN(ledger_id) FROM stock_ledger WHERE batch_id = ?)
+           AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = o.ledger_id)",
+        identity_match("bm", "om")
+    ))
+    .bind(batch_id)
+    .bind(batch_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    if let Some(part) = issued {
+        return Err(format!(
+            "Cannot revert import batch {}: '{}' has been issued since it was imported",
+            batch_id, part
+        ));
+    }
+
+    let rows = sqlx::query(
+        "SELECT l.ledger_id, l.stock_id, l.quantity_change, l.reference, l.po_line_id
+         FROM stock_ledger l
+         WHERE l.batch_id = ?
+           AND NOT EXISTS (SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id)
+         FOR UPDATE"
+    )
+    .bind(batch_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for row in rows {
+        let ledger_id: i32 = row.get("ledger_id");
+        let stock_id: String = row.get("stock_id");
+        let qty: Decimal = row.get("quantity_change");
+        let reference: Option<String> = row.get("reference");
+        let po_line_id: Option<i32> = row.get("po_line_id");
+
+        sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by, reverses_ledger_id) VALUES (?, 'REVERSAL', ?, NOW(), ?, ?, ?, ?)")
+            .bind(&stock_id)
+            .bind(-qty)
+            .bind(format!("Reversal of Ledger ID: {}", ledger_id))
+            .bind(format!("Import batch {} reverted | Original Ref: {}", batch_id, reference.unwrap_or_default()))
+            .bind(&user)
+            .bind(ledger_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Only lines the receipts closed are reopened; lines closed by hand stay closed
+        if let Some(po_line_id) = po_line_id {
+            sqlx::query(
+                "UPDATE purchase_order_line
+                 SET received_qty = received_qty - ?,
+                     status = IF(closed_by = 'RECEIPT' AND received_qty < ordered_qty, 'OPEN', status),
+                     closed_by = IF(status = 'OPEN', NULL, closed_by)
+                 WHERE po_line_id = ?"
+            )
+            .bind(qty)
+            .bind(po_line_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    sqlx::query("UPDATE import_batch SET status = 'REVERTED', reverted_by = ?, reverted_at = NOW() WHERE batch_id = ?")
+        .bind(&user)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_duplicate_overrides(state: tauri::State<'_, AppState>) -> Result<Vec<DuplicateOverride>, String> {
+    sqlx::query_as::<_, DuplicateOverride>(
+        "SELECT o.*, m.part_name, l.quantity_change
+         FROM import_duplicate_override o
+         JOIN stock_ledger l ON l.ledger_id = o.ledger_id
+         JOIN stock_master m ON m.stock_id = l.stock_id
+         ORDER BY o.created_at DESC"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    pub items: Vec<HistoryEntry>,
+    pub total_count: i64,
+}
+
+#[tauri::command]
+pub async fn get_history(
+    state: tauri::State<'_, AppState>,
+    page: i32,
+    page_size: i32,
+    search: Option<String>,
 ) -> Result<HistoryResponse, String> {
     let offset = (page - 1) * page_size;
     
@@ -392,149 +4154,2106 @@ pub async fn get_history(
         }
     }
 
-    // 2. Get Total Count
-    let count_query = format!("SELECT COUNT(*) FROM stock_ledger l JOIN stock_master m ON l.stock_id = m.stock_id {}", where_clause);
-    let total_count: i64 = sqlx::query_scalar(&count_query)
-        .fetch_one(&state.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+    // 2. Get Total Count
+    let count_query = format!("SELECT COUNT(*) FROM stock_ledger l JOIN stock_master m ON l.stock_id = m.stock_id {}", where_clause);
+    let total_count: i64 = sqlx::query_scalar(&count_query)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 3. Fetch Data
+    let query = format!(
+        "SELECT l.*, m.part_name, m.description,
+        EXISTS(SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id) as is_already_reversed
+        FROM stock_ledger l 
+        JOIN stock_master m ON l.stock_id = m.stock_id
+        {} 
+        ORDER BY l.transaction_date DESC LIMIT ? OFFSET ?",
+        where_clause
+    );
+
+    let items = sqlx::query_as::<sqlx::MySql, HistoryEntry>(&query)
+        .bind(page_size)
+        .bind(offset)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(HistoryResponse { items, total_count })
+}
+
+/// Streams the audit trail straight from the database to `file_path`, so large
+/// exports never pass through the webview. Returns the rows written; when there
+/// are none, no file is left.
+#[tauri::command]
+pub async fn export_history(
+    state: tauri::State<'_, AppState>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    status: Option<String>,
+    file_path: String,
+) -> Result<usize, String> {
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut binds: Vec<String> = Vec::new();
+    if let Some(from) = parse_report_date(date_from)? {
+        conditions.push("l.transaction_date >= ?");
+        binds.push(from);
+    }
+    if let Some(to) = parse_report_date(date_to)? {
+        conditions.push("l.transaction_date <= ?");
+        binds.push(format!("{} 23:59:59", to));
+    }
+    if let Some(s) = status.filter(|s| !s.is_empty() && s != "All") {
+        conditions.push("l.transaction_type = ?");
+        binds.push(s);
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let query = format!(
+        "SELECT l.*, m.part_name, m.description,
+        EXISTS(SELECT 1 FROM stock_ledger r WHERE r.reverses_ledger_id = l.ledger_id) as is_already_reversed
+        FROM stock_ledger l 
+        JOIN stock_master m ON l.stock_id = m.stock_id
+        {}
+        ORDER BY l.transaction_date DESC",
+        where_clause
+    );
+
+    let columns = [
+        ExportColumn::new("Date", ColumnKind::Date),
+        ExportColumn::new("Ledger ID", ColumnKind::Integer),
+        ExportColumn::new("Type", ColumnKind::Text),
+        ExportColumn::new("Part Name", ColumnKind::Text),
+        ExportColumn::new("Description", ColumnKind::Text),
+        ExportColumn::new("Quantity", ColumnKind::Quantity),
+        ExportColumn::new("Unit Cost", ColumnKind::Money),
+        ExportColumn::new("Reference", ColumnKind::Text),
+        ExportColumn::new("Reason", ColumnKind::Text),
+        ExportColumn::new("User", ColumnKind::Text),
+        ExportColumn::new("Reversed", ColumnKind::Text),
+    ];
+    let mut writer = TableWriter::create(std::path::Path::new(&file_path), "Audit Trail", &columns)?;
+
+    let mut q = sqlx::query_as::<sqlx::MySql, HistoryEntry>(&query);
+    for value in &binds {
+        q = q.bind(value);
+    }
+    let mut rows = q.fetch(&state.pool);
+    while let Some(entry) = rows.try_next().await.map_err(|e| e.to_string())? {
+        writer.write_row(vec![
+            entry.transaction_date.into(),
+            entry.ledger_id.into(),
+            entry.transaction_type.into(),
+            entry.part_name.into(),
+            entry.description.into(),
+            entry.quantity_change.into(),
+            entry.unit_cost.into(),
+            entry.reference.into(),
+            entry.optional_reason.into(),
+            entry.created_by.into(),
+            if entry.is_already_reversed.unwrap_or(false) { "Yes" } else { "No" }.into(),
+        ])?;
+    }
+    let count = writer.finish()?;
+    // Nothing matched the filters, so don't leave an empty workbook behind
+    if count == 0 {
+        std::fs::remove_file(&file_path).map_err(|e| e.to_string())?;
+    }
+    Ok(count)
+}
+
+#[derive(Serialize)]
+pub struct StockCard {
+    pub item: StockMaster,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub opening_balance: Decimal,
+    pub total_in: Decimal,
+    pub total_out: Decimal,
+    pub closing_balance: Decimal,
+    pub entries: Vec<StockCardEntry>,
+}
+
+/// Validates an optional `YYYY-MM-DD` report filter; blank means no filter.
+fn parse_report_date(value: Option<String>) -> Result<Option<String>, String> {
+    match value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        Some(v) => chrono::NaiveDate::parse_from_str(&v, "%Y-%m-%d")
+            .map(|d| Some(d.format("%Y-%m-%d").to_string()))
+            .map_err(|_| format!("Invalid date '{}'. Use YYYY-MM-DD", v)),
+        None => Ok(None),
+    }
+}
+
+/// Movement ledger for one item identity over an optional date range, with the
+/// balance brought forward, a running balance per row and reversal pairs linked.
+#[tauri::command]
+pub async fn get_stock_card(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<StockCard, String> {
+    stock_card(&state.pool, stock_id, date_from, date_to).await
+}
+
+async fn stock_card(
+    pool: &MySqlPool,
+    stock_id: String,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<StockCard, String> {
+    let item = sqlx::query_as::<_, StockMaster>("SELECT * FROM stock_master WHERE stock_id = ?")
+        .bind(&stock_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Stock item {} not found", stock_id))?;
+
+    let date_from = parse_report_date(date_from)?;
+    let date_to = parse_report_date(date_to)?;
+    if let (Some(from), Some(to)) = (&date_from, &date_to) {
+        if from > to {
+            return Err("Start date must be on or before end date".to_string());
+        }
+    }
+
+    let identity_join = format!(
+        "JOIN stock_master m ON l.stock_id = m.stock_id JOIN stock_master s ON s.stock_id = ? AND {}",
+        identity_match("m", "s")
+    );
+
+    let opening_balance: Decimal = match &date_from {
+        Some(from) => sqlx::query_scalar(&format!(
+            "SELECT COALESCE(SUM(l.quantity_change), 0) FROM stock_ledger l {} WHERE l.transaction_date < ?",
+            identity_join
+        ))
+        .bind(&stock_id)
+        .bind(from)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?,
+        None => Decimal::ZERO,
+    };
+
+    let mut conditions = Vec::new();
+    let mut binds = Vec::new();
+    if let Some(from) = &date_from {
+        conditions.push("l.transaction_date >= ?");
+        binds.push(from.clone());
+    }
+    if let Some(to) = &date_to {
+        conditions.push("l.transaction_date <= ?");
+        binds.push(format!("{} 23:59:59", to));
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let query_str = format!(
+        "SELECT l.ledger_id, l.stock_id, l.transaction_type, l.quantity_change, l.transaction_date,
+                l.reference, l.optional_reason, l.created_by, l.unit_cost, l.reverses_ledger_id
+         FROM stock_ledger l {} {}
+         ORDER BY l.transaction_date ASC, l.ledger_id ASC",
+        identity_join, where_clause
+    );
+    let mut query = sqlx::query_as::<_, StockCardEntry>(&query_str).bind(&stock_id);
+    for value in &binds {
+        query = query.bind(value);
+    }
+    let mut entries = query
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Reversals may be posted after the period closes, so link against all of them
+    let reversal_rows = sqlx::query(&format!(
+        "SELECT l.ledger_id, l.reverses_ledger_id FROM stock_ledger l {} WHERE l.reverses_ledger_id IS NOT NULL",
+        identity_join
+    ))
+    .bind(&stock_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut reversed_by: HashMap<i32, i32> = HashMap::new();
+    for row in reversal_rows {
+        reversed_by.entry(row.get("reverses_ledger_id")).or_insert(row.get("ledger_id"));
+    }
+
+    let mut balance = opening_balance;
+    let mut total_in = Decimal::ZERO;
+    let mut total_out = Decimal::ZERO;
+    for entry in entries.iter_mut() {
+        balance += entry.quantity_change;
+        if entry.quantity_change.is_sign_positive() {
+            total_in += entry.quantity_change;
+        } else {
+            total_out -= entry.quantity_change;
+        }
+        entry.running_balance = balance;
+        entry.reversed_by_ledger_id = reversed_by.get(&entry.ledger_id).copied();
+    }
+
+    Ok(StockCard {
+        item,
+        date_from,
+        date_to,
+        opening_balance,
+        total_in,
+        total_out,
+        closing_balance: balance,
+        entries,
+    })
+}
+
+/// Writes a stock card to `file_path`, with the opening and closing balances as the
+/// first and last rows. Returns the movement rows written.
+#[tauri::command]
+pub async fn export_stock_card(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    file_path: String,
+) -> Result<usize, String> {
+    let card = stock_card(&state.pool, stock_id, date_from, date_to).await?;
+
+    let columns = [
+        ExportColumn::new("Date", ColumnKind::Date),
+        ExportColumn::new("Ledger ID", ColumnKind::Integer),
+        ExportColumn::new("Type", ColumnKind::Text),
+        ExportColumn::new("Reference", ColumnKind::Text),
+        ExportColumn::new("In", ColumnKind::Quantity),
+        ExportColumn::new("Out", ColumnKind::Quantity),
+        ExportColumn::new("Balance", ColumnKind::Quantity),
+        ExportColumn::new("Unit Cost", ColumnKind::Money),
+        ExportColumn::new("Reversal Of", ColumnKind::Integer),
+        ExportColumn::new("Reversed By", ColumnKind::Integer),
+        ExportColumn::new("User", ColumnKind::Text),
+    ];
+    let balance_row = |label: &str, balance: Decimal| {
+        let mut cells = vec![Cell::Empty; columns.len()];
+        cells[2] = label.into();
+        cells[6] = balance.into();
+        cells
+    };
+
+    let mut writer = TableWriter::create(std::path::Path::new(&file_path), "Stock Card", &columns)?;
+    writer.write_row(balance_row("Opening Balance", card.opening_balance))?;
+    let count = card.entries.len();
+    for entry in card.entries {
+        let (qty_in, qty_out) = if entry.quantity_change.is_sign_negative() {
+            (None, Some(-entry.quantity_change))
+        } else {
+            (Some(entry.quantity_change), None)
+        };
+        writer.write_row(vec![
+            entry.transaction_date.into(),
+            entry.ledger_id.into(),
+            entry.transaction_type.into(),
+            entry.reference.into(),
+            qty_in.into(),
+            qty_out.into(),
+            entry.running_balance.into(),
+            entry.unit_cost.into(),
+            entry.reverses_ledger_id.into(),
+            entry.reversed_by_ledger_id.into(),
+            entry.created_by.into(),
+        ])?;
+    }
+    writer.write_row(balance_row("Closing Balance", card.closing_balance))?;
+    writer.finish()?;
+    Ok(count)
+}
+
+#[tauri::command]
+pub async fn reverse_transaction(
+    state: tauri::State<'_, AppState>,
+    ledger_id: i32,
+    user: String,
+) -> Result<(), String> {
+    // 1. Check if already reversed
+    let already_reversed: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM stock_ledger WHERE reverses_ledger_id = ?)"
+    )
+    .bind(ledger_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if already_reversed {
+        return Err("This transaction has already been reversed.".to_string());
+    }
+
+    // 2. Get original details
+    let original = sqlx::query("SELECT * FROM stock_ledger WHERE ledger_id = ?")
+        .bind(ledger_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let stock_id: String = original.get("stock_id");
+    let qty: Decimal = original.get("quantity_change");
+    let reference: String = original.get("reference");
+
+    // 3. Insert reversal entry
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by, reverses_ledger_id) VALUES (?, 'REVERSAL', ?, NOW(), ?, ?, ?, ?)")
+        .bind(&stock_id)
+        .bind(-qty)
+        .bind(format!("Reversal of Ledger ID: {}", ledger_id))
+        .bind(format!("Original Ref: {}", reference))
+        .bind(&user)
+        .bind(ledger_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Reversing an issue puts stock back, which brings an archived item back too
+    if qty < Decimal::ZERO {
+        set_items_status(&mut tx, &[stock_id.as_str()], "ACTIVE", &user).await?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_stock_entry(
+    state: tauri::State<'_, AppState>,
+    row: ImportRow,
+    user: String,
+) -> Result<(), String> {
+    let preview = preview_import_rows(&state.pool, vec![(None, row)], None).await?;
+    if let Some(error) = preview.iter().find(|p| p.status == "ERROR") {
+        return Err(error.diff_reason.clone().unwrap_or_default());
+    }
+    post_import(&state.pool, preview, &user, None, None).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_stock_quantity(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    quantity: Decimal,
+    user: String,
+) -> Result<(), String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+
+    // Insert positive ledger entry (IN)
+    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, created_by) VALUES (?, 'IN', ?, NOW(), ?, ?)")
+        .bind(&stock_id)
+        .bind(quantity)
+        .bind("Manual Stock Addition")
+        .bind(&user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Stock arriving for an archived item brings it back
+    set_items_status(&mut tx, &[stock_id.as_str()], "ACTIVE", &user).await?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Corrections to an item's master data. Fields left out are kept.
+#[derive(Debug, Default, Deserialize)]
+pub struct ItemUpdate {
+    #[serde(default)]
+    pub part_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub remarks: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ItemUpdateResult {
+    /// Fields that changed on the item
+    pub changed_fields: Vec<String>,
+    /// Existing item the new values would make this one identical to. Nothing was
+    /// saved; the two can be merged instead.
+    pub conflicts_with: Option<StockMaster>,
+}
+
+/// Corrects an item's part name, description, location or remarks and records each
+/// change in `item_history`. Identity fields change on every record sharing the
+/// item's identity, so the group stays together; remarks only on `stock_id`.
+#[tauri::command]
+pub async fn update_item(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+    changes: ItemUpdate,
+    user: String,
+) -> Result<ItemUpdateResult, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let members = sqlx::query_as::<_, StockMaster>(&format!(
+        "SELECT m.* FROM stock_master m
+         JOIN stock_master target ON {}
+         WHERE target.stock_id = ? AND m.merged_into IS NULL
+         ORDER BY m.created_at, m.stock_id
+         FOR UPDATE",
+        identity_match("m", "target")
+    ))
+    .bind(&stock_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let item = members
+        .iter()
+        .find(|m| m.stock_id == stock_id)
+        .ok_or_else(|| format!("Stock item {} not found", stock_id))?;
+
+    let required = |field: &str, value: Option<String>, current: &str, max: usize| -> Result<String, String> {
+        let value = value.map(|v| v.trim().to_string()).unwrap_or_else(|| current.to_string());
+        if value.is_empty() {
+            return Err(format!("{} cannot be empty", field));
+        }
+        if value.chars().count() > max {
+            return Err(format!("{} is longer than {} characters", field, max));
+        }
+        Ok(value)
+    };
+    let part_name = required("Part name", changes.part_name, &item.part_name, 100)?;
+    let location = required("Location", changes.location, &item.location, 100)?;
+    let description = changes
+        .description
+        .map(|d| d.trim().to_string())
+        .unwrap_or_else(|| item.description.clone());
+    let remarks = match changes.remarks {
+        Some(r) => Some(r.trim().to_string()).filter(|r| !r.is_empty()),
+        None => item.remarks.clone(),
+    };
+
+    // (stock_id, field, old, new)
+    let mut history: Vec<(&str, &str, Option<&str>, Option<&str>)> = Vec::new();
+    for member in &members {
+        for (field, old, new) in [
+            ("part_name", &member.part_name, &part_name),
+            ("description", &member.description, &description),
+            ("location", &member.location, &location),
+        ] {
+            if old != new {
+                history.push((&member.stock_id, field, Some(old), Some(new)));
+            }
+        }
+    }
+    if item.remarks != remarks {
+        history.push((&item.stock_id, "remarks", item.remarks.as_deref(), remarks.as_deref()));
+    }
+    let changed_fields: Vec<String> = history
+        .iter()
+        .filter(|(id, ..)| *id == stock_id)
+        .map(|(_, field, ..)| field.to_string())
+        .collect();
+    if history.is_empty() {
+        return Ok(ItemUpdateResult { changed_fields, conflicts_with: None });
+    }
+
+    let renamed = normalize_string(&part_name) != normalize_string(&item.part_name)
+        || normalize_string(&description) != normalize_string(&item.description)
+        || normalize_string(&location) != normalize_string(&item.location);
+    if renamed {
+        let conflict = sqlx::query_as::<_, StockMaster>(
+            "SELECT * FROM stock_master
+             WHERE merged_into IS NULL
+               AND LOWER(TRIM(project)) = LOWER(TRIM(?)) AND LOWER(TRIM(part_name)) = LOWER(TRIM(?))
+               AND LOWER(TRIM(description)) = LOWER(TRIM(?)) AND LOWER(TRIM(uom)) = LOWER(TRIM(?))
+               AND LOWER(TRIM(location)) = LOWER(TRIM(?))
+             ORDER BY created_at, stock_id
+             LIMIT 1"
+        )
+        .bind(&item.project)
+        .bind(&part_name)
+        .bind(&description)
+        .bind(&item.uom)
+        .bind(&location)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|m| !members.iter().any(|member| member.stock_id == m.stock_id));
+        if conflict.is_some() {
+            return Ok(ItemUpdateResult { changed_fields: Vec::new(), conflicts_with: conflict });
+        }
+    }
+
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("UPDATE stock_master SET part_name = ");
+    builder
+        .push_bind(&part_name)
+        .push(", description = ")
+        .push_bind(&description)
+        .push(", location = ")
+        .push_bind(&location)
+        .push(", remarks = IF(stock_id = ")
+        .push_bind(&stock_id)
+        .push(", ")
+        .push_bind(&remarks)
+        .push(", remarks) WHERE stock_id IN ");
+    builder.push_tuples(&members, |mut b, member| {
+        b.push_bind(&member.stock_id);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_history (stock_id, field_name, old_value, new_value, changed_by) "
+    );
+    builder.push_values(&history, |mut b, (id, field, old, new)| {
+        b.push_bind(*id).push_bind(*field).push_bind(*old).push_bind(*new).push_bind(&user);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(ItemUpdateResult { changed_fields, conflicts_with: None })
+}
+
+/// Changes to the item and every record sharing its identity, newest first.
+#[tauri::command]
+pub async fn get_item_history(
+    state: tauri::State<'_, AppState>,
+    stock_id: String,
+) -> Result<Vec<ItemHistory>, String> {
+    sqlx::query_as::<_, ItemHistory>(&format!(
+        "SELECT h.* FROM item_history h
+         JOIN stock_master m ON h.stock_id = m.stock_id
+         JOIN stock_master target ON {}
+         WHERE target.stock_id = ?
+         ORDER BY h.changed_at DESC, h.history_id DESC",
+        identity_match("m", "target")
+    ))
+    .bind(&stock_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Tables pointing at an item, with their primary key, item column and the
+/// condition picking out rows that are about items, that a merge repoints to the
+/// target.
+const ITEM_LINKS: [(&str, &str, &str, &str); 7] = [
+    ("stock_ledger", "ledger_id", "stock_id", "TRUE"),
+    ("purchase_order_line", "po_line_id", "stock_id", "TRUE"),
+    ("stock_reservation", "reservation_id", "stock_id", "TRUE"),
+    ("requisition_line", "line_id", "stock_id", "TRUE"),
+    ("issue_slip_line", "line_id", "stock_id", "TRUE"),
+    ("item_alias", "alias_id", "target_stock_id", "TRUE"),
+    ("attachment", "attachment_id", "entity_id", "entity_type = 'ITEM'"),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeReport {
+    #[serde(default)]
+    pub merge_id: i32,
+    pub target: StockMaster,
+    /// Records folded into the target, including others sharing a source's identity
+    pub merged_items: Vec<StockMaster>,
+    /// Rows repointed to the target, by table
+    pub moved_rows: BTreeMap<String, u64>,
+    /// Net ledger quantity the merged items brought to the target
+    pub quantity_moved: Decimal,
+    /// Whether the target took over a merged item's stock threshold
+    pub threshold_moved: bool,
+    /// Spellings kept as aliases for future imports
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub merged_by: Option<String>,
+    #[serde(default)]
+    pub merged_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub undone_by: Option<String>,
+    #[serde(default)]
+    pub undone_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Loads and locks an item that hasn't been merged away.
+async fn lock_active_item(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    stock_id: &str,
+) -> Result<StockMaster, String> {
+    let row = sqlx::query("SELECT * FROM stock_master WHERE stock_id = ? FOR UPDATE")
+        .bind(stock_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Stock item {} not found", stock_id))?;
+    let item = StockMaster::from_row(&row).map_err(|e| e.to_string())?;
+    let merged_into: Option<String> = row.get("merged_into");
+    match merged_into {
+        Some(target) => Err(format!("'{}' has already been merged into item {}", item.part_name, target)),
+        None => Ok(item),
+    }
+}
+
+/// Active records sharing `stock_id`'s identity, locked for the rest of `tx`.
+async fn lock_identity_group(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    stock_id: &str,
+) -> Result<Vec<StockMaster>, String> {
+    sqlx::query_as::<_, StockMaster>(&format!(
+        "SELECT m.* FROM stock_master m
+         JOIN stock_master target ON {}
+         WHERE target.stock_id = ? AND m.merged_into IS NULL
+         ORDER BY m.created_at, m.stock_id
+         FOR UPDATE",
+        identity_match("m", "target")
+    ))
+    .bind(stock_id)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+fn item_spelling(item: &StockMaster) -> String {
+    [&item.part_name, &item.description, &item.uom, &item.location]
+        .iter()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Folds duplicate items into `target_id` in one transaction: ledger entries, PO
+/// lines, reservations, requisition and issue slip lines move to the target, which
+/// also takes over a threshold and any attribute values it has none of, and is
+/// restored if it was archived and stock moves onto it. The merged records stay
+/// behind, hidden, and their spellings are kept as aliases so imports resolve them
+/// to the target. Undo with `undo_item_merge` until the next period close.
+#[tauri::command]
+pub async fn merge_items(
+    state: tauri::State<'_, AppState>,
+    source_ids: Vec<String>,
+    target_id: String,
+    user: String,
+) -> Result<MergeReport, String> {
+    merge(&state.pool, source_ids, target_id, user).await
+}
+
+async fn merge(
+    pool: &MySqlPool,
+    source_ids: Vec<String>,
+    target_id: String,
+    user: String,
+) -> Result<MergeReport, String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let target = lock_active_item(&mut tx, &target_id).await?;
+    let target_group: HashSet<String> = lock_identity_group(&mut tx, &target_id)
+        .await?
+        .into_iter()
+        .map(|m| m.stock_id)
+        .collect();
+
+    // Each source brings every record sharing its identity
+    let mut merged_items: Vec<StockMaster> = Vec::new();
+    for source_id in &source_ids {
+        let source = lock_active_item(&mut tx, source_id).await?;
+        if target_group.contains(&source.stock_id) {
+            return Err(format!("'{}' is already the same item as the target", source.part_name));
+        }
+        if normalize_string(&source.project) != normalize_string(&target.project) {
+            return Err(format!(
+                "'{}' belongs to project '{}', not '{}'",
+                source.part_name, source.project, target.project
+            ));
+        }
+        if matching::uom_similarity(&source.uom, &target.uom) < 1.0 {
+            return Err(format!("'{}' is counted in '{}', not '{}'", source.part_name, source.uom, target.uom));
+        }
+        for member in lock_identity_group(&mut tx, source_id).await? {
+            if !merged_items.iter().any(|m| m.stock_id == member.stock_id) {
+                merged_items.push(member);
+            }
+        }
+    }
+    if merged_items.is_empty() {
+        return Err("Select at least one item to merge".to_string());
+    }
+    let merged_ids: Vec<&String> = merged_items.iter().map(|m| &m.stock_id).collect();
+
+    let merge_id = sqlx::query("INSERT INTO item_merge (target_stock_id, report, merged_by) VALUES (?, '{}', ?)")
+        .bind(&target_id)
+        .bind(&user)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .last_insert_id() as i32;
+
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "SELECT COALESCE(SUM(quantity_change), 0) FROM stock_ledger WHERE stock_id IN "
+    );
+    builder.push_tuples(&merged_ids, |mut b, id| {
+        b.push_bind(*id);
+    });
+    let quantity_moved: Decimal = builder
+        .build_query_scalar()
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Remember where every row pointed, then repoint it
+    let mut moved_rows = BTreeMap::new();
+    for (table, key, column, condition) in ITEM_LINKS {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO item_merge_row (merge_id, table_name, row_id, from_stock_id) SELECT "
+        );
+        builder
+            .push_bind(merge_id)
+            .push(", ")
+            .push_bind(table)
+            .push(format!(", {key}, {column} FROM {table} WHERE {condition} AND {column} IN "));
+        builder.push_tuples(&merged_ids, |mut b, id| {
+            b.push_bind(*id);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(format!("UPDATE {table} SET {column} = "));
+        builder.push_bind(&target_id).push(format!(" WHERE {condition} AND {column} IN "));
+        builder.push_tuples(&merged_ids, |mut b, id| {
+            b.push_bind(*id);
+        });
+        let moved = builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?.rows_affected();
+        moved_rows.insert(table.to_string(), moved);
+    }
+
+    // A target that now holds stock can't stay archived
+    if quantity_moved > Decimal::ZERO {
+        set_items_status(&mut tx, &[target_id.as_str()], "ACTIVE", &user).await?;
+    }
+
+    // Thresholds are one per record, so only move one over if the target has none
+    let target_threshold: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM stock_threshold t
+         JOIN stock_master m ON t.stock_id = m.stock_id
+         JOIN stock_master target ON {}
+         WHERE target.stock_id = ?",
+        identity_match("m", "target")
+    ))
+    .bind(&target_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut threshold_moved = false;
+    if target_threshold == 0 {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("SELECT stock_id FROM stock_threshold WHERE stock_id IN ");
+        builder.push_tuples(&merged_ids, |mut b, id| {
+            b.push_bind(*id);
+        });
+        builder.push(" ORDER BY updated_at DESC LIMIT 1");
+        let source: Option<String> = builder
+            .build_query_scalar()
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(source) = source {
+            sqlx::query("INSERT INTO item_merge_row (merge_id, table_name, row_id, from_stock_id) VALUES (?, 'stock_threshold', ?, ?)")
+                .bind(merge_id)
+                .bind(&source)
+                .bind(&source)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            sqlx::query("UPDATE stock_threshold SET stock_id = ? WHERE stock_id = ?")
+                .bind(&target_id)
+                .bind(&source)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            threshold_moved = true;
+        }
+    }
+
+    // Attribute values are one per record and attribute, so the target only takes
+    // over those it has none for. The row id kept is the attribute's.
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_merge_row (merge_id, table_name, row_id, from_stock_id) SELECT "
+    );
+    builder
+        .push_bind(merge_id)
+        .push(", 'item_attribute_value', v.attribute_id, MIN(v.stock_id) FROM item_attribute_value v")
+        .push(" WHERE v.stock_id IN ");
+    builder.push_tuples(&merged_ids, |mut b, id| {
+        b.push_bind(*id);
+    });
+    builder
+        .push(format!(
+            " AND v.attribute_id NOT IN (
+                SELECT tv.attribute_id FROM item_attribute_value tv
+                JOIN stock_master m ON tv.stock_id = m.stock_id
+                JOIN stock_master target ON {}
+                WHERE target.stock_id = ",
+            identity_match("m", "target")
+        ))
+        .push_bind(&target_id)
+        .push(") GROUP BY v.attribute_id");
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    let moved = sqlx::query(
+        "UPDATE item_attribute_value v
+         JOIN item_merge_row r ON r.merge_id = ? AND r.table_name = 'item_attribute_value'
+           AND r.row_id = CAST(v.attribute_id AS CHAR) AND r.from_stock_id = v.stock_id
+         SET v.stock_id = ?"
+    )
+    .bind(merge_id)
+    .bind(&target_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .rows_affected();
+    moved_rows.insert("item_attribute_value".to_string(), moved);
+
+    // One alias per merged spelling
+    let mut spellings: HashMap<IdentityKey, &StockMaster> = HashMap::new();
+    for item in &merged_items {
+        spellings.entry(master_identity(item)).or_insert(item);
+    }
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_alias (merge_id, project, part_name, description, uom, location, target_stock_id) "
+    );
+    builder.push_values(spellings.values(), |mut b, item| {
+        b.push_bind(merge_id)
+            .push_bind(&item.project)
+            .push_bind(&item.part_name)
+            .push_bind(&item.description)
+            .push_bind(&item.uom)
+            .push_bind(&item.location)
+            .push_bind(&target_id);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_merge_row (merge_id, table_name, row_id, from_stock_id) "
+    );
+    builder.push_values(&merged_ids, |mut b, id| {
+        b.push_bind(merge_id).push_bind("stock_master").push_bind(*id).push_bind(*id);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("UPDATE stock_master SET merged_into = ");
+    builder.push_bind(&target_id).push(" WHERE stock_id IN ");
+    builder.push_tuples(&merged_ids, |mut b, id| {
+        b.push_bind(*id);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_history (stock_id, field_name, old_value, new_value, changed_by) "
+    );
+    builder.push_values(&merged_ids, |mut b, id| {
+        b.push_bind(*id)
+            .push_bind("merged_into")
+            .push_bind(None::<String>)
+            .push_bind(&target_id)
+            .push_bind(&user);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+    let mut aliases: Vec<String> = spellings.values().map(|item| item_spelling(item)).collect();
+    aliases.sort();
+    let mut report = MergeReport {
+        merge_id,
+        target,
+        merged_items,
+        moved_rows,
+        quantity_moved,
+        threshold_moved,
+        aliases,
+        merged_by: Some(user),
+        merged_at: Some(chrono::Utc::now()),
+        undone_by: None,
+        undone_at: None,
+    };
+    sqlx::query("UPDATE item_merge SET report = ? WHERE merge_id = ?")
+        .bind(serde_json::to_string(&report).map_err(|e| e.to_string())?)
+        .bind(merge_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    report.merge_id = merge_id;
+    Ok(report)
+}
+
+/// Puts a merge back: every repointed row returns to its item, the merged items
+/// reappear and their aliases go. Only possible until the books are next closed,
+/// and refused if it would leave an item with a negative balance.
+#[tauri::command]
+pub async fn undo_item_merge(
+    state: tauri::State<'_, AppState>,
+    merge_id: i32,
+    user: String,
+) -> Result<(), String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let merge = sqlx::query_as::<_, ItemMerge>("SELECT * FROM item_merge WHERE merge_id = ? FOR UPDATE")
+        .bind(merge_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Merge {} not found", merge_id))?;
+    if merge.undone_at.is_some() {
+        return Err(format!("Merge {} has already been undone", merge_id));
+    }
+    let closed: Option<NaiveDate> = sqlx::query_scalar("SELECT MAX(closed_through) FROM period_close WHERE closed_at >= ?")
+        .bind(merge.merged_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(closed) = closed {
+        return Err(format!(
+            "The books have been closed through {} since this merge, so it can no longer be undone",
+            closed
+        ));
+    }
+    let target = lock_active_item(&mut tx, &merge.target_stock_id)
+        .await
+        .map_err(|_| "The target item has since been merged into another item; undo that merge first".to_string())?;
+
+    for (table, key, column, condition) in ITEM_LINKS {
+        sqlx::query(&format!(
+            "UPDATE {table} t
+             JOIN item_merge_row r ON r.merge_id = ? AND r.table_name = ? AND r.row_id = t.{key}
+             SET t.{column} = r.from_stock_id
+             WHERE {condition} AND t.{column} = ?"
+        ))
+        .bind(merge_id)
+        .bind(table)
+        .bind(&target.stock_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    sqlx::query(
+        "UPDATE stock_threshold t
+         JOIN item_merge_row r ON r.merge_id = ? AND r.table_name = 'stock_threshold'
+         SET t.stock_id = r.from_stock_id
+         WHERE t.stock_id = ?"
+    )
+    .bind(merge_id)
+    .bind(&target.stock_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query(
+        "UPDATE item_attribute_value v
+         JOIN item_merge_row r ON r.merge_id = ? AND r.table_name = 'item_attribute_value'
+           AND r.row_id = CAST(v.attribute_id AS CHAR)
+         SET v.stock_id = r.from_stock_id
+         WHERE v.stock_id = ?"
+    )
+    .bind(merge_id)
+    .bind(&target.stock_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM item_alias WHERE merge_id = ?")
+        .bind(merge_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let merged_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT row_id FROM item_merge_row WHERE merge_id = ? AND table_name = 'stock_master'"
+    )
+    .bind(merge_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    for stock_id in &merged_ids {
+        sqlx::query("UPDATE stock_master SET merged_into = NULL WHERE stock_id = ?")
+            .bind(stock_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("INSERT INTO item_history (stock_id, field_name, old_value, new_value, changed_by) VALUES (?, 'merged_into', ?, NULL, ?)")
+            .bind(stock_id)
+            .bind(&target.stock_id)
+            .bind(&user)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Stock issued from the target since the merge may have come from a merged item
+    let mut stocked: Vec<&str> = Vec::new();
+    for stock_id in std::iter::once(&target.stock_id).chain(&merged_ids) {
+        let balance = available_for_identity(&mut *tx, stock_id, false).await?;
+        if balance > Decimal::ZERO {
+            stocked.push(stock_id);
+        }
+        if balance < Decimal::ZERO {
+            let part_name: String = sqlx::query_scalar("SELECT part_name FROM stock_master WHERE stock_id = ?")
+                .bind(stock_id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            return Err(format!(
+                "Undoing the merge would leave '{}' with a balance of {}",
+                part_name,
+                balance.normalize()
+            ));
+        }
+    }
+
+    // Records holding stock again after the undo can't stay archived
+    set_items_status(&mut tx, &stocked, "ACTIVE", &user).await?;
+
+    sqlx::query("UPDATE item_merge SET undone_by = ?, undone_at = NOW() WHERE merge_id = ?")
+        .bind(&user)
+        .bind(merge_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_item_merges(state: tauri::State<'_, AppState>) -> Result<Vec<MergeReport>, String> {
+    let merges = sqlx::query_as::<_, ItemMerge>("SELECT * FROM item_merge ORDER BY merge_id DESC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    merges
+        .into_iter()
+        .map(|merge| {
+            let mut report: MergeReport = serde_json::from_str(&merge.report)
+                .map_err(|e| format!("Merge {} report is corrupt: {}", merge.merge_id, e))?;
+            report.merge_id = merge.merge_id;
+            report.merged_by = merge.merged_by;
+            report.merged_at = merge.merged_at;
+            report.undone_by = merge.undone_by;
+            report.undone_at = merge.undone_at;
+            Ok(report)
+        })
+        .collect()
+}
+
+/// Sets `status` on every active record sharing an identity with `stock_ids` and
+/// logs the change in item_history. Returns the records changed.
+async fn set_items_status(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    stock_ids: &[&str],
+    status: &str,
+    user: &str,
+) -> Result<Vec<String>, String> {
+    let mut changed: Vec<String> = Vec::new();
+    for chunk in stock_ids.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(format!(
+            "SELECT DISTINCT m.stock_id FROM stock_master m
+             JOIN stock_master target ON {}
+             WHERE m.merged_into IS NULL AND m.status <> ",
+            identity_match("m", "target")
+        ));
+        builder.push_bind(status).push(" AND target.stock_id IN ");
+        builder.push_tuples(chunk, |mut b, id| {
+            b.push_bind(*id);
+        });
+        builder.push(" FOR UPDATE");
+        let ids: Vec<String> = builder
+            .build_query_scalar()
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        changed.extend(ids);
+    }
+    let mut seen = HashSet::new();
+    changed.retain(|id| seen.insert(id.clone()));
+    let previous = if status == "ACTIVE" { "ARCHIVED" } else { "ACTIVE" };
+
+    for chunk in changed.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("UPDATE stock_master SET status = ");
+        builder.push_bind(status).push(" WHERE stock_id IN ");
+        builder.push_tuples(chunk, |mut b, id| {
+            b.push_bind(id);
+        });
+        builder.build().execute(&mut **tx).await.map_err(|e| e.to_string())?;
+
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "INSERT INTO item_history (stock_id, field_name, old_value, new_value, changed_by) "
+        );
+        builder.push_values(chunk, |mut b, id| {
+            b.push_bind(id).push_bind("status").push_bind(previous).push_bind(status).push_bind(user);
+        });
+        builder.build().execute(&mut **tx).await.map_err(|e| e.to_string())?;
+    }
+    Ok(changed)
+}
+
+/// Archives items, hiding them from the inventory without touching their history.
+/// Refused for any item with stock on hand. Returns the records archived.
+#[tauri::command]
+pub async fn archive_items(
+    state: tauri::State<'_, AppState>,
+    stock_ids: Vec<String>,
+    user: String,
+) -> Result<usize, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    for stock_id in &stock_ids {
+        let item = lock_active_item(&mut tx, stock_id).await?;
+        let on_hand = available_for_identity(&mut *tx, stock_id, true).await?;
+        if !on_hand.is_zero() {
+            return Err(format!(
+                "'{}' still has {} {} on hand and can't be archived",
+                item.part_name,
+                on_hand.normalize(),
+                item.uom
+            ));
+        }
+    }
+    let ids: Vec<&str> = stock_ids.iter().map(String::as_str).collect();
+    let archived = set_items_status(&mut tx, &ids, "ARCHIVED", &user).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(archived.len())
+}
+
+/// Brings archived items back into the inventory. Returns the records restored.
+#[tauri::command]
+pub async fn restore_items(
+    state: tauri::State<'_, AppState>,
+    stock_ids: Vec<String>,
+    user: String,
+) -> Result<usize, String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    for stock_id in &stock_ids {
+        lock_active_item(&mut tx, stock_id).await?;
+    }
+    let ids: Vec<&str> = stock_ids.iter().map(String::as_str).collect();
+    let restored = set_items_status(&mut tx, &ids, "ACTIVE", &user).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(restored.len())
+}
+
+/// Items with nothing on hand and no movement for their dead-stock period
+/// (per-item threshold, else the `dead_stock_days` setting), aged from their last
+/// ledger entry or, without one, from when they were created. Archives them unless
+/// `dry_run` is set, and returns them either way.
+#[tauri::command]
+pub async fn archive_dead_stock(
+    state: tauri::State<'_, AppState>,
+    dry_run: Option<bool>,
+    user: String,
+) -> Result<Vec<ArchiveCandidate>, String> {
+    let default_dead = setting_days(&state.pool, "dead_stock_days", 90).await?;
+    let query = format!(
+        "SELECT
+            MAX(m.stock_id) as stock_id,
+            MAX(m.project) as project,
+            MAX(m.part_name) as part_name,
+            MAX(m.description) as description,
+            MAX(m.uom) as uom,
+            MAX(m.location) as location,
+            (
+                SELECT MAX(l2.transaction_date)
+                FROM stock_ledger l2
+                JOIN stock_master m2 ON l2.stock_id = m2.stock_id
+                WHERE {}
+            ) as last_movement,
+            (
+                SELECT COALESCE(SUM(l3.quantity_change), 0)
+                FROM stock_ledger l3
+                JOIN stock_master m3 ON l3.stock_id = m3.stock_id
+                WHERE {}
+            ) as on_hand_quantity,
+            MIN(m.created_at) as created_at,
+            MAX(t.dead_stock_days) as dead_stock_days
+        FROM stock_master m
+        LEFT JOIN stock_threshold t ON m.stock_id = t.stock_id
+        WHERE m.merged_into IS NULL AND m.status = 'ACTIVE'
+        GROUP BY m.project, m.part_name, m.uom, m.location, m.description
+        HAVING on_hand_quantity = 0
+        ORDER BY MAX(m.part_name)",
+        identity_match("m2", "m"),
+        identity_match("m3", "m"),
+    );
+    let items = sqlx::query_as::<sqlx::MySql, ArchiveCandidate>(&query)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let now = chrono::Utc::now();
+    let candidates: Vec<ArchiveCandidate> = items
+        .into_iter()
+        .filter_map(|mut item| {
+            let dead = item.dead_stock_days.map(i64::from).unwrap_or(default_dead);
+            item.days_since_movement = item.last_movement.or(item.created_at).map(|d| (now - d).num_days()).unwrap_or(0);
+            (item.days_since_movement >= dead).then_some(item)
+        })
+        .collect();
+
+    if !dry_run.unwrap_or(false) && !candidates.is_empty() {
+        let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+        // Re-checked under lock, in case stock arrived since the scan
+        for item in &candidates {
+            let on_hand = available_for_identity(&mut *tx, &item.stock_id, true).await?;
+            if !on_hand.is_zero() {
+                return Err(format!("'{}' has received stock since the scan; run it again", item.part_name));
+            }
+        }
+        let ids: Vec<&str> = candidates.iter().map(|c| c.stock_id.as_str()).collect();
+        set_items_status(&mut tx, &ids, "ARCHIVED", &user).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    Ok(candidates)
+}
+
+fn attribute_from_record(record: AttributeDefinitionRecord) -> Result<AttributeDefinition, String> {
+    let data_type = AttributeType::from_name(&record.data_type)
+        .ok_or_else(|| format!("Attribute '{}' has unknown type '{}'", record.name, record.data_type))?;
+    let allowed_values = match record.allowed_values.as_deref().filter(|v| !v.trim().is_empty()) {
+        Some(json) => serde_json::from_str(json).map_err(|e| format!("Attribute '{}' is corrupt: {}", record.name, e))?,
+        None => Vec::new(),
+    };
+    Ok(AttributeDefinition {
+        attribute_id: Some(record.attribute_id),
+        name: record.name,
+        data_type,
+        required: record.required,
+        allowed_values,
+        sort_order: record.sort_order,
+    })
+}
+
+async fn attribute_definitions<'e, E>(executor: E) -> Result<Vec<AttributeDefinition>, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let records = sqlx::query_as::<_, AttributeDefinitionRecord>(
+        "SELECT * FROM attribute_definition ORDER BY sort_order, name"
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| e.to_string())?;
+    records.into_iter().map(attribute_from_record).collect()
+}
+
+/// Attribute values of each of `stock_ids` by attribute name, gathered from every
+/// record sharing its identity.
+async fn item_attribute_values(
+    conn: &mut sqlx::MySqlConnection,
+    stock_ids: &[&str],
+) -> Result<HashMap<String, BTreeMap<String, String>>, String> {
+    let mut values: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+    for chunk in stock_ids.chunks(IMPORT_CHUNK_SIZE) {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(format!(
+            "SELECT target.stock_id, d.name, MAX(v.value)
+             FROM stock_master target
+             JOIN stock_master m ON {}
+             JOIN item_attribute_value v ON v.stock_id = m.stock_id
+             JOIN attribute_definition d ON d.attribute_id = v.attribute_id
+             WHERE m.merged_into IS NULL AND target.stock_id IN ",
+            identity_match("m", "target")
+        ));
+        builder.push_tuples(chunk, |mut b, id| {
+            b.push_bind(*id);
+        });
+        builder.push(" GROUP BY target.stock_id, d.name");
+        let rows: Vec<(String, String, String)> = builder
+            .build_query_as()
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        for (stock_id, name, value) in rows {
+            values.entry(stock_id).or_default().insert(name, value);
+        }
+    }
+    Ok(values)
+}
+
+#[tauri::command]
+pub async fn get_attribute_definitions(state: tauri::State<'_, AppState>) -> Result<Vec<AttributeDefinition>, String> {
+    attribute_definitions(&state.pool).await
+}
 
-    // 3. Fetch Data
-    let query = format!(
-        "SELECT l.*, m.part_name, m.description,
-        EXISTS(SELECT 1 FROM stock_ledger WHERE reference LIKE CONCAT('%Ledger ID: ', l.ledger_id, '%') AND transaction_type = 'REVERSAL') as is_already_reversed
-        FROM stock_ledger l 
-        JOIN stock_master m ON l.stock_id = m.stock_id
-        {} 
-        ORDER BY l.transaction_date DESC LIMIT ? OFFSET ?",
-        where_clause
-    );
+/// Creates an attribute, or changes the one with `attribute_id`. A change of type or
+/// allowed values is refused while any item holds a value that wouldn't fit; values
+/// that fit are rewritten in the new normalized form. Making an attribute required
+/// applies to new items and later edits, not to items already without a value.
+#[tauri::command]
+pub async fn save_attribute_definition(
+    state: tauri::State<'_, AppState>,
+    mut definition: AttributeDefinition,
+    user: String,
+) -> Result<AttributeDefinition, String> {
+    definition.validate()?;
+    definition.name = definition.name.trim().to_string();
+    definition.allowed_values = if definition.data_type == AttributeType::List {
+        definition
+            .allowed_values
+            .iter()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let allowed_values = match definition.data_type {
+        AttributeType::List => Some(serde_json::to_string(&definition.allowed_values).map_err(|e| e.to_string())?),
+        _ => None,
+    };
+    let duplicate_name = |e: sqlx::Error| match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            format!("An attribute named '{}' already exists", definition.name)
+        }
+        e => e.to_string(),
+    };
 
-    let items = sqlx::query_as::<sqlx::MySql, HistoryEntry>(&query)
-        .bind(page_size)
-        .bind(offset)
-        .fetch_all(&state.pool)
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    match definition.attribute_id {
+        Some(id) => {
+            let exists: Option<i32> =
+                sqlx::query_scalar("SELECT attribute_id FROM attribute_definition WHERE attribute_id = ? FOR UPDATE")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            if exists.is_none() {
+                return Err(format!("Attribute {} not found", id));
+            }
+
+            let stored: Vec<(String, String)> =
+                sqlx::query_as("SELECT stock_id, value FROM item_attribute_value WHERE attribute_id = ? FOR UPDATE")
+                    .bind(id)
+                    .fetch_all(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            let mut rewritten: Vec<(String, String)> = Vec::new();
+            let mut invalid = 0;
+            for (stock_id, value) in stored {
+                match definition.normalize_value(&value) {
+                    Ok(normalized) if normalized != value => rewritten.push((stock_id, normalized)),
+                    Ok(_) => {}
+                    Err(_) => invalid += 1,
+                }
+            }
+            if invalid > 0 {
+                return Err(format!(
+                    "{} item record(s) have '{}' values that don't fit the new definition",
+                    invalid, definition.name
+                ));
+            }
+
+            sqlx::query(
+                "UPDATE attribute_definition
+                 SET name = ?, data_type = ?, required = ?, allowed_values = ?, sort_order = ?, created_by = ?
+                 WHERE attribute_id = ?"
+            )
+            .bind(&definition.name)
+            .bind(definition.data_type.as_str())
+            .bind(definition.required)
+            .bind(&allowed_values)
+            .bind(definition.sort_order)
+            .bind(&user)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(duplicate_name)?;
+
+            for chunk in rewritten.chunks(IMPORT_CHUNK_SIZE) {
+                let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+                    "INSERT INTO item_attribute_value (stock_id, attribute_id, value) "
+                );
+                builder.push_values(chunk, |mut b, (stock_id, value)| {
+                    b.push_bind(stock_id).push_bind(id).push_bind(value);
+                });
+                builder.push(" ON DUPLICATE KEY UPDATE value = VALUES(value)");
+                builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+            }
+        }
+        None => {
+            let result = sqlx::query(
+                "INSERT INTO attribute_definition (name, data_type, required, allowed_values, sort_order, created_by)
+                 VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&definition.name)
+            .bind(definition.data_type.as_str())
+            .bind(definition.required)
+            .bind(&allowed_values)
+            .bind(definition.sort_order)
+            .bind(&user)
+            .execute(&mut *tx)
+            .await
+            .map_err(duplicate_name)?;
+            definition.attribute_id = Some(result.last_insert_id() as i32);
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(definition)
+}
+
+/// Removes an attribute along with every item's value for it.
+#[tauri::command]
+pub async fn delete_attribute_definition(state: tauri::State<'_, AppState>, attribute_id: i32) -> Result<(), String> {
+    sqlx::query("DELETE FROM attribute_definition WHERE attribute_id = ?")
+        .bind(attribute_id)
+        .execute(&state.pool)
         .await
         .map_err(|e| e.to_string())?;
-
-    Ok(HistoryResponse { items, total_count })
+    Ok(())
 }
 
+/// Sets attribute values, keyed by attribute name, on an item and every record
+/// sharing its identity, logging each change in item_history. A blank value clears
+/// the attribute, except for required ones. Attributes left out are kept. Returns
+/// the item's attributes after the change.
 #[tauri::command]
-pub async fn get_export_history(
+pub async fn set_item_attributes(
     state: tauri::State<'_, AppState>,
-    date_from: Option<String>,
-    date_to: Option<String>,
-    status: Option<String>,
-) -> Result<Vec<HistoryEntry>, String> {
-    let mut where_clause = String::from("WHERE 1=1");
-    
-    if let Some(from) = date_from {
-        if !from.is_empty() {
-            where_clause.push_str(&format!(" AND l.transaction_date >= '{}'", from));
-        }
+    stock_id: String,
+    values: BTreeMap<String, String>,
+    user: String,
+) -> Result<BTreeMap<String, String>, String> {
+    let definitions = attribute_definitions(&state.pool).await?;
+    let values = attributes::normalize_values(&definitions, &values)?;
+    let cleared: Vec<&str> = attributes::missing_required(&definitions, &values)
+        .into_iter()
+        .filter(|name| values.contains_key(*name))
+        .collect();
+    if !cleared.is_empty() {
+        return Err(format!("Required attribute(s) can't be cleared: {}", cleared.join(", ")));
     }
-    if let Some(to) = date_to {
-        if !to.is_empty() {
-            where_clause.push_str(&format!(" AND l.transaction_date <= '{} 23:59:59'", to)); // End of day
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    lock_active_item(&mut tx, &stock_id).await?;
+    let members = lock_identity_group(&mut tx, &stock_id).await?;
+    let current = item_attribute_values(&mut tx, &[stock_id.as_str()])
+        .await?
+        .remove(&stock_id)
+        .unwrap_or_default();
+
+    let mut updated = current.clone();
+    // (attribute, old, new)
+    let mut changes: Vec<(&AttributeDefinition, Option<&str>, Option<&str>)> = Vec::new();
+    for (name, value) in &values {
+        let old = current.get(name).map(String::as_str);
+        let new = Some(value.as_str()).filter(|v| !v.is_empty());
+        if old == new {
+            continue;
         }
+        let definition = attributes::find(&definitions, name).ok_or_else(|| format!("Unknown attribute '{}'", name))?;
+        changes.push((definition, old, new));
+        match new {
+            Some(v) => updated.insert(name.clone(), v.to_string()),
+            None => updated.remove(name),
+        };
+    }
+    if changes.is_empty() {
+        return Ok(updated);
     }
-    if let Some(s) = status {
-        if !s.is_empty() && s != "All" {
-            where_clause.push_str(&format!(" AND l.transaction_type = '{}'", s));
+
+    for (definition, _, new) in &changes {
+        let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new("DELETE FROM item_attribute_value WHERE attribute_id = ");
+        builder.push_bind(definition.attribute_id).push(" AND stock_id IN ");
+        builder.push_tuples(&members, |mut b, member| {
+            b.push_bind(&member.stock_id);
+        });
+        builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+        if let Some(value) = new {
+            let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+                "INSERT INTO item_attribute_value (stock_id, attribute_id, value) "
+            );
+            builder.push_values(&members, |mut b, member| {
+                b.push_bind(&member.stock_id).push_bind(definition.attribute_id).push_bind(*value);
+            });
+            builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
         }
     }
 
-    let query = format!(
-        "SELECT l.*, m.part_name, m.description,
-        EXISTS(SELECT 1 FROM stock_ledger WHERE reference LIKE CONCAT('%Ledger ID: ', l.ledger_id, '%') AND transaction_type = 'REVERSAL') as is_already_reversed
-        FROM stock_ledger l 
-        JOIN stock_master m ON l.stock_id = m.stock_id
-        {}
-        ORDER BY l.transaction_date DESC",
-        where_clause
+    let history: Vec<(&str, String, Option<&str>, Option<&str>)> = members
+        .iter()
+        .flat_map(|member| {
+            changes.iter().map(|(definition, old, new)| {
+                (member.stock_id.as_str(), format!("{}{}", attributes::FIELD_PREFIX, definition.name), *old, *new)
+            })
+        })
+        .collect();
+    let mut builder = sqlx::QueryBuilder::<sqlx::MySql>::new(
+        "INSERT INTO item_history (stock_id, field_name, old_value, new_value, changed_by) "
     );
+    builder.push_values(&history, |mut b, (id, field, old, new)| {
+        b.push_bind(*id).push_bind(field).push_bind(*old).push_bind(*new).push_bind(&user);
+    });
+    builder.build().execute(&mut *tx).await.map_err(|e| e.to_string())?;
 
-    let items = sqlx::query_as::<sqlx::MySql, HistoryEntry>(&query)
-        .fetch_all(&state.pool)
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(updated)
+}
+
+/// Folder attachments are stored in: the `attachment_folder` setting, so several
+/// workstations can share one, else `attachments` under the app data directory.
+/// Files are found by hash, so changing the setting means moving the files too.
+async fn attachment_root(app: &tauri::AppHandle, pool: &MySqlPool) -> Result<std::path::PathBuf, String> {
+    let configured: Option<String> =
+        sqlx::query_scalar("SELECT setting_value FROM app_setting WHERE setting_key = 'attachment_folder'")
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .flatten();
+    match configured.map(|f| f.trim().to_string()).filter(|f| !f.is_empty()) {
+        Some(folder) => Ok(std::path::PathBuf::from(folder)),
+        None => Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("attachments")),
+    }
+}
+
+/// Checks that the item or document exists and returns its type and id as stored:
+/// `ITEM` by stock id, `GRN` by GRN number, `ISSUE_SLIP` by slip number.
+async fn attachment_entity(pool: &MySqlPool, entity_type: &str, entity_id: &str) -> Result<(&'static str, String), String> {
+    let entity_id = entity_id.trim();
+    let (entity_type, query, label) = match entity_type.trim().to_uppercase().as_str() {
+        "ITEM" => ("ITEM", "SELECT stock_id FROM stock_master WHERE stock_id = ?", "Stock item"),
+        "GRN" => ("GRN", "SELECT grn_no FROM goods_receipt WHERE grn_no = ?", "Goods receipt"),
+        "ISSUE_SLIP" => ("ISSUE_SLIP", "SELECT slip_no FROM issue_slip WHERE slip_no = ?", "Issue slip"),
+        other => return Err(format!("Unsupported attachment type: {}", other)),
+    };
+    let found: Option<String> = sqlx::query_scalar(query)
+        .bind(entity_id)
+        .fetch_optional(pool)
         .await
         .map_err(|e| e.to_string())?;
+    found
+        .map(|id| (entity_type, id))
+        .ok_or_else(|| format!("{} {} not found", label, entity_id))
+}
 
-    Ok(items)
+async fn load_attachment(pool: &MySqlPool, attachment_id: i32) -> Result<Attachment, String> {
+    sqlx::query_as::<_, Attachment>("SELECT * FROM attachment WHERE attachment_id = ?")
+        .bind(attachment_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Attachment {} not found", attachment_id))
 }
 
+/// Attaches a copy of the file at `file_path` to an item, goods receipt or issue
+/// slip. The same file attached twice to one record is only kept once.
 #[tauri::command]
-pub async fn reverse_transaction(
+pub async fn add_attachment(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-    ledger_id: i32,
+    entity_type: String,
+    entity_id: String,
+    file_path: String,
+    description: Option<String>,
     user: String,
-) -> Result<(), String> {
-    // 1. Check if already reversed
-    let already_reversed: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM stock_ledger WHERE reference LIKE CONCAT('%Ledger ID: ', ?, '%') AND transaction_type = 'REVERSAL')"
+) -> Result<Attachment, String> {
+    let (entity_type, entity_id) = attachment_entity(&state.pool, &entity_type, &entity_id).await?;
+    let source = std::path::PathBuf::from(&file_path);
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("'{}' is not a file", file_path))?;
+    if file_name.chars().count() > 255 {
+        return Err("File name is longer than 255 characters".to_string());
+    }
+
+    let root = attachment_root(&app, &state.pool).await?;
+    let stored_root = root.clone();
+    let (file_hash, size) = tauri::async_runtime::spawn_blocking(move || attachments::store(&stored_root, &source))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let existing = sqlx::query_as::<_, Attachment>(
+        "SELECT * FROM attachment WHERE entity_type = ? AND entity_id = ? AND file_hash = ? LIMIT 1 FOR UPDATE"
     )
-    .bind(ledger_id)
-    .fetch_one(&state.pool)
+    .bind(entity_type)
+    .bind(&entity_id)
+    .bind(&file_hash)
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|e| e.to_string())?;
+    if let Some(existing) = existing {
+        return Ok(existing);
+    }
 
-    if already_reversed {
-        return Err("This transaction has already been reversed.".to_string());
+    let description = description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    let attachment_id = sqlx::query(
+        "INSERT INTO attachment (entity_type, entity_id, file_name, file_hash, mime_type, size_bytes, description, uploaded_by)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(entity_type)
+    .bind(&entity_id)
+    .bind(&file_name)
+    .bind(&file_hash)
+    .bind(attachments::mime_type(&file_name))
+    .bind(size as i64)
+    .bind(&description)
+    .bind(&user)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .last_insert_id() as i32;
+
+    // A remove of the last attachment with this content may have deleted the file
+    // since it was stored. It has committed by now, as the insert waits on its lock
+    // of the hash, so store the file again.
+    if !attachments::stored_path(&root, &file_hash).exists() {
+        let source = std::path::PathBuf::from(&file_path);
+        let (stored_hash, _) = tauri::async_runtime::spawn_blocking(move || attachments::store(&root, &source))
+            .await
+            .map_err(|e| e.to_string())??;
+        if stored_hash != file_hash {
+            return Err(format!("'{}' changed while it was being attached; try again", file_name));
+        }
     }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    load_attachment(&state.pool, attachment_id).await
+}
 
-    // 2. Get original details
-    let original = sqlx::query("SELECT * FROM stock_ledger WHERE ledger_id = ?")
-        .bind(ledger_id)
-        .fetch_one(&state.pool)
+/// Attachments of an item, goods receipt or issue slip, newest first. An item's
+/// include those on every record sharing its identity.
+#[tauri::command]
+pub async fn get_attachments(
+    state: tauri::State<'_, AppState>,
+    entity_type: String,
+    entity_id: String,
+) -> Result<Vec<Attachment>, String> {
+    let (entity_type, entity_id) = attachment_entity(&state.pool, &entity_type, &entity_id).await?;
+    let query = if entity_type == "ITEM" {
+        format!(
+            "SELECT a.* FROM attachment a
+             WHERE a.entity_type = ? AND a.entity_id IN (
+                 SELECT m.stock_id FROM stock_master m
+                 JOIN stock_master target ON {}
+                 WHERE target.stock_id = ?
+             )
+             ORDER BY a.uploaded_at DESC, a.attachment_id DESC",
+            identity_match("m", "target")
+        )
+    } else {
+        "SELECT a.* FROM attachment a WHERE a.entity_type = ? AND a.entity_id = ?
+         ORDER BY a.uploaded_at DESC, a.attachment_id DESC"
+            .to_string()
+    };
+    sqlx::query_as::<_, Attachment>(&query)
+        .bind(entity_type)
+        .bind(&entity_id)
+        .fetch_all(&state.pool)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())
+}
 
-    let stock_id: String = original.get("stock_id");
-    let qty: Decimal = original.get("quantity_change");
-    let reference: String = original.get("reference");
+/// Opens an attachment in the program the system uses for its file type.
+#[tauri::command]
+pub async fn open_attachment(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    attachment_id: i32,
+) -> Result<(), String> {
+    let attachment = load_attachment(&state.pool, attachment_id).await?;
+    let root = attachment_root(&app, &state.pool).await?;
+    let path = tauri::async_runtime::spawn_blocking(move || {
+        attachments::checkout(&root, &attachment.file_hash, &attachment.file_name)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    app.opener()
+        .open_path(path.to_string_lossy().to_string(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
 
-    // 3. Insert reversal entry
-    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, optional_reason, created_by) VALUES (?, 'REVERSAL', ?, NOW(), ?, ?, ?)")
-        .bind(stock_id)
-        .bind(-qty)
-        .bind(format!("Reversal of Ledger ID: {}", ledger_id))
-        .bind(format!("Original Ref: {}", reference))
-        .bind(user)
-        .execute(&state.pool)
+/// Removes an attachment. The stored file is deleted once nothing else points at
+/// the same content.
+#[tauri::command]
+pub async fn remove_attachment(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    attachment_id: i32,
+) -> Result<(), String> {
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let file_hash: String = sqlx::query_scalar("SELECT file_hash FROM attachment WHERE attachment_id = ? FOR UPDATE")
+        .bind(attachment_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Attachment {} not found", attachment_id))?;
+    sqlx::query("DELETE FROM attachment WHERE attachment_id = ?")
+        .bind(attachment_id)
+        .execute(&mut *tx)
         .await
         .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
 
+    // The file goes once the removal is committed and nothing else points at its
+    // content. The count locks the hash in the index, so an add of the same content
+    // waits until the file is gone and then stores it again
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    let still_used: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachment WHERE file_hash = ? FOR UPDATE")
+        .bind(&file_hash)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if still_used == 0 {
+        let root = attachment_root(&app, &state.pool).await?;
+        tauri::async_runtime::spawn_blocking(move || attachments::remove(&root, &file_hash))
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn add_stock_entry(
+pub async fn get_settings(
     state: tauri::State<'_, AppState>,
-    row: ImportRow,
+) -> Result<HashMap<String, String>, String> {
+    let rows = sqlx::query("SELECT setting_key, COALESCE(setting_value, '') FROM app_setting")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+#[tauri::command]
+pub async fn update_setting(
+    state: tauri::State<'_, AppState>,
+    key: String,
+    value: Option<String>,
     user: String,
 ) -> Result<(), String> {
-    let preview = bulk_upload_preview(state.clone(), vec![row]).await?;
-    confirm_bulk_upload(state, preview, user).await?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("Setting key is required".to_string());
+    }
+
+    sqlx::query("INSERT INTO app_setting (setting_key, setting_value, updated_by) VALUES (?, ?, ?)
+                 ON DUPLICATE KEY UPDATE setting_value = VALUES(setting_value), updated_by = VALUES(updated_by)")
+        .bind(key)
+        .bind(value)
+        .bind(user)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
     Ok(())
 }
 
+/// Latest date the books are closed through. Nothing may be posted on or before it.
+async fn locked_through<'e, E>(executor: E) -> Result<Option<NaiveDate>, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    sqlx::query_scalar("SELECT MAX(closed_through) FROM period_close")
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Closes the books through `through_date` (YYYY-MM-DD). Only whole past days can
+/// be closed, and a close can't move backwards.
 #[tauri::command]
-pub async fn add_stock_quantity(
+pub async fn close_period(
     state: tauri::State<'_, AppState>,
-    stock_id: String,
-    quantity: Decimal,
+    through_date: String,
     user: String,
 ) -> Result<(), String> {
-    
-    // Insert positive ledger entry (IN)
-    sqlx::query("INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, created_by) VALUES (?, 'IN', ?, NOW(), ?, ?)")
-        .bind(stock_id)
-        .bind(quantity)
-        .bind("Manual Stock Addition")
-        .bind(user)
-        .execute(&state.pool)
+    let through = NaiveDate::parse_from_str(through_date.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}'. Use YYYY-MM-DD", through_date))?;
+    if through >= chrono::Local::now().date_naive() {
+        return Err("Only days that have already ended can be closed".to_string());
+    }
+
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+    // Serialise closes so two users can't race past each other's lock date
+    sqlx::query("SELECT close_id FROM period_close ORDER BY close_id DESC LIMIT 1 FOR UPDATE")
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(current) = locked_through(&mut *tx).await? {
+        if through <= current {
+            return Err(format!("The books are already closed through {}", current));
+        }
+    }
+
+    sqlx::query("INSERT INTO period_close (closed_through, closed_by) VALUES (?, ?)")
+        .bind(through)
+        .bind(&user)
+        .execute(&mut *tx)
         .await
         .map_err(|e| e.to_string())?;
 
+    tx.commit().await.map_err(|e| e.to_string())?;
     Ok(())
 }
+
+#[tauri::command]
+pub async fn get_period_closes(state: tauri::State<'_, AppState>) -> Result<Vec<PeriodClose>, String> {
+    sqlx::query_as::<_, PeriodClose>("SELECT * FROM period_close ORDER BY closed_through DESC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct GoodsReceiptDocument {
+    pub receipt: GoodsReceipt,
+    pub lines: Vec<GoodsReceiptLine>,
+}
+
+#[tauri::command]
+pub async fn get_goods_receipt(
+    state: tauri::State<'_, AppState>,
+    grn_no: String,
+) -> Result<GoodsReceiptDocument, String> {
+    let receipt = sqlx::query_as::<sqlx::MySql, GoodsReceipt>("SELECT * FROM goods_receipt WHERE grn_no = ?")
+        .bind(grn_no.trim())
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Goods receipt {} not found", grn_no))?;
+
+    let lines = sqlx::query_as::<sqlx::MySql, GoodsReceiptLine>(
+        "SELECT l.ledger_id, l.stock_id, l.quantity_change, l.transaction_date,
+                m.project, m.part_name, m.description, m.uom, m.location
+         FROM stock_ledger l
+         JOIN stock_master m ON l.stock_id = m.stock_id
+         WHERE l.grn_id = ? AND l.transaction_type = 'IN'
+         ORDER BY l.ledger_id"
+    )
+    .bind(receipt.grn_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(GoodsReceiptDocument { receipt, lines })
+}
+
+async fn load_company_header(pool: &MySqlPool) -> Result<CompanyHeader, String> {
+    let rows = sqlx::query("SELECT setting_key, setting_value FROM app_setting WHERE setting_key IN ('company_name', 'company_address', 'company_logo_path')")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut header = CompanyHeader::default();
+    for row in rows {
+        let key: String = row.get(0);
+        let value: Option<String> = row.get(1);
+        let value = match value.map(|v| v.trim().to_string()) {
+            Some(v) if !v.is_empty() => v,
+            _ => continue,
+        };
+        match key.as_str() {
+            "company_name" => header.name = value,
+            "company_address" => header.address = Some(value),
+            "company_logo_path" => {
+                let logo = tokio::fs::read(&value)
+                    .await
+                    .map_err(|e| format!("Failed to read company logo {}: {}", value, e))?;
+                header.logo = Some(logo);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(header)
+}
+
+fn format_quantity(quantity: Decimal) -> String {
+    quantity.normalize().to_string()
+}
+
+fn format_doc_date(date: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    date.map(|d| d.format("%d-%m-%Y %H:%M").to_string()).unwrap_or_default()
+}
+
+fn issue_slip_printable(document: &IssueSlipDocument) -> PrintableDocument {
+    let slip = &document.slip;
+    PrintableDocument {
+        title: format!("MATERIAL ISSUE SLIP - {}", slip.slip_no),
+        fields: vec![
+            ("Slip No".to_string(), slip.slip_no.clone()),
+            ("Date".to_string(), format_doc_date(slip.created_at)),
+            ("Issued To".to_string(), slip.issued_to.clone()),
+            ("Project".to_string(), slip.project.clone()),
+            ("Work Order".to_string(), slip.work_order.clone().unwrap_or_default()),
+            ("Reference".to_string(), slip.reference.clone().unwrap_or_default()),
+            ("Issued By".to_string(), slip.created_by.clone().unwrap_or_default()),
+            ("Remarks".to_string(), slip.remarks.clone().unwrap_or_default()),
+        ],
+        columns: vec![
+            Column::right("#", 25.0),
+            Column::left("Part", 110.0),
+            Column::left("Description", 175.0),
+            Column::left("Location", 80.0),
+            Column::left("UOM", 50.0),
+            Column::right("Qty", 75.0),
+        ],
+        rows: document
+            .lines
+            .iter()
+            .map(|l| {
+                vec![
+                    l.line_no.to_string(),
+                    l.part_name.clone().unwrap_or_default(),
+                    l.description.clone().unwrap_or_default(),
+                    l.location.clone().unwrap_or_default(),
+                    l.uom.clone().unwrap_or_default(),
+                    format_quantity(l.quantity),
+                ]
+            })
+            .collect(),
+        signatures: vec!["Issued By".to_string(), "Received By".to_string(), "Authorised By".to_string()],
+    }
+}
+
+fn goods_receipt_printable(document: &GoodsReceiptDocument) -> PrintableDocument {
+    let receipt = &document.receipt;
+    PrintableDocument {
+        title: format!("GOODS RECEIVED NOTE - {}", receipt.grn_no),
+        fields: vec![
+            ("GRN No".to_string(), receipt.grn_no.clone()),
+            ("Date".to_string(), format_doc_date(receipt.receipt_date.or(receipt.created_at))),
+            ("Supplier".to_string(), receipt.supplier_name.clone()),
+            ("Invoice".to_string(), receipt.invoice.clone()),
+            ("PO No".to_string(), receipt.po_no.clone()),
+            ("Received By".to_string(), receipt.created_by.clone().unwrap_or_default()),
+        ],
+        columns: vec![
+            Column::right("#", 25.0),
+            Column::left("Project", 75.0),
+            Column::left("Part", 100.0),
+            Column::left("Description", 140.0),
+            Column::left("Location", 60.0),
+            Column::left("UOM", 40.0),
+            Column::right("Qty", 75.0),
+        ],
+        rows: document
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(idx, l)| {
+                vec![
+                    (idx + 1).to_string(),
+                    l.project.clone(),
+                    l.part_name.clone(),
+                    l.description.clone(),
+                    l.location.clone(),
+                    l.uom.clone(),
+                    format_quantity(l.quantity_change),
+                ]
+            })
+            .collect(),
+        signatures: vec!["Received By".to_string(), "Checked By".to_string(), "Store In-charge".to_string()],
+    }
+}
+
+/// Renders a stored document to PDF. `doc_type` is `ISSUE_SLIP` or `GRN` and
+/// `doc_id` the slip/GRN number.
+#[tauri::command]
+pub async fn render_document_pdf(
+    state: tauri::State<'_, AppState>,
+    doc_type: String,
+    doc_id: String,
+) -> Result<Vec<u8>, String> {
+    let printable = match doc_type.trim().to_uppercase().as_str() {
+        "ISSUE_SLIP" => {
+            let slip = get_issue_slip(state.clone(), doc_id).await?;
+            issue_slip_printable(&slip)
+        }
+        "GRN" => {
+            let receipt = get_goods_receipt(state.clone(), doc_id).await?;
+            goods_receipt_printable(&receipt)
+        }
+        other => return Err(format!("Unsupported document type: {}", other)),
+    };
+
+    let company = load_company_header(&state.pool).await?;
+    pdf::render(&printable, &company)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::mysql::MySqlPoolOptions;
+
+    /// Scratch database from `TEST_DATABASE_URL`, with `schema.sql` already applied.
+    /// Tests write to it, so never point it at a live database; without it they
+    /// are skipped.
+    async fn test_pool() -> Option<MySqlPool> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set; skipping");
+            return None;
+        };
+        let pool = MySqlPoolOptions::new().max_connections(2).connect(&url).await.unwrap();
+        crate::migrations::run(&pool).await.unwrap();
+        Some(pool)
+    }
+
+    /// An item of `project` with `quantity` received, returning its stock_id.
+    async fn add_item(pool: &MySqlPool, project: &str, part_name: &str, quantity: i64) -> String {
+        let stock_id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO stock_master (stock_id, project, supplier_name, invoice, po_no, part_name, description, uom, location)
+             VALUES (?, ?, 'Acme', 'INV-1', 'PO-1', ?, '', 'Nos', 'Main Store')"
+        )
+        .bind(&stock_id)
+        .bind(project)
+        .bind(part_name)
+        .execute(pool)
+        .await
+        .unwrap();
+        if quantity > 0 {
+            sqlx::query(
+                "INSERT INTO stock_ledger (stock_id, transaction_type, quantity_change, transaction_date, reference, created_by)
+                 VALUES (?, 'IN', ?, NOW(), 'Test receipt', 'test')"
+            )
+            .bind(&stock_id)
+            .bind(Decimal::from(quantity))
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        stock_id
+    }
+
+    async fn status_of(pool: &MySqlPool, stock_id: &str) -> String {
+        sqlx::query_scalar("SELECT status FROM stock_master WHERE stock_id = ?")
+            .bind(stock_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merging_stock_onto_an_archived_item_reactivates_it() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let project = format!("Merge test {}", Uuid::new_v4());
+        let target = add_item(&pool, &project, "Hex Bolt M6", 0).await;
+        let source = add_item(&pool, &project, "Hex Bolt M-6", 5).await;
+        sqlx::query("UPDATE stock_master SET status = 'ARCHIVED' WHERE stock_id = ?")
+            .bind(&target)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = merge(&pool, vec![source], target.clone(), "test".to_string()).await.unwrap();
+        assert_eq!(report.quantity_moved, Decimal::from(5));
+        assert_eq!(status_of(&pool, &target).await, "ACTIVE");
+    }
+
+    #[tokio::test]
+    async fn merging_no_stock_leaves_an_archived_item_archived() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let project = format!("Merge test {}", Uuid::new_v4());
+        let target = add_item(&pool, &project, "Flat Washer M6", 0).await;
+        let source = add_item(&pool, &project, "Flat Washer M-6", 0).await;
+        sqlx::query("UPDATE stock_master SET status = 'ARCHIVED' WHERE stock_id = ?")
+            .bind(&target)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = merge(&pool, vec![source], target.clone(), "test".to_string()).await.unwrap();
+        assert_eq!(report.quantity_moved, Decimal::ZERO);
+        assert_eq!(status_of(&pool, &target).await, "ARCHIVED");
+    }
+}
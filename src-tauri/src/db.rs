@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{mysql::MySqlPool, FromRow};
 use rust_decimal::Decimal;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct StockMaster {
@@ -30,6 +31,7 @@ pub struct StockLedger {
     pub created_by: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub is_already_reversed: Option<bool>,
+    pub unit_cost: Option<Decimal>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -37,9 +39,19 @@ pub struct StockSummary {
     #[sqlx(flatten)]
     #[serde(flatten)]
     pub master: StockMaster,
+    pub on_hand_quantity: Decimal,
+    pub reserved_quantity: Decimal,
     pub available_quantity: Decimal,
     pub min_quantity: Decimal,
+    pub abc_class: Option<String>,
+    pub xyz_class: Option<String>,
+    pub count_interval_days: Option<i32>,
     pub last_movement: Option<DateTime<Utc>>,
+    /// `ACTIVE` or `ARCHIVED`
+    pub status: String,
+    /// Custom attribute values by attribute name
+    #[sqlx(skip)]
+    pub attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,6 +60,8 @@ pub struct Stats {
     pub total_received: Decimal,
     pub total_issued: Decimal,
     pub low_stock_count: i64,
+    pub slow_moving_count: i64,
+    pub dead_stock_count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -62,8 +76,329 @@ pub struct HistoryEntry {
     pub created_by: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub is_already_reversed: Option<bool>,
+    pub unit_cost: Option<Decimal>,
     pub part_name: Option<String>,
     pub description: Option<String>,
 }
 
 
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct IssueSlip {
+    pub slip_id: i32,
+    pub slip_no: String,
+    pub issued_to: String,
+    pub project: String,
+    pub work_order: Option<String>,
+    pub reference: Option<String>,
+    pub remarks: Option<String>,
+    pub requisition_id: Option<i32>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct IssueSlipLine {
+    pub line_id: i32,
+    pub slip_id: i32,
+    pub line_no: i32,
+    pub stock_id: String,
+    pub quantity: Decimal,
+    pub ledger_id: i32,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct GoodsReceipt {
+    pub grn_id: i32,
+    pub grn_no: String,
+    pub supplier_name: String,
+    pub invoice: String,
+    pub po_no: String,
+    pub receipt_date: Option<DateTime<Utc>>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct GoodsReceiptLine {
+    pub ledger_id: i32,
+    pub stock_id: String,
+    pub quantity_change: Decimal,
+    pub transaction_date: DateTime<Utc>,
+    pub project: String,
+    pub part_name: String,
+    pub description: String,
+    pub uom: String,
+    pub location: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Requisition {
+    pub requisition_id: i32,
+    pub requisition_no: String,
+    pub project: String,
+    pub requested_by: String,
+    pub required_by: Option<NaiveDate>,
+    pub remarks: Option<String>,
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct RequisitionLine {
+    pub line_id: i32,
+    pub requisition_id: i32,
+    pub line_no: i32,
+    pub stock_id: String,
+    pub requested_qty: Decimal,
+    pub approved_qty: Option<Decimal>,
+    pub issued_qty: Decimal,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct RequisitionHistory {
+    pub history_id: i32,
+    pub requisition_id: i32,
+    pub action: String,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub comment: Option<String>,
+    pub performed_by: Option<String>,
+    pub performed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OutstandingRequisition {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub requisition: Requisition,
+    pub line_count: i64,
+    pub requested_total: Decimal,
+    pub approved_total: Decimal,
+    pub issued_total: Decimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct StockReservation {
+    pub reservation_id: i32,
+    pub stock_id: String,
+    pub project: String,
+    pub quantity: Decimal,
+    pub consumed_qty: Decimal,
+    pub status: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub reference: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PurchaseOrderLine {
+    pub po_line_id: i32,
+    pub po_no: String,
+    pub supplier_name: String,
+    pub stock_id: String,
+    pub ordered_qty: Decimal,
+    pub received_qty: Decimal,
+    pub expected_date: Option<NaiveDate>,
+    pub status: String,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub part_name: Option<String>,
+    pub description: Option<String>,
+    pub uom: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ReorderSuggestion {
+    pub stock_id: String,
+    pub project: String,
+    pub part_name: String,
+    pub description: String,
+    pub uom: String,
+    pub location: String,
+    pub on_hand_quantity: Decimal,
+    pub reserved_quantity: Decimal,
+    pub on_order_quantity: Decimal,
+    pub min_quantity: Decimal,
+    pub reorder_point: Decimal,
+    pub reorder_quantity: Decimal,
+    #[sqlx(skip)]
+    pub monthly_forecast: Option<Decimal>,
+    #[sqlx(skip)]
+    pub days_of_cover: Option<Decimal>,
+    #[sqlx(skip)]
+    pub suggested_quantity: Decimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct MovementAgingItem {
+    pub stock_id: String,
+    pub project: String,
+    pub part_name: String,
+    pub description: String,
+    pub uom: String,
+    pub location: String,
+    pub on_hand_quantity: Decimal,
+    pub last_issue_date: Option<DateTime<Utc>>,
+    pub first_receipt_date: Option<DateTime<Utc>>,
+    pub unit_cost: Option<Decimal>,
+    pub slow_moving_days: Option<i32>,
+    pub dead_stock_days: Option<i32>,
+    #[sqlx(skip)]
+    pub days_since_movement: i64,
+    #[sqlx(skip)]
+    pub movement_class: String,
+    #[sqlx(skip)]
+    pub stock_value: Option<Decimal>,
+}
+
+/// An item with nothing on hand that has been idle past its dead-stock limit.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ArchiveCandidate {
+    pub stock_id: String,
+    pub project: String,
+    pub part_name: String,
+    pub description: String,
+    pub uom: String,
+    pub location: String,
+    pub last_movement: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub dead_stock_days: Option<i32>,
+    #[sqlx(skip)]
+    pub days_since_movement: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct StockCardEntry {
+    pub ledger_id: i32,
+    pub stock_id: String,
+    pub transaction_type: String,
+    pub quantity_change: Decimal,
+    pub transaction_date: DateTime<Utc>,
+    pub reference: Option<String>,
+    pub optional_reason: Option<String>,
+    pub created_by: Option<String>,
+    pub unit_cost: Option<Decimal>,
+    #[sqlx(skip)]
+    pub running_balance: Decimal,
+    /// Ledger row this entry reverses, for REVERSAL rows
+    pub reverses_ledger_id: Option<i32>,
+    /// Ledger row that reversed this entry, even if it falls outside the period
+    #[sqlx(skip)]
+    pub reversed_by_ledger_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PeriodClose {
+    pub close_id: i32,
+    pub closed_through: NaiveDate,
+    pub closed_by: Option<String>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct DuplicateOverride {
+    pub override_id: i32,
+    pub ledger_id: i32,
+    pub duplicate_of_ledger_id: i32,
+    pub supplier_name: String,
+    pub invoice: String,
+    pub reason: Option<String>,
+    pub overridden_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub part_name: Option<String>,
+    pub quantity_change: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ItemHistory {
+    pub history_id: i32,
+    pub stock_id: String,
+    pub field_name: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ItemMerge {
+    pub merge_id: i32,
+    pub target_stock_id: String,
+    pub report: String,
+    pub merged_by: Option<String>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub undone_by: Option<String>,
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ImportProfileRecord {
+    pub profile_id: i32,
+    pub name: String,
+    pub header_fingerprint: Option<String>,
+    pub definition: String,
+    pub created_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct AttributeDefinitionRecord {
+    pub attribute_id: i32,
+    pub name: String,
+    pub data_type: String,
+    pub required: bool,
+    /// JSON array, for LIST attributes
+    pub allowed_values: Option<String>,
+    pub sort_order: i32,
+    pub created_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Attachment {
+    pub attachment_id: i32,
+    /// `ITEM`, `GRN` or `ISSUE_SLIP`
+    pub entity_type: String,
+    /// Stock id, GRN number or issue slip number
+    pub entity_id: String,
+    pub file_name: String,
+    pub file_hash: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub description: Option<String>,
+    pub uploaded_by: Option<String>,
+    pub uploaded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ImportBatch {
+    pub batch_id: i32,
+    pub file_name: Option<String>,
+    pub file_hash: Option<String>,
+    pub row_count: i32,
+    pub new_item_count: i32,
+    pub merged_count: i32,
+    pub duplicate_count: i32,
+    pub total_quantity: Decimal,
+    pub status: String,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub reverted_by: Option<String>,
+    pub reverted_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,263 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// How a column's values should be written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Text,
+    /// Whole numbers such as ids and day counts
+    Integer,
+    Quantity,
+    Money,
+    /// Numbers of any scale, shown as stored
+    Number,
+    Date,
+    /// Calendar dates without a time of day
+    Day,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportColumn {
+    pub heading: Cow<'static, str>,
+    pub kind: ColumnKind,
+}
+
+impl ExportColumn {
+    pub fn new(heading: impl Into<Cow<'static, str>>, kind: ColumnKind) -> Self {
+        Self { heading: heading.into(), kind }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(Decimal),
+    Date(DateTime<Utc>),
+    Day(NaiveDate),
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<Decimal> for Cell {
+    fn from(value: Decimal) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Cell::Number(Decimal::from(value))
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Number(Decimal::from(value))
+    }
+}
+
+impl From<DateTime<Utc>> for Cell {
+    fn from(value: DateTime<Utc>) -> Self {
+        Cell::Date(value)
+    }
+}
+
+impl From<NaiveDate> for Cell {
+    fn from(value: NaiveDate) -> Self {
+        Cell::Day(value)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Cell::Empty)
+    }
+}
+
+impl Cell {
+    fn to_text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(s) => s.clone(),
+            Cell::Number(n) => n.normalize().to_string(),
+            Cell::Date(d) => d.format("%Y-%m-%d %H:%M:%S").to_string(),
+            Cell::Day(d) => d.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Writes a table to `path` in one go. See `TableWriter` for row-by-row output.
+pub fn write_table<I>(path: &Path, columns: &[ExportColumn], rows: I) -> Result<usize, String>
+where
+    I: IntoIterator<Item = Vec<Cell>>,
+{
+    let mut writer = TableWriter::create(path, "Report", columns)?;
+    for row in rows {
+        writer.write_row(row)?;
+    }
+    writer.finish()
+}
+
+enum Sink {
+    Csv(Box<csv::Writer<std::fs::File>>),
+    Xlsx {
+        workbook: Box<Workbook>,
+        formats: Vec<Format>,
+    },
+}
+
+/// Row-by-row table output to CSV or XLSX, picked from the file extension. XLSX
+/// sheets are written in constant-memory mode, so rows can be streamed straight
+/// from a query without holding the whole export in memory.
+pub struct TableWriter {
+    path: PathBuf,
+    sink: Sink,
+    columns: usize,
+    written: usize,
+}
+
+impl TableWriter {
+    pub fn create(path: &Path, sheet_name: &str, columns: &[ExportColumn]) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+
+        let sink = match extension.as_str() {
+            "csv" => {
+                let mut writer = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+                writer
+                    .write_record(columns.iter().map(|c| c.heading.as_ref()))
+                    .map_err(|e| e.to_string())?;
+                Sink::Csv(Box::new(writer))
+            }
+            "xlsx" => {
+                let mut workbook = Workbook::new();
+                let header = Format::new()
+                    .set_bold()
+                    .set_background_color(Color::RGB(0xE7E6E6))
+                    .set_border_bottom(FormatBorder::Thin);
+                let sheet = workbook.add_worksheet_with_constant_memory();
+                sheet.set_name(sheet_name).map_err(|e| e.to_string())?;
+                for (col, column) in columns.iter().enumerate() {
+                    let col = col as u16;
+                    sheet
+                        .set_column_width(col, column.kind.width())
+                        .map_err(|e| e.to_string())?;
+                    sheet
+                        .write_string_with_format(0, col, column.heading.as_ref(), &header)
+                        .map_err(|e| e.to_string())?;
+                }
+                sheet.set_freeze_panes(1, 0).map_err(|e| e.to_string())?;
+
+                let formats = columns.iter().map(|c| c.kind.format()).collect();
+                Sink::Xlsx {
+                    workbook: Box::new(workbook),
+                    formats,
+                }
+            }
+            other => return Err(format!("Unsupported export format '.{}'. Use .xlsx or .csv", other)),
+        };
+
+        // rust_xlsxwriter only writes the file on save, so fail early on a bad path
+        if let Sink::Xlsx { .. } = sink {
+            std::fs::File::create(path).map_err(|e| e.to_string())?;
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink,
+            columns: columns.len(),
+            written: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, cells: Vec<Cell>) -> Result<(), String> {
+        debug_assert_eq!(cells.len(), self.columns);
+        match &mut self.sink {
+            Sink::Csv(writer) => writer
+                .write_record(cells.iter().map(Cell::to_text))
+                .map_err(|e| e.to_string())?,
+            Sink::Xlsx { workbook, formats } => {
+                if self.written + 1 >= XLSX_MAX_ROWS {
+                    return Err(format!(
+                        "Export has more than {} rows, which is the XLSX limit. Use CSV or narrow the filters",
+                        XLSX_MAX_ROWS - 1
+                    ));
+                }
+                let row = (self.written + 1) as u32;
+                let sheet = workbook.worksheet_from_index(0).map_err(|e| e.to_string())?;
+                for (col, cell) in cells.into_iter().enumerate() {
+                    let format = &formats[col];
+                    let col = col as u16;
+                    match cell {
+                        Cell::Empty => continue,
+                        Cell::Text(s) => sheet.write_string_with_format(row, col, s, format),
+                        Cell::Number(n) => {
+                            sheet.write_number_with_format(row, col, n.to_f64().unwrap_or_default(), format)
+                        }
+                        Cell::Date(d) => sheet.write_datetime_with_format(row, col, d.naive_utc(), format),
+                        Cell::Day(d) => sheet.write_date_with_format(row, col, d, format),
+                    }
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flushes and closes the file, returning the number of data rows written.
+    pub fn finish(self) -> Result<usize, String> {
+        match self.sink {
+            Sink::Csv(mut writer) => writer.flush().map_err(|e| e.to_string())?,
+            Sink::Xlsx { mut workbook, .. } => workbook.save(&self.path).map_err(|e| e.to_string())?,
+        }
+        Ok(self.written)
+    }
+}
+
+/// Rows per sheet in the XLSX format, including the header.
+const XLSX_MAX_ROWS: usize = 1_048_576;
+
+impl ColumnKind {
+    fn width(self) -> f64 {
+        match self {
+            ColumnKind::Text => 24.0,
+            ColumnKind::Integer => 10.0,
+            ColumnKind::Quantity => 14.0,
+            ColumnKind::Money => 16.0,
+            ColumnKind::Number => 14.0,
+            ColumnKind::Date => 18.0,
+            ColumnKind::Day => 12.0,
+        }
+    }
+
+    fn format(self) -> Format {
+        match self {
+            ColumnKind::Text => Format::new(),
+            ColumnKind::Integer => Format::new().set_num_format("0"),
+            ColumnKind::Quantity => Format::new().set_num_format("#,##0.00##"),
+            ColumnKind::Money => Format::new().set_num_format("#,##0.00"),
+            ColumnKind::Number => Format::new().set_num_format("General"),
+            ColumnKind::Date => Format::new().set_num_format("yyyy-mm-dd hh:mm"),
+            ColumnKind::Day => Format::new().set_num_format("yyyy-mm-dd"),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// The forecasting method that won the backtest for a demand series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastModel {
+    pub method: &'static str,
+    /// Moving-average window (months) or smoothing factor alpha
+    pub parameter: f64,
+    /// Mean absolute one-step-ahead error over the backtest, if there was enough history
+    pub mae: Option<f64>,
+    /// Forecast demand per period going forward (both methods produce a flat forecast)
+    pub next: f64,
+}
+
+const MA_WINDOWS: [usize; 2] = [3, 6];
+const SES_ALPHAS: [f64; 5] = [0.1, 0.3, 0.5, 0.7, 0.9];
+/// Periods held back before the first backtest forecast is scored
+const MIN_TRAINING: usize = 3;
+
+/// Mean of the last `window` periods (or all of them if the series is shorter).
+pub fn moving_average(series: &[f64], window: usize) -> Option<f64> {
+    if series.is_empty() || window == 0 {
+        return None;
+    }
+    let tail = &series[series.len().saturating_sub(window)..];
+    Some(tail.iter().sum::<f64>() / tail.len() as f64)
+}
+
+/// Simple exponential smoothing, seeded with the first observation.
+pub fn exponential_smoothing(series: &[f64], alpha: f64) -> Option<f64> {
+    let (first, rest) = series.split_first()?;
+    Some(rest.iter().fold(*first, |level, x| alpha * x + (1.0 - alpha) * level))
+}
+
+/// Mean absolute error of one-step-ahead forecasts made from every prefix of the
+/// series that has at least `MIN_TRAINING` periods.
+fn backtest(series: &[f64], forecast: impl Fn(&[f64]) -> Option<f64>) -> Option<f64> {
+    let errors: Vec<f64> = (MIN_TRAINING..series.len())
+        .filter_map(|t| forecast(&series[..t]).map(|f| (series[t] - f).abs()))
+        .collect();
+    if errors.is_empty() {
+        None
+    } else {
+        Some(errors.iter().sum::<f64>() / errors.len() as f64)
+    }
+}
+
+/// Backtests every candidate moving average and smoothing factor on `series` and
+/// returns the one with the lowest error. With too little history to backtest,
+/// falls back to the average of everything seen so far.
+pub fn best_forecast(series: &[f64]) -> ForecastModel {
+    let mut candidates: Vec<ForecastModel> = Vec::new();
+    for window in MA_WINDOWS {
+        candidates.push(ForecastModel {
+            method: "MOVING_AVERAGE",
+            parameter: window as f64,
+            mae: backtest(series, |s| moving_average(s, window)),
+            next: moving_average(series, window).unwrap_or(0.0),
+        });
+    }
+    for alpha in SES_ALPHAS {
+        candidates.push(ForecastModel {
+            method: "EXPONENTIAL_SMOOTHING",
+            parameter: alpha,
+            mae: backtest(series, |s| exponential_smoothing(s, alpha)),
+            next: exponential_smoothing(series, alpha).unwrap_or(0.0),
+        });
+    }
+
+    // Earlier candidates win ties, so simpler models are preferred
+    let best = candidates
+        .iter()
+        .filter(|c| c.mae.is_some())
+        .fold(None::<&ForecastModel>, |best, c| match best {
+            Some(b) if b.mae <= c.mae => Some(b),
+            _ => Some(c),
+        });
+
+    match best {
+        Some(model) => model.clone(),
+        None => ForecastModel {
+            method: "MOVING_AVERAGE",
+            parameter: series.len() as f64,
+            mae: None,
+            next: moving_average(series, series.len()).unwrap_or(0.0),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn moving_average_uses_the_last_window() {
+        assert_eq!(moving_average(&[1.0, 2.0, 3.0, 4.0], 2), Some(3.5));
+        // Shorter series average everything
+        assert_eq!(moving_average(&[2.0, 4.0], 6), Some(3.0));
+        assert_eq!(moving_average(&[], 3), None);
+        assert_eq!(moving_average(&[1.0], 0), None);
+    }
+
+    #[test]
+    fn exponential_smoothing_is_seeded_with_first_value() {
+        assert_eq!(exponential_smoothing(&[10.0], 0.3), Some(10.0));
+        // 0.5 * 20 + 0.5 * 10 = 15, then 0.5 * 0 + 0.5 * 15
+        assert!(close(exponential_smoothing(&[10.0, 20.0, 0.0], 0.5).unwrap(), 7.5));
+        assert_eq!(exponential_smoothing(&[], 0.5), None);
+    }
+
+    #[test]
+    fn backtest_needs_more_than_the_training_periods() {
+        assert_eq!(backtest(&[1.0, 2.0, 3.0], |s| moving_average(s, 3)), None);
+        // One forecast (2.0) scored against the fourth period
+        assert_eq!(backtest(&[1.0, 2.0, 3.0, 5.0], |s| moving_average(s, 3)), Some(3.0));
+    }
+
+    #[test]
+    fn short_history_falls_back_to_overall_average() {
+        let model = best_forecast(&[3.0, 6.0]);
+        assert_eq!(model.method, "MOVING_AVERAGE");
+        assert_eq!(model.parameter, 2.0);
+        assert_eq!(model.mae, None);
+        assert_eq!(model.next, 4.5);
+    }
+
+    #[test]
+    fn no_history_forecasts_zero() {
+        let model = best_forecast(&[]);
+        assert_eq!(model.next, 0.0);
+        assert_eq!(model.mae, None);
+    }
+
+    #[test]
+    fn steady_demand_prefers_the_simplest_model() {
+        // Every candidate is exact, so the first (3-month average) wins the tie
+        let model = best_forecast(&[5.0; 12]);
+        assert_eq!((model.method, model.parameter), ("MOVING_AVERAGE", 3.0));
+        assert_eq!(model.mae, Some(0.0));
+        assert_eq!(model.next, 5.0);
+    }
+
+    #[test]
+    fn trending_demand_prefers_fast_smoothing() {
+        let series: Vec<f64> = (1..=12).map(f64::from).collect();
+        let model = best_forecast(&series);
+        assert_eq!((model.method, model.parameter), ("EXPONENTIAL_SMOOTHING", 0.9));
+        assert!(model.next > 11.0 && model.next < 12.0);
+    }
+}
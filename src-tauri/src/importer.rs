@@ -0,0 +1,1274 @@
+use crate::attributes::{self, AttributeDefinition};
+use crate::commands::{ImportRow, ThresholdField, ThresholdImportRow, ThresholdInput};
+use calamine::{open_workbook_auto, Data, Reader};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A value read from a spreadsheet cell, before validation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawCell {
+    Empty,
+    Text(String),
+    Number(f64),
+    DateTime(NaiveDateTime),
+}
+
+impl RawCell {
+    /// The cell as the user would read it, used for text fields and error messages.
+    pub fn display(&self) -> String {
+        match self {
+            RawCell::Empty => String::new(),
+            RawCell::Text(s) => s.trim().to_string(),
+            RawCell::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+            RawCell::Number(n) => n.to_string(),
+            RawCell::DateTime(d) => d.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+
+    fn is_blank(&self) -> bool {
+        self.display().is_empty()
+    }
+}
+
+/// The first worksheet (or the CSV file) as a header row plus data rows. Each data
+/// row keeps its 1-based row number as shown in the spreadsheet.
+#[derive(Debug, Clone)]
+pub struct RawSheet {
+    pub headers: Vec<String>,
+    pub rows: Vec<(u32, Vec<RawCell>)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CellError {
+    pub sheet_row: u32,
+    pub column: String,
+    pub value: String,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ParsedRow {
+    pub sheet_row: u32,
+    pub row: ImportRow,
+}
+
+#[derive(Debug)]
+pub struct ParsedSheet {
+    pub rows: Vec<ParsedRow>,
+    pub errors: Vec<CellError>,
+    /// Non-blank data rows in the file, valid or not
+    pub total_rows: usize,
+    /// Rows left out by a profile's skip rules
+    pub skipped_rows: usize,
+}
+
+/// Reads a spreadsheet or CSV file, picking the reader from the extension.
+pub fn read_sheet(path: &Path) -> Result<RawSheet, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "csv" => read_csv(path),
+        "xlsx" | "xlsm" | "xls" | "xlsb" | "ods" => read_workbook(path),
+        other => Err(format!("Unsupported file type '.{}'. Use .xlsx, .xls, .ods or .csv", other)),
+    }
+}
+
+fn read_workbook(path: &Path) -> Result<RawSheet, String> {
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("Could not open file: {}", e))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("The workbook has no worksheets")?
+        .map_err(|e| format!("Could not read the first worksheet: {}", e))?;
+
+    let first_row = range.start().map(|(row, _)| row).unwrap_or(0);
+    let first_col = range.start().map(|(_, col)| col).unwrap_or(0) as usize;
+    let mut rows = range.rows().enumerate().map(|(i, cells)| {
+        // Pad so that cell positions line up with columns even when the used range
+        // starts to the right of column A
+        let mut values = vec![RawCell::Empty; first_col];
+        values.extend(cells.iter().map(|cell| match cell {
+            Data::Empty => RawCell::Empty,
+            Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => RawCell::Text(s.clone()),
+            Data::Int(n) => RawCell::Number(*n as f64),
+            Data::Float(n) => RawCell::Number(*n),
+            Data::Bool(b) => RawCell::Text(b.to_string()),
+            Data::DateTime(d) => match d.as_datetime() {
+                Some(dt) => RawCell::DateTime(dt),
+                None => RawCell::Number(d.as_f64()),
+            },
+            Data::Error(e) => RawCell::Text(format!("#{:?}", e)),
+        }));
+        (first_row + i as u32 + 1, values)
+    });
+
+    // The header is the first row with anything in it
+    let headers = loop {
+        match rows.next() {
+            Some((_, cells)) if cells.iter().all(RawCell::is_blank) => continue,
+            Some((_, cells)) => break cells.iter().map(RawCell::display).collect(),
+            None => return Err("The worksheet is empty".to_string()),
+        }
+    };
+    Ok(RawSheet {
+        headers,
+        rows: rows.collect(),
+    })
+}
+
+fn read_csv(path: &Path) -> Result<RawSheet, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Could not open file: {}", e))?;
+
+    let mut headers: Option<Vec<String>> = None;
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Could not read CSV: {}", e))?;
+        let line = record.position().map(|p| p.line() as u32).unwrap_or(0);
+        let cells: Vec<RawCell> = record
+            .iter()
+            .map(|v| if v.trim().is_empty() { RawCell::Empty } else { RawCell::Text(v.to_string()) })
+            .collect();
+        match headers {
+            None if cells.iter().all(RawCell::is_blank) => continue,
+            None => headers = Some(cells.iter().map(RawCell::display).collect()),
+            Some(_) => rows.push((line, cells)),
+        }
+    }
+    Ok(RawSheet {
+        headers: headers.ok_or("The file is empty")?,
+        rows,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Project,
+    SupplierName,
+    Invoice,
+    PoNo,
+    PartName,
+    Description,
+    Quantity,
+    Uom,
+    Location,
+    Remarks,
+    RecDate,
+    UnitCost,
+}
+
+impl Field {
+    pub const ALL: [Field; 12] = [
+        Field::Project,
+        Field::SupplierName,
+        Field::Invoice,
+        Field::PoNo,
+        Field::PartName,
+        Field::Description,
+        Field::Quantity,
+        Field::Uom,
+        Field::Location,
+        Field::Remarks,
+        Field::RecDate,
+        Field::UnitCost,
+    ];
+
+    /// Column heading in the upload template.
+    pub fn heading(self) -> &'static str {
+        match self {
+            Field::Project => "Project",
+            Field::SupplierName => "Supplier Name",
+            Field::Invoice => "Invoice",
+            Field::PoNo => "PO No",
+            Field::PartName => "Part Name",
+            Field::Description => "Description",
+            Field::Quantity => "Qty",
+            Field::Uom => "UOM",
+            Field::Location => "Location",
+            Field::Remarks => "Remarks",
+            Field::RecDate => "Rec Date",
+            Field::UnitCost => "Unit Cost",
+        }
+    }
+
+    /// Other headings accepted for the column, compared after `header_key`.
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Field::Project => &["project", "projectname"],
+            Field::SupplierName => &["suppliername", "supplier", "vendor", "vendorname"],
+            Field::Invoice => &["invoice", "invoiceno", "invoicenumber"],
+            Field::PoNo => &["pono", "po", "ponumber", "purchaseorder"],
+            Field::PartName => &["partname", "part", "itemname", "item"],
+            Field::Description => &["description", "desc"],
+            Field::Quantity => &["qty", "quantity"],
+            Field::Uom => &["uom", "unit", "units"],
+            Field::Location => &["location", "bin", "store"],
+            Field::Remarks => &["remarks", "remark", "notes"],
+            Field::RecDate => &["recdate", "receiptdate", "receiveddate", "date"],
+            Field::UnitCost => &["unitcost", "rate", "unitprice", "price"],
+        }
+    }
+
+    /// `ImportRow` field name, as used in mapping profiles.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Project => "project",
+            Field::SupplierName => "supplier_name",
+            Field::Invoice => "invoice",
+            Field::PoNo => "po_no",
+            Field::PartName => "part_name",
+            Field::Description => "description",
+            Field::Quantity => "quantity",
+            Field::Uom => "uom",
+            Field::Location => "location",
+            Field::Remarks => "remarks",
+            Field::RecDate => "rec_date",
+            Field::UnitCost => "unit_cost",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Field> {
+        Field::ALL.into_iter().find(|f| f.name() == name.trim())
+    }
+
+    pub fn required(self) -> bool {
+        matches!(self, Field::Project | Field::PartName | Field::Quantity | Field::Uom | Field::Location)
+    }
+
+    /// Column width in `stock_master`, where there is a limit.
+    fn max_length(self) -> Option<usize> {
+        match self {
+            Field::Uom => Some(50),
+            Field::Project | Field::SupplierName | Field::Invoice | Field::PoNo | Field::PartName | Field::Location => {
+                Some(100)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Lowercase alphanumerics only, so "PO No." and "po_no" compare equal.
+pub fn header_key(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Identifies a spreadsheet layout: SHA-256 over its normalized, sorted headers, so
+/// column order and spelling of case or punctuation don't matter.
+pub fn header_fingerprint(headers: &[String]) -> String {
+    use sha2::{Digest, Sha256};
+    let mut keys: Vec<String> = headers.iter().map(|h| header_key(h)).filter(|k| !k.is_empty()).collect();
+    keys.sort();
+    keys.dedup();
+    format!("{:x}", Sha256::digest(keys.join("|").as_bytes()))
+}
+
+/// How the cells of one sheet become `ImportRow`s.
+#[derive(Debug, Default)]
+pub struct SheetMapping {
+    pub columns: Vec<(Field, usize)>,
+    pub defaults: Vec<(Field, String)>,
+    pub conversions: Vec<UnitConversion>,
+    pub skip_rules: Vec<(usize, SkipRule)>,
+    /// Custom attribute columns, by attribute name
+    pub attributes: Vec<(String, usize)>,
+}
+
+impl SheetMapping {
+    fn column(&self, field: Field) -> Option<usize> {
+        self.columns.iter().find(|(f, _)| *f == field).map(|(_, idx)| *idx)
+    }
+
+    fn default_value(&self, field: Field) -> Option<&str> {
+        self.defaults.iter().find(|(f, _)| *f == field).map(|(_, v)| v.as_str())
+    }
+
+    fn check_required(&self) -> Result<(), String> {
+        let missing: Vec<&str> = Field::ALL
+            .into_iter()
+            .filter(|f| f.required() && self.column(*f).is_none() && self.default_value(*f).is_none())
+            .map(Field::heading)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Missing required column(s): {}", missing.join(", ")))
+        }
+    }
+
+    /// Checks the attribute columns a profile named against `definitions`, then
+    /// adds any other unmapped header that is the name of an attribute.
+    pub fn match_attributes(&mut self, headers: &[String], definitions: &[AttributeDefinition]) -> Result<(), String> {
+        for (name, _) in &mut self.attributes {
+            let definition = attributes::find(definitions, name).ok_or_else(|| format!("Unknown attribute '{}'", name))?;
+            *name = definition.name.clone();
+        }
+        for (idx, header) in headers.iter().enumerate() {
+            let mapped = self.columns.iter().any(|(_, i)| *i == idx) || self.attributes.iter().any(|(_, i)| *i == idx);
+            if mapped {
+                continue;
+            }
+            if let Some(definition) = definitions.iter().find(|d| header_key(&d.name) == header_key(header)) {
+                if !self.attributes.iter().any(|(name, _)| *name == definition.name) {
+                    self.attributes.push((definition.name.clone(), idx));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Matches sheet headers against the template columns. Fails if a required column
+/// is missing.
+pub fn template_columns(headers: &[String]) -> Result<SheetMapping, String> {
+    let keys: Vec<String> = headers.iter().map(|h| header_key(h)).collect();
+    let mut mapping = SheetMapping::default();
+    for field in Field::ALL {
+        if let Some(idx) = keys.iter().position(|k| field.aliases().contains(&k.as_str())) {
+            mapping.columns.push((field, idx));
+        }
+    }
+    mapping.check_required()?;
+    Ok(mapping)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Heading in the supplier's sheet
+    pub source: String,
+    /// `ImportRow` field name, e.g. `part_name`, or `attribute:` and the name of a
+    /// custom attribute
+    pub field: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldDefault {
+    pub field: String,
+    pub value: String,
+}
+
+/// Rewrites a supplier's unit into ours, scaling quantity (and unit cost) by `factor`,
+/// e.g. BOX -> Nos with factor 100.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitConversion {
+    pub from_uom: String,
+    pub to_uom: String,
+    pub factor: Decimal,
+}
+
+/// Leaves out rows whose `column` is empty (`EMPTY`) or whose text `EQUALS`,
+/// `CONTAINS` or `STARTS_WITH` the value, ignoring case. Used for subtotal lines,
+/// section headings and the like.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkipRule {
+    pub column: String,
+    pub operator: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+impl SkipRule {
+    fn matches(&self, cell: &RawCell) -> bool {
+        let text = cell.display().to_lowercase();
+        let value = self.value.as_deref().unwrap_or_default().trim().to_lowercase();
+        match self.operator.to_uppercase().as_str() {
+            "EMPTY" => text.is_empty(),
+            "EQUALS" => text == value,
+            "CONTAINS" => !value.is_empty() && text.contains(&value),
+            "STARTS_WITH" => !value.is_empty() && text.starts_with(&value),
+            _ => false,
+        }
+    }
+}
+
+/// A saved layout for one supplier's spreadsheets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MappingProfile {
+    #[serde(default)]
+    pub profile_id: Option<i32>,
+    pub name: String,
+    /// Fingerprint of the headers the profile was built from, see `header_fingerprint`
+    #[serde(default)]
+    pub header_fingerprint: Option<String>,
+    pub columns: Vec<ColumnMapping>,
+    #[serde(default)]
+    pub defaults: Vec<FieldDefault>,
+    #[serde(default)]
+    pub unit_conversions: Vec<UnitConversion>,
+    #[serde(default)]
+    pub skip_rules: Vec<SkipRule>,
+}
+
+impl MappingProfile {
+    /// Checks field names, operators and factors without needing a sheet.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Profile name is required".to_string());
+        }
+        for name in self.columns.iter().map(|c| &c.field).chain(self.defaults.iter().map(|d| &d.field)) {
+            if Field::from_name(name).is_none() && attribute_name(name).is_none() {
+                return Err(format!("Unknown field '{}'", name));
+            }
+        }
+        for conversion in &self.unit_conversions {
+            if conversion.factor <= Decimal::ZERO {
+                return Err(format!("Conversion factor for '{}' must be greater than zero", conversion.from_uom));
+            }
+        }
+        for rule in &self.skip_rules {
+            if !["EMPTY", "EQUALS", "CONTAINS", "STARTS_WITH"].contains(&rule.operator.to_uppercase().as_str()) {
+                return Err(format!("Unknown skip rule operator '{}'", rule.operator));
+            }
+        }
+        Ok(())
+    }
+
+    /// Source headers present in `headers`, out of those the profile maps.
+    pub fn coverage(&self, headers: &[String]) -> usize {
+        let keys: Vec<String> = headers.iter().map(|h| header_key(h)).collect();
+        self.columns
+            .iter()
+            .filter(|c| keys.contains(&header_key(&c.source)))
+            .count()
+    }
+
+    /// Ties the profile to the columns of an actual sheet.
+    pub fn resolve(&self, headers: &[String]) -> Result<SheetMapping, String> {
+        self.validate()?;
+        let keys: Vec<String> = headers.iter().map(|h| header_key(h)).collect();
+        let position = |source: &str| {
+            keys.iter()
+                .position(|k| *k == header_key(source))
+                .ok_or_else(|| format!("Profile '{}' expects a '{}' column", self.name, source))
+        };
+
+        let mut mapping = SheetMapping::default();
+        for column in &self.columns {
+            if let Some(name) = attribute_name(&column.field) {
+                mapping.attributes.push((name.to_string(), position(&column.source)?));
+                continue;
+            }
+            let field = Field::from_name(&column.field).ok_or_else(|| format!("Unknown field '{}'", column.field))?;
+            mapping.columns.push((field, position(&column.source)?));
+        }
+        for default in &self.defaults {
+            let field = Field::from_name(&default.field).ok_or_else(|| format!("Unknown field '{}'", default.field))?;
+            mapping.defaults.push((field, default.value.clone()));
+        }
+        mapping.conversions = self.unit_conversions.clone();
+        for rule in &self.skip_rules {
+            mapping.skip_rules.push((position(&rule.column)?, rule.clone()));
+        }
+        mapping.check_required()?;
+        Ok(mapping)
+    }
+}
+
+/// The attribute a profile field like `attribute:Grade` names.
+fn attribute_name(field: &str) -> Option<&str> {
+    field
+        .get(..attributes::FIELD_PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(attributes::FIELD_PREFIX))
+        .map(|_| field[attributes::FIELD_PREFIX.len()..].trim())
+        .filter(|name| !name.is_empty())
+}
+
+fn parse_decimal(cell: &RawCell) -> Option<Decimal> {
+    match cell {
+        RawCell::Number(n) => Decimal::from_f64(*n).map(|d| d.round_dp(10).normalize()),
+        RawCell::Text(s) => Decimal::from_str(&s.trim().replace(',', "")).ok(),
+        _ => None,
+    }
+}
+
+/// Validates every data row of `sheet`. Rows with any invalid cell are reported and
+/// left out; the rest are returned for preview. Entirely blank rows are skipped.
+/// Attribute cells are checked against `definitions`; blank ones are left out.
+pub fn parse_sheet(sheet: &RawSheet, mapping: &SheetMapping, definitions: &[AttributeDefinition]) -> ParsedSheet {
+    let mut parsed = ParsedSheet {
+        rows: Vec::new(),
+        errors: Vec::new(),
+        total_rows: 0,
+        skipped_rows: 0,
+    };
+
+    for (sheet_row, cells) in &sheet.rows {
+        if cells.iter().all(RawCell::is_blank) {
+            continue;
+        }
+        parsed.total_rows += 1;
+        if mapping
+            .skip_rules
+            .iter()
+            .any(|(idx, rule)| rule.matches(cells.get(*idx).unwrap_or(&RawCell::Empty)))
+        {
+            parsed.skipped_rows += 1;
+            continue;
+        }
+
+        let mut row = ImportRow {
+            project: String::new(),
+            supplier_name: String::new(),
+            invoice: String::new(),
+            po_no: String::new(),
+            part_name: String::new(),
+            description: String::new(),
+            quantity: Decimal::ZERO,
+            uom: String::new(),
+            location: String::new(),
+            remarks: None,
+            rec_date: None,
+            unit_cost: None,
+            attributes: BTreeMap::new(),
+        };
+        let mut row_errors = Vec::new();
+        let mut error = |field: Field, cell: &RawCell, message: &str| {
+            // Name the column as it appears in the user's sheet
+            let column = mapping
+                .column(field)
+                .and_then(|idx| sheet.headers.get(idx))
+                .cloned()
+                .unwrap_or_else(|| field.heading().to_string());
+            row_errors.push(CellError {
+                sheet_row: *sheet_row,
+                column,
+                value: cell.display(),
+                message: message.to_string(),
+            });
+        };
+
+        for field in Field::ALL {
+            let mut cell = mapping
+                .column(field)
+                .and_then(|idx| cells.get(idx))
+                .cloned()
+                .unwrap_or(RawCell::Empty);
+            if cell.is_blank() {
+                if let Some(value) = mapping.default_value(field) {
+                    cell = RawCell::Text(value.to_string());
+                }
+            }
+            let cell = &cell;
+            let text = cell.display();
+            if text.is_empty() {
+                if field.required() {
+                    error(field, cell, "Required value is missing");
+                }
+                continue;
+            }
+            if let Some(max) = field.max_length() {
+                if text.chars().count() > max {
+                    error(field, cell, &format!("Longer than {} characters", max));
+                    continue;
+                }
+            }
+
+            match field {
+                Field::Quantity => match parse_decimal(cell) {
+                    Some(q) if q <= Decimal::ZERO => error(field, cell, "Quantity must be greater than zero"),
+                    Some(q) if q.scale() > 4 => error(field, cell, "Quantity has more than 4 decimal places"),
+                    Some(q) => row.quantity = q,
+                    None => error(field, cell, "Not a number"),
+                },
+                Field::UnitCost => match parse_decimal(cell) {
+                    Some(c) if c.is_sign_negative() => error(field, cell, "Unit cost cannot be negative"),
+                    Some(c) if c.scale() > 4 => error(field, cell, "Unit cost has more than 4 decimal places"),
+                    Some(c) => row.unit_cost = Some(c),
+                    None => error(field, cell, "Not a number"),
+                },
+                Field::Project => row.project = text,
+                Field::SupplierName => row.supplier_name = text,
+                Field::Invoice => row.invoice = text,
+                Field::PoNo => row.po_no = text,
+                Field::PartName => row.part_name = text,
+                Field::Description => row.description = text,
+                Field::Uom => row.uom = text,
+                Field::Location => row.location = text,
+                Field::Remarks => row.remarks = Some(text),
+                Field::RecDate => match parse_receipt_date(&text) {
+                    Ok(date) if date.date() > Local::now().date_naive() => {
+                        error(field, cell, "Receipt date is in the future")
+                    }
+                    Ok(date) => row.rec_date = Some(date.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    Err(message) => error(field, cell, &message),
+                },
+            }
+        }
+
+        for (name, idx) in &mapping.attributes {
+            let Some(definition) = attributes::find(definitions, name) else {
+                continue;
+            };
+            let cell = cells.get(*idx).unwrap_or(&RawCell::Empty);
+            let text = cell.display();
+            if text.is_empty() {
+                continue;
+            }
+            match definition.normalize_value(&text) {
+                Ok(value) => {
+                    row.attributes.insert(definition.name.clone(), value);
+                }
+                Err(message) => row_errors.push(CellError {
+                    sheet_row: *sheet_row,
+                    column: sheet.headers.get(*idx).cloned().unwrap_or_else(|| name.clone()),
+                    value: text,
+                    message,
+                }),
+            }
+        }
+
+        if row_errors.is_empty() {
+            if let Some(conversion) = mapping
+                .conversions
+                .iter()
+                .find(|c| header_key(&c.from_uom) == header_key(&row.uom))
+            {
+                // Quantities are kept exact, so one that needs more than 4 decimal
+                // places once converted is an error rather than rounded away. Costs
+                // round half away from zero, as the database would.
+                match row.quantity.checked_mul(conversion.factor).map(|q| q.normalize()) {
+                    Some(q) if q.scale() <= 4 => row.quantity = q,
+                    _ => row_errors.push(CellError {
+                        sheet_row: *sheet_row,
+                        column: mapping
+                            .column(Field::Quantity)
+                            .and_then(|idx| sheet.headers.get(idx))
+                            .cloned()
+                            .unwrap_or_else(|| Field::Quantity.heading().to_string()),
+                        value: row.quantity.to_string(),
+                        message: format!("Quantity has more than 4 decimal places in {}", conversion.to_uom.trim()),
+                    }),
+                }
+                row.unit_cost = row
+                    .unit_cost
+                    .map(|c| (c / conversion.factor).round_dp_with_strategy(4, RoundingStrategy::MidpointAwayFromZero));
+                row.uom = conversion.to_uom.trim().to_string();
+            }
+        }
+        if row_errors.is_empty() {
+            parsed.rows.push(ParsedRow {
+                sheet_row: *sheet_row,
+                row,
+            });
+        } else {
+            parsed.errors.extend(row_errors);
+        }
+    }
+
+    parsed
+}
+
+#[derive(Debug)]
+pub struct ParsedThresholds {
+    pub rows: Vec<(u32, ThresholdImportRow)>,
+    pub errors: Vec<CellError>,
+}
+
+/// Threshold columns of a threshold update sheet: heading and accepted header keys.
+const MIN_QUANTITY: (&str, &[&str]) = (
+    ThresholdField::MinQuantity.heading(),
+    &["minqty", "minquantity", "minimumquantity", "minstock"],
+);
+const REORDER_POINT: (&str, &[&str]) = (
+    ThresholdField::ReorderPoint.heading(),
+    &["reorderpoint", "rop", "reorderlevel"],
+);
+const REORDER_QUANTITY: (&str, &[&str]) = (
+    ThresholdField::ReorderQuantity.heading(),
+    &["reorderqty", "reorderquantity", "orderqty"],
+);
+const SLOW_MOVING_DAYS: (&str, &[&str]) = (
+    ThresholdField::SlowMovingDays.heading(),
+    &["slowmovingdays", "slowdays"],
+);
+const DEAD_STOCK_DAYS: (&str, &[&str]) = (
+    ThresholdField::DeadStockDays.heading(),
+    &["deadstockdays", "deaddays"],
+);
+const STOCK_ID: (&str, &[&str]) = ("Stock ID", &["stockid"]);
+
+/// Validates a threshold update sheet. Items are named by a Stock ID column or by
+/// the identity columns of the upload template; Min Qty is required and the other
+/// threshold columns are optional. Rows with any invalid cell are reported and left
+/// out. Blank reorder cells mean zero, blank day limits keep the item's current ones.
+pub fn parse_threshold_sheet(sheet: &RawSheet) -> Result<ParsedThresholds, String> {
+    let keys: Vec<String> = sheet.headers.iter().map(|h| header_key(h)).collect();
+    let find = |aliases: &[&str]| keys.iter().position(|k| aliases.contains(&k.as_str()));
+    let identity = [Field::Project, Field::PartName, Field::Description, Field::Uom, Field::Location]
+        .map(|field| find(field.aliases()));
+    let stock_id = find(STOCK_ID.1);
+    if stock_id.is_none() && identity.iter().zip([true, true, false, true, true]).any(|(idx, needed)| needed && idx.is_none()) {
+        return Err("Name items with a Stock ID column, or with Project, Part Name, UOM and Location columns".to_string());
+    }
+    let min_quantity = find(MIN_QUANTITY.1).ok_or("Missing required column: Min Qty")?;
+    let reorder_point = find(REORDER_POINT.1);
+    let reorder_quantity = find(REORDER_QUANTITY.1);
+    let slow_moving_days = find(SLOW_MOVING_DAYS.1);
+    let dead_stock_days = find(DEAD_STOCK_DAYS.1);
+
+    let mut parsed = ParsedThresholds {
+        rows: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (sheet_row, cells) in &sheet.rows {
+        if cells.iter().all(RawCell::is_blank) {
+            continue;
+        }
+        let cell = |idx: Option<usize>| idx.and_then(|i| cells.get(i)).cloned().unwrap_or(RawCell::Empty);
+        let text = |idx: Option<usize>| Some(cell(idx).display()).filter(|t| !t.is_empty());
+        let mut row_errors = Vec::new();
+        let mut error = |idx: usize, heading: &str, message: &str| {
+            row_errors.push(CellError {
+                sheet_row: *sheet_row,
+                column: sheet.headers.get(idx).cloned().unwrap_or_else(|| heading.to_string()),
+                value: cell(Some(idx)).display(),
+                message: message.to_string(),
+            });
+        };
+        let mut quantity = |idx: Option<usize>, heading: &str, required: bool| {
+            let Some(idx) = idx.filter(|_| !cell(idx).is_blank()) else {
+                if required {
+                    error(min_quantity, heading, "Required value is missing");
+                }
+                return Decimal::ZERO;
+            };
+            match parse_decimal(&cell(Some(idx))) {
+                Some(q) if q.is_sign_negative() => error(idx, heading, "Cannot be negative"),
+                Some(q) if q.scale() > 4 => error(idx, heading, "More than 4 decimal places"),
+                Some(q) => return q,
+                None => error(idx, heading, "Not a number"),
+            }
+            Decimal::ZERO
+        };
+        let threshold_min = quantity(Some(min_quantity), MIN_QUANTITY.0, true);
+        let threshold_point = quantity(reorder_point, REORDER_POINT.0, false);
+        let threshold_quantity = quantity(reorder_quantity, REORDER_QUANTITY.0, false);
+        let mut days = |idx: Option<usize>, heading: &str| {
+            let idx = idx.filter(|_| !cell(idx).is_blank())?;
+            match parse_decimal(&cell(Some(idx))) {
+                Some(d) if d.fract().is_zero() && d > Decimal::ZERO && d.to_i32().is_some() => d.to_i32(),
+                Some(_) => {
+                    error(idx, heading, "Must be a whole number of days greater than zero");
+                    None
+                }
+                None => {
+                    error(idx, heading, "Not a number");
+                    None
+                }
+            }
+        };
+        let threshold_slow = days(slow_moving_days, SLOW_MOVING_DAYS.0);
+        let threshold_dead = days(dead_stock_days, DEAD_STOCK_DAYS.0);
+
+        if row_errors.is_empty() {
+            parsed.rows.push((
+                *sheet_row,
+                ThresholdImportRow {
+                    stock_id: text(stock_id),
+                    project: text(identity[0]),
+                    part_name: text(identity[1]),
+                    description: text(identity[2]),
+                    uom: text(identity[3]),
+                    location: text(identity[4]),
+                    threshold: ThresholdInput {
+                        min_quantity: threshold_min,
+                        reorder_point: threshold_point,
+                        reorder_quantity: threshold_quantity,
+                        slow_moving_days: threshold_slow,
+                        dead_stock_days: threshold_dead,
+                    },
+                },
+            ));
+        } else {
+            parsed.errors.extend(row_errors);
+        }
+    }
+    Ok(parsed)
+}
+
+const DATE_FORMATS: [&str; 10] = [
+    "%Y-%m-%d", "%Y/%m/%d", "%d-%m-%Y", "%d/%m/%Y", "%d.%m.%Y", "%d-%b-%Y", "%d %b %Y", "%d-%m-%y", "%d/%m/%y",
+    "%d-%b-%y",
+];
+const DATE_TIME_FORMATS: [&str; 7] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d-%m-%Y %H:%M:%S",
+];
+
+/// Parses a receipt date from an Excel serial number (e.g. `45382`) or text. Numeric
+/// text dates are read day first, as on our supplier paperwork. A bare date means
+/// midnight; RFC 3339 timestamps are converted to local time.
+pub fn parse_receipt_date(value: &str) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+    if let Ok(serial) = value.parse::<f64>() {
+        return excel_serial_date(serial).ok_or_else(|| format!("'{}' is not a valid Excel date", value));
+    }
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Local).naive_local());
+    }
+
+    let plausible = |d: &NaiveDate| (1900..=9999).contains(&chrono::Datelike::year(d));
+    for format in DATE_TIME_FORMATS {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(value, format) {
+            if plausible(&ts.date()) {
+                return Ok(ts);
+            }
+        }
+    }
+    for format in DATE_FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            if plausible(&date) {
+                return Ok(date.and_time(chrono::NaiveTime::MIN));
+            }
+        }
+    }
+    Err(format!("'{}' is not a recognised date. Use YYYY-MM-DD or DD/MM/YYYY", value))
+}
+
+/// Excel's 1900 date system, counting from 1899-12-30 so that the phantom
+/// 29 February 1900 only affects dates nobody will import.
+fn excel_serial_date(serial: f64) -> Option<NaiveDateTime> {
+    if !(1.0..2_958_466.0).contains(&serial) {
+        return None;
+    }
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)?.and_time(chrono::NaiveTime::MIN);
+    let seconds = (serial * 86_400.0).round() as i64;
+    epoch.checked_add_signed(chrono::Duration::seconds(seconds))
+}
+
+/// SHA-256 of a file's contents as lowercase hex.
+pub fn file_sha256(path: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Could not open file: {}", e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> RawCell {
+        if value.is_empty() {
+            RawCell::Empty
+        } else {
+            RawCell::Text(value.to_string())
+        }
+    }
+
+    fn sheet(headers: &[&str], rows: &[Vec<RawCell>]) -> RawSheet {
+        RawSheet {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: rows.iter().enumerate().map(|(i, cells)| (i as u32 + 2, cells.clone())).collect(),
+        }
+    }
+
+    const TEMPLATE: [&str; 7] = ["Project", "Part Name", "Description", "Qty", "UOM", "Location", "Rec Date"];
+
+    fn template_row(part: &str, qty: RawCell, date: RawCell) -> Vec<RawCell> {
+        vec![text("P1"), text(part), text("Hex bolt"), qty, text("Nos"), text("Store A"), date]
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_time(chrono::NaiveTime::MIN)
+    }
+
+    #[test]
+    fn excel_serials_count_from_1899_12_30() {
+        assert_eq!(excel_serial_date(45382.0), Some(date(2024, 3, 31)));
+        assert_eq!(excel_serial_date(45382.5), Some(date(2024, 3, 31) + chrono::Duration::hours(12)));
+        // From 1 March 1900 on Excel and we agree
+        assert_eq!(excel_serial_date(61.0), Some(date(1900, 3, 1)));
+        // Serial 60 is Excel's phantom 29 February 1900, which doesn't exist
+        assert_eq!(excel_serial_date(60.0), Some(date(1900, 2, 28)));
+        assert_eq!(excel_serial_date(1.0), Some(date(1899, 12, 31)));
+    }
+
+    #[test]
+    fn excel_serials_out_of_range_are_rejected() {
+        assert_eq!(excel_serial_date(0.0), None);
+        assert_eq!(excel_serial_date(-5.0), None);
+        assert_eq!(excel_serial_date(2_958_465.0), Some(date(9999, 12, 31)));
+        assert_eq!(excel_serial_date(2_958_466.0), None);
+    }
+
+    #[test]
+    fn receipt_dates_are_read_day_first() {
+        assert_eq!(parse_receipt_date("31/03/2024"), Ok(date(2024, 3, 31)));
+        assert_eq!(parse_receipt_date("01/02/2024"), Ok(date(2024, 2, 1)));
+        assert_eq!(parse_receipt_date(" 2024-03-31 "), Ok(date(2024, 3, 31)));
+        assert_eq!(parse_receipt_date("31-Mar-2024"), Ok(date(2024, 3, 31)));
+        assert_eq!(parse_receipt_date("31.03.2024"), Ok(date(2024, 3, 31)));
+        assert_eq!(parse_receipt_date("45382"), Ok(date(2024, 3, 31)));
+        assert_eq!(
+            parse_receipt_date("2024-03-31 14:05"),
+            Ok(date(2024, 3, 31) + chrono::Duration::minutes(14 * 60 + 5))
+        );
+    }
+
+    #[test]
+    fn two_digit_years_are_not_read_as_first_century() {
+        // %d-%m-%Y accepts "24" as year 24, which is skipped as implausible
+        assert_eq!(parse_receipt_date("31-03-24"), Ok(date(2024, 3, 31)));
+    }
+
+    #[test]
+    fn receipt_dates_before_1900_are_rejected() {
+        assert_eq!(parse_receipt_date("1900-01-01"), Ok(date(1900, 1, 1)));
+        assert!(parse_receipt_date("31/12/1899").is_err());
+        assert!(parse_receipt_date("31/02/2024").is_err());
+        assert!(parse_receipt_date("yesterday").is_err());
+        assert!(parse_receipt_date("0").is_err());
+    }
+
+    #[test]
+    fn rfc3339_timestamps_become_local_time() {
+        let expected = DateTime::parse_from_rfc3339("2024-03-31T10:00:00+05:30")
+            .unwrap()
+            .with_timezone(&Local)
+            .naive_local();
+        assert_eq!(parse_receipt_date("2024-03-31T10:00:00+05:30"), Ok(expected));
+    }
+
+    #[test]
+    fn template_columns_match_aliases_in_any_order() {
+        let headers: Vec<String> = ["Store", "qty", "Item Name", "U.O.M", "Project Name", "Desc", "Extra"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let mapping = template_columns(&headers).unwrap();
+        assert_eq!(mapping.column(Field::Location), Some(0));
+        assert_eq!(mapping.column(Field::Quantity), Some(1));
+        assert_eq!(mapping.column(Field::PartName), Some(2));
+        assert_eq!(mapping.column(Field::Uom), Some(3));
+        assert_eq!(mapping.column(Field::Project), Some(4));
+        assert_eq!(mapping.column(Field::Description), Some(5));
+        assert_eq!(mapping.column(Field::RecDate), None);
+    }
+
+    #[test]
+    fn template_columns_require_the_identity_and_quantity() {
+        let headers: Vec<String> = ["Project", "Part Name", "Qty", "Location"].iter().map(|h| h.to_string()).collect();
+        let err = template_columns(&headers).unwrap_err();
+        assert_eq!(err, "Missing required column(s): UOM");
+    }
+
+    #[test]
+    fn parse_sheet_keeps_valid_rows_and_reports_bad_cells() {
+        let sheet = sheet(
+            &TEMPLATE,
+            &[
+                template_row("M6 Bolt", RawCell::Number(10.0), RawCell::Number(45382.0)),
+                template_row("M8 Bolt", text("ten"), RawCell::Empty),
+                vec![RawCell::Empty; 7],
+                template_row("", text("1,250.5"), text("2024-02-30")),
+                template_row("M10 Bolt", text("-1"), RawCell::Empty),
+                template_row("M12 Bolt", text("0.12345"), RawCell::Empty),
+            ],
+        );
+        let mapping = template_columns(&sheet.headers).unwrap();
+        let parsed = parse_sheet(&sheet, &mapping, &[]);
+
+        assert_eq!(parsed.total_rows, 5);
+        assert_eq!(parsed.rows.len(), 1);
+        let row = &parsed.rows[0];
+        assert_eq!(row.sheet_row, 2);
+        assert_eq!(row.row.part_name, "M6 Bolt");
+        assert_eq!(row.row.quantity, Decimal::from(10));
+        assert_eq!(row.row.rec_date.as_deref(), Some("2024-03-31 00:00:00"));
+
+        let errors: Vec<(u32, &str, &str)> = parsed
+            .errors
+            .iter()
+            .map(|e| (e.sheet_row, e.column.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (3, "Qty", "Not a number"),
+                (5, "Part Name", "Required value is missing"),
+                (5, "Rec Date", "'2024-02-30' is not a recognised date. Use YYYY-MM-DD or DD/MM/YYYY"),
+                (6, "Qty", "Quantity must be greater than zero"),
+                (7, "Qty", "Quantity has more than 4 decimal places"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_sheet_reads_numbers_without_float_noise() {
+        let sheet = sheet(&TEMPLATE, &[template_row("M6 Bolt", RawCell::Number(0.1 + 0.2), RawCell::Empty)]);
+        let parsed = parse_sheet(&sheet, &template_columns(&sheet.headers).unwrap(), &[]);
+        assert_eq!(parsed.rows[0].row.quantity, Decimal::new(3, 1));
+    }
+
+    #[test]
+    fn parse_sheet_checks_text_lengths() {
+        let long = "x".repeat(101);
+        let sheet = sheet(&TEMPLATE, &[template_row(&long, RawCell::Number(1.0), RawCell::Empty)]);
+        let parsed = parse_sheet(&sheet, &template_columns(&sheet.headers).unwrap(), &[]);
+        assert!(parsed.rows.is_empty());
+        assert_eq!(parsed.errors[0].message, "Longer than 100 characters");
+    }
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|h| h.to_string()).collect()
+    }
+
+    fn rule(column: &str, operator: &str, value: Option<&str>) -> SkipRule {
+        SkipRule {
+            column: column.to_string(),
+            operator: operator.to_string(),
+            value: value.map(str::to_string),
+        }
+    }
+
+    fn conversion(from_uom: &str, to_uom: &str, factor: Decimal) -> UnitConversion {
+        UnitConversion {
+            from_uom: from_uom.to_string(),
+            to_uom: to_uom.to_string(),
+            factor,
+        }
+    }
+
+    const SUPPLIER: [&str; 6] = ["Material", "Qty.", "Unit", "Rate", "Job", "Grade"];
+
+    /// A supplier layout without a location column, which comes from a default
+    fn supplier_profile() -> MappingProfile {
+        let column = |source: &str, field: &str| ColumnMapping {
+            source: source.to_string(),
+            field: field.to_string(),
+        };
+        MappingProfile {
+            profile_id: None,
+            name: "Acme".to_string(),
+            header_fingerprint: None,
+            columns: vec![
+                column("MATERIAL", "part_name"),
+                column("qty", "quantity"),
+                column("Unit", "uom"),
+                column("Rate", "unit_cost"),
+                column("Job", "project"),
+                column("Grade", "attribute:Grade"),
+            ],
+            defaults: vec![FieldDefault {
+                field: "location".to_string(),
+                value: "Main Store".to_string(),
+            }],
+            unit_conversions: vec![
+                conversion("BOX", "Nos", Decimal::from(100)),
+                conversion("Pair", "Nos", Decimal::from(2)),
+                conversion("Half", "Nos", Decimal::new(5, 1)),
+            ],
+            skip_rules: vec![rule("Material", "STARTS_WITH", Some("Total")), rule("Job", "EMPTY", None)],
+        }
+    }
+
+    fn supplier_row(part: &str, qty: &str, uom: &str, rate: &str, job: &str) -> Vec<RawCell> {
+        vec![text(part), text(qty), text(uom), text(rate), text(job), RawCell::Empty]
+    }
+
+    #[test]
+    fn profile_resolve_finds_sources_by_header_key() {
+        let headers = headers(&["Job", "Grade", "material", "UNIT", "Qty", "Rate"]);
+        let mapping = supplier_profile().resolve(&headers).unwrap();
+        assert_eq!(mapping.column(Field::Project), Some(0));
+        assert_eq!(mapping.column(Field::PartName), Some(2));
+        assert_eq!(mapping.column(Field::Uom), Some(3));
+        assert_eq!(mapping.column(Field::Quantity), Some(4));
+        assert_eq!(mapping.column(Field::UnitCost), Some(5));
+        assert_eq!(mapping.column(Field::Location), None);
+        assert_eq!(mapping.default_value(Field::Location), Some("Main Store"));
+        assert_eq!(mapping.attributes, vec![("Grade".to_string(), 1)]);
+        let skip_columns: Vec<usize> = mapping.skip_rules.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(skip_columns, vec![2, 0]);
+        assert_eq!(mapping.conversions.len(), 3);
+    }
+
+    #[test]
+    fn profile_resolve_reports_what_the_sheet_lacks() {
+        let err = supplier_profile().resolve(&headers(&["Material", "Qty", "Unit", "Rate", "Grade"])).unwrap_err();
+        assert_eq!(err, "Profile 'Acme' expects a 'Job' column");
+
+        let mut profile = supplier_profile();
+        profile.defaults.clear();
+        let err = profile.resolve(&headers(&SUPPLIER)).unwrap_err();
+        assert_eq!(err, "Missing required column(s): Location");
+    }
+
+    #[test]
+    fn profile_resolve_validates_the_profile_first() {
+        let mut profile = supplier_profile();
+        profile.columns[0].field = "colour".to_string();
+        assert_eq!(profile.resolve(&headers(&SUPPLIER)).unwrap_err(), "Unknown field 'colour'");
+
+        let mut profile = supplier_profile();
+        profile.unit_conversions[0].factor = Decimal::ZERO;
+        assert_eq!(
+            profile.resolve(&headers(&SUPPLIER)).unwrap_err(),
+            "Conversion factor for 'BOX' must be greater than zero"
+        );
+
+        let mut profile = supplier_profile();
+        profile.skip_rules.push(rule("Material", "LIKE", Some("x")));
+        assert_eq!(profile.resolve(&headers(&SUPPLIER)).unwrap_err(), "Unknown skip rule operator 'LIKE'");
+    }
+
+    #[test]
+    fn attribute_fields_need_a_name() {
+        assert_eq!(attribute_name("attribute:Grade"), Some("Grade"));
+        assert_eq!(attribute_name("ATTRIBUTE: Shelf life "), Some("Shelf life"));
+        assert_eq!(attribute_name("attribute: "), None);
+        assert_eq!(attribute_name("part_name"), None);
+    }
+
+    #[test]
+    fn skip_rules_ignore_case_and_surrounding_space() {
+        assert!(rule("A", "equals", Some(" total ")).matches(&text("TOTAL")));
+        assert!(rule("A", "CONTAINS", Some("sub")).matches(&text("Page subtotal")));
+        assert!(rule("A", "STARTS_WITH", Some("Section")).matches(&text("  section B")));
+        assert!(!rule("A", "STARTS_WITH", Some("Section")).matches(&text("Cross section")));
+        assert!(rule("A", "EQUALS", Some("10")).matches(&RawCell::Number(10.0)));
+        assert!(!rule("A", "BETWEEN", Some("a")).matches(&text("a")));
+    }
+
+    #[test]
+    fn skip_rules_on_blank_cells() {
+        for blank in [RawCell::Empty, text("   ")] {
+            assert!(rule("A", "EMPTY", None).matches(&blank));
+            // A rule with no value never matches by containing or starting with it
+            assert!(!rule("A", "CONTAINS", None).matches(&blank));
+            assert!(!rule("A", "CONTAINS", Some(" ")).matches(&blank));
+            assert!(!rule("A", "STARTS_WITH", None).matches(&blank));
+            assert!(!rule("A", "EQUALS", Some("Total")).matches(&blank));
+        }
+        assert!(!rule("A", "EMPTY", None).matches(&text("x")));
+        assert!(!rule("A", "CONTAINS", None).matches(&text("x")));
+    }
+
+    #[test]
+    fn header_fingerprint_ignores_order_case_and_punctuation() {
+        let fingerprint = header_fingerprint(&headers(&["PO No.", "Part Name", "Qty"]));
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(header_fingerprint(&headers(&["qty", "part_name", "po no"])), fingerprint);
+        // Blank and repeated headers don't count
+        assert_eq!(header_fingerprint(&headers(&["Qty", "", "Part Name", "--", "PO No", "QTY"])), fingerprint);
+        assert_ne!(header_fingerprint(&headers(&["PO No", "Part Name", "Quantity"])), fingerprint);
+    }
+
+    #[test]
+    fn parse_sheet_applies_profile_skip_rules_and_defaults() {
+        let sheet = sheet(
+            &SUPPLIER,
+            &[
+                supplier_row("M6 Bolt", "5", "Nos", "", "P1"),
+                supplier_row("Total for page", "5", "", "", "P1"),
+                // Only the skip rule's column is blank
+                supplier_row("Heading", "", "", "", ""),
+                supplier_row("M8 Bolt", "3", "Nos", "", "P1"),
+            ],
+        );
+        let mapping = supplier_profile().resolve(&sheet.headers).unwrap();
+        let parsed = parse_sheet(&sheet, &mapping, &[]);
+
+        assert_eq!(parsed.total_rows, 4);
+        assert_eq!(parsed.skipped_rows, 2);
+        assert!(parsed.errors.is_empty());
+        let parts: Vec<(&str, &str)> = parsed
+            .rows
+            .iter()
+            .map(|r| (r.row.part_name.as_str(), r.row.location.as_str()))
+            .collect();
+        assert_eq!(parts, vec![("M6 Bolt", "Main Store"), ("M8 Bolt", "Main Store")]);
+    }
+
+    #[test]
+    fn parse_sheet_converts_units() {
+        let sheet = sheet(
+            &SUPPLIER,
+            &[
+                supplier_row("M6 Bolt", "2", "box", "150", "P1"),
+                // 10 / 3 per piece
+                supplier_row("M8 Bolt", "1", "BOX", "1000", "P1"),
+                supplier_row("Gasket", "1", "Pair", "10", "P1"),
+                supplier_row("Kg item", "1.5", "Kg", "7.25", "P1"),
+            ],
+        );
+        let mapping = supplier_profile().resolve(&sheet.headers).unwrap();
+        let rows: Vec<(Decimal, String, Option<Decimal>)> = parse_sheet(&sheet, &mapping, &[])
+            .rows
+            .into_iter()
+            .map(|r| (r.row.quantity, r.row.uom, r.row.unit_cost))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Decimal::from(200), "Nos".to_string(), Some(Decimal::new(15, 1))),
+                (Decimal::from(100), "Nos".to_string(), Some(Decimal::from(10))),
+                (Decimal::from(2), "Nos".to_string(), Some(Decimal::from(5))),
+                (Decimal::new(15, 1), "Kg".to_string(), Some(Decimal::new(725, 2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn unit_conversion_rounds_cost_half_away_from_zero() {
+        let sheet = sheet(
+            &SUPPLIER,
+            &[
+                supplier_row("Washer", "3", "Pair", "0.0005", "P1"),
+                supplier_row("Nut", "1", "Box", "0.015", "P1"),
+                supplier_row("Pin", "1", "Box", "0.025", "P1"),
+            ],
+        );
+        let mapping = supplier_profile().resolve(&sheet.headers).unwrap();
+        let costs: Vec<Option<Decimal>> = parse_sheet(&sheet, &mapping, &[])
+            .rows
+            .into_iter()
+            .map(|r| r.row.unit_cost)
+            .collect();
+        // 0.00025 would be 0.0002 under banker's rounding
+        assert_eq!(
+            costs,
+            vec![Some(Decimal::new(3, 4)), Some(Decimal::new(2, 4)), Some(Decimal::new(3, 4))]
+        );
+    }
+
+    #[test]
+    fn unit_conversion_rejects_quantities_it_cannot_keep_exact() {
+        let sheet = sheet(
+            &SUPPLIER,
+            &[
+                supplier_row("Shim", "0.0001", "Half", "", "P1"),
+                supplier_row("Plate", "0.0002", "Half", "", "P1"),
+            ],
+        );
+        let mapping = supplier_profile().resolve(&sheet.headers).unwrap();
+        let parsed = parse_sheet(&sheet, &mapping, &[]);
+
+        assert_eq!(parsed.rows.len(), 1);
+        assert_eq!(parsed.rows[0].row.quantity, Decimal::new(1, 4));
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].sheet_row, 2);
+        assert_eq!(parsed.errors[0].column, "Qty.");
+        assert_eq!(parsed.errors[0].message, "Quantity has more than 4 decimal places in Nos");
+    }
+}
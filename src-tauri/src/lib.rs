@@ -1,9 +1,24 @@
 mod db;
 mod commands;
+mod pdf;
+mod classification;
+mod forecast;
+mod export;
+mod importer;
+mod matching;
+mod attributes;
+mod attachments;
+mod migrations;
 
 use commands::*;
 use std::env;
 
+/// The bulk upload steps, for `examples/import_benchmark.rs`.
+#[doc(hidden)]
+pub mod import {
+    pub use crate::commands::{post_import, preview_import_rows, ImportPreview, ImportRow};
+}
+
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -18,22 +33,88 @@ pub fn run() {
             .connect(&database_url)
             .await
             .expect("Failed to connect to MySQL");
+        migrations::run(&pool)
+            .await
+            .expect("Failed to upgrade the database schema");
         
         tauri::Builder::default()
             .plugin(tauri_plugin_opener::init())
+            .plugin(tauri_plugin_dialog::init())
             .manage(commands::AppState { pool })
             .invoke_handler(tauri::generate_handler![
                 login_user,
                 get_inventory,
+                get_inventory_as_of,
+                export_inventory,
                 issue_stock,
+                create_issue_slip,
+                get_issue_slip,
+                list_issue_slips,
+                create_requisition,
+                review_requisition,
+                reject_requisition,
+                cancel_requisition,
+                fulfil_requisition,
+                get_requisition,
+                get_outstanding_requisitions,
+                create_reservation,
+                release_reservation,
+                get_reservations,
+                set_item_threshold,
+                bulk_set_thresholds,
+                get_reorder_suggestions,
+                get_forecast,
+                create_purchase_order,
+                close_purchase_order_line,
+                get_open_purchase_orders,
                 get_stats,
+                get_movement_aging_report,
+                export_movement_aging_report,
+                get_project_consumption,
+                export_project_consumption,
+                recompute_item_classification,
                 bulk_upload_preview,
+                preview_import_file,
+                read_import_headers,
+                list_import_profiles,
+                save_import_profile,
+                delete_import_profile,
+                export_import_profiles,
+                import_import_profiles,
+                accept_import_suggestion,
                 confirm_bulk_upload,
+                get_duplicate_overrides,
+                get_import_batches,
+                revert_import_batch,
                 get_history,
                 reverse_transaction,
                 add_stock_entry,
                 add_stock_quantity,
-                get_export_history
+                update_item,
+                get_item_history,
+                merge_items,
+                undo_item_merge,
+                get_item_merges,
+                archive_items,
+                restore_items,
+                archive_dead_stock,
+                get_attribute_definitions,
+                save_attribute_definition,
+                delete_attribute_definition,
+                set_item_attributes,
+                add_attachment,
+                get_attachments,
+                open_attachment,
+                remove_attachment,
+                export_history,
+                get_stock_card,
+                export_stock_card,
+                get_settings,
+                update_setting,
+                close_period,
+                get_period_closes,
+                get_goods_receipt,
+                render_document_pdf
             ])
             .run(tauri::generate_context!())
             .expect("error while running tauri application");
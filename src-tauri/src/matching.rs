@@ -0,0 +1,316 @@
+use std::collections::BTreeSet;
+
+/// Units that suppliers spell differently, folded to one token.
+const UOM_ALIASES: [(&str, &[&str]); 8] = [
+    ("nos", &["no", "nos", "number", "numbers", "num"]),
+    ("pcs", &["pc", "pcs", "piece", "pieces"]),
+    ("ea", &["ea", "each"]),
+    ("kg", &["kg", "kgs", "kilogram", "kilograms"]),
+    ("m", &["m", "mtr", "mtrs", "meter", "meters", "metre", "metres"]),
+    ("l", &["l", "ltr", "ltrs", "litre", "litres", "liter", "liters"]),
+    ("set", &["set", "sets"]),
+    ("box", &["box", "boxes", "bx"]),
+];
+
+/// Lowercase alphanumeric tokens, so "M6-Bolt, SS" gives `m6`, `bolt`, `ss`.
+pub fn tokens(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn canonical_uom(value: &str) -> String {
+    let joined = tokens(value).join("");
+    UOM_ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&joined.as_str()))
+        .map(|(canonical, _)| canonical.to_string())
+        .unwrap_or(joined)
+}
+
+/// Character edit distance between two strings.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    edit_distance(&a, &b)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Free text split up once for comparing against many other values.
+pub struct PreparedText {
+    tokens: BTreeSet<String>,
+    /// The distinct tokens, sorted and joined by spaces
+    sorted: Vec<char>,
+    /// Characters of `sorted` in order, for `bag_distance`
+    letters: Vec<char>,
+}
+
+impl PreparedText {
+    pub fn new(value: &str) -> PreparedText {
+        let tokens: BTreeSet<String> = tokens(value).into_iter().collect();
+        let sorted: Vec<char> = tokens.iter().cloned().collect::<Vec<_>>().join(" ").chars().collect();
+        let mut letters = sorted.clone();
+        letters.sort_unstable();
+        PreparedText { tokens, sorted, letters }
+    }
+
+    /// See `text_similarity`.
+    fn similarity(&self, other: &PreparedText) -> f64 {
+        if self.sorted == other.sorted {
+            return 1.0;
+        }
+        self.score(other, edit_distance(&self.sorted, &other.sorted))
+    }
+
+    /// At least `similarity`, and much cheaper: counts the characters one side has
+    /// that the other lacks, which no fewer edits can fix, in place of the edit
+    /// distance.
+    fn similarity_bound(&self, other: &PreparedText) -> f64 {
+        self.score(other, bag_distance(&self.letters, &other.letters))
+    }
+
+    fn score(&self, other: &PreparedText, distance: usize) -> f64 {
+        if self.tokens.is_empty() && other.tokens.is_empty() {
+            return 1.0;
+        }
+        if self.tokens.is_empty() || other.tokens.is_empty() {
+            return 0.0;
+        }
+
+        let shared = self.tokens.intersection(&other.tokens).count() as f64;
+        let token_score = shared / self.tokens.union(&other.tokens).count() as f64;
+
+        let longest = self.sorted.len().max(other.sorted.len()) as f64;
+        let edit_score = 1.0 - distance as f64 / longest;
+
+        token_score.max(edit_score)
+    }
+}
+
+/// Characters of one string missing from the other, counted with repeats, for
+/// whichever side has more. Both are given sorted. Never more than the edit distance.
+fn bag_distance(a: &[char], b: &[char]) -> usize {
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    (a.len() - common).max(b.len() - common)
+}
+
+/// Similarity of two free-text values from 0 to 1. Takes the better of token-set
+/// overlap (word order doesn't matter) and edit distance over the sorted tokens
+/// (catches typos), so "M6 Bolt SS" and "M6 SS Bolt" score 1.
+pub fn text_similarity(a: &str, b: &str) -> f64 {
+    PreparedText::new(a).similarity(&PreparedText::new(b))
+}
+
+pub fn uom_similarity(a: &str, b: &str) -> f64 {
+    PreparedUom::new(a).similarity(&PreparedUom::new(b))
+}
+
+struct PreparedUom {
+    canonical: String,
+    text: PreparedText,
+}
+
+impl PreparedUom {
+    fn new(value: &str) -> PreparedUom {
+        PreparedUom {
+            canonical: canonical_uom(value),
+            text: PreparedText::new(value),
+        }
+    }
+
+    fn similarity(&self, other: &PreparedUom) -> f64 {
+        if self.canonical == other.canonical {
+            1.0
+        } else {
+            self.text.similarity(&other.text)
+        }
+    }
+
+    fn similarity_bound(&self, other: &PreparedUom) -> f64 {
+        if self.canonical == other.canonical {
+            1.0
+        } else {
+            self.text.similarity_bound(&other.text)
+        }
+    }
+}
+
+/// The identity fields of an item, as compared by `item_similarity`.
+pub struct ItemText<'a> {
+    pub part_name: &'a str,
+    pub description: &'a str,
+    pub uom: &'a str,
+    pub location: &'a str,
+}
+
+const PART_NAME_WEIGHT: f64 = 0.55;
+const DESCRIPTION_WEIGHT: f64 = 0.2;
+const UOM_WEIGHT: f64 = 0.15;
+const LOCATION_WEIGHT: f64 = 0.1;
+
+/// Weighted similarity of two items within a project. The part name carries most
+/// of the weight; description, unit and location break ties.
+pub fn item_similarity(a: &ItemText, b: &ItemText) -> f64 {
+    PreparedItem::new(a).similarity(&PreparedItem::new(b))
+}
+
+/// An item's identity fields split up once, for scoring against many others.
+pub struct PreparedItem {
+    part_name: PreparedText,
+    description: PreparedText,
+    uom: PreparedUom,
+    location: PreparedText,
+}
+
+impl PreparedItem {
+    pub fn new(item: &ItemText) -> PreparedItem {
+        PreparedItem {
+            part_name: PreparedText::new(item.part_name),
+            description: PreparedText::new(item.description),
+            uom: PreparedUom::new(item.uom),
+            location: PreparedText::new(item.location),
+        }
+    }
+
+    /// See `item_similarity`.
+    pub fn similarity(&self, other: &PreparedItem) -> f64 {
+        PART_NAME_WEIGHT * self.part_name.similarity(&other.part_name)
+            + DESCRIPTION_WEIGHT * self.description.similarity(&other.description)
+            + UOM_WEIGHT * self.uom.similarity(&other.uom)
+            + LOCATION_WEIGHT * self.location.similarity(&other.location)
+    }
+
+    /// `similarity` when it reaches `threshold`. Pairs that can't get there even by
+    /// the cheap upper bound on each field are ruled out without computing any edit
+    /// distance.
+    pub fn similarity_at_least(&self, other: &PreparedItem, threshold: f64) -> Option<f64> {
+        let bound = PART_NAME_WEIGHT * self.part_name.similarity_bound(&other.part_name)
+            + DESCRIPTION_WEIGHT * self.description.similarity_bound(&other.description)
+            + UOM_WEIGHT * self.uom.similarity_bound(&other.uom)
+            + LOCATION_WEIGHT * self.location.similarity_bound(&other.location);
+        if bound < threshold {
+            return None;
+        }
+        Some(self.similarity(other)).filter(|score| *score >= threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item<'a>(part_name: &'a str, uom: &'a str) -> ItemText<'a> {
+        ItemText {
+            part_name,
+            description: "Stainless steel",
+            uom,
+            location: "Rack A",
+        }
+    }
+
+    #[test]
+    fn tokens_split_on_punctuation_and_lowercase() {
+        assert_eq!(tokens("M6-Bolt, SS"), vec!["m6", "bolt", "ss"]);
+        assert!(tokens(" - ").is_empty());
+    }
+
+    #[test]
+    fn levenshtein_counts_character_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("bolt", "bolt"), 0);
+        // Characters, not bytes
+        assert_eq!(levenshtein("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn reordered_tokens_score_one() {
+        assert_eq!(text_similarity("M6 Bolt SS", "M6 SS Bolt"), 1.0);
+        assert_eq!(text_similarity("m6-bolt, ss", "SS Bolt M6"), 1.0);
+    }
+
+    #[test]
+    fn typo_scores_by_edit_distance() {
+        // Two edits over "bolt hex"; the token overlap alone would give 1/3
+        assert_eq!(text_similarity("Hex Bolt", "Hex Blot"), 0.75);
+    }
+
+    #[test]
+    fn blank_text_matches_only_blank() {
+        assert_eq!(text_similarity("", " "), 1.0);
+        assert_eq!(text_similarity("", "Bolt"), 0.0);
+    }
+
+    #[test]
+    fn uom_spellings_fold_together() {
+        assert_eq!(uom_similarity("Nos", "NOS."), 1.0);
+        assert_eq!(uom_similarity("No.", "Numbers"), 1.0);
+        assert_eq!(uom_similarity("Mtrs", "metre"), 1.0);
+        assert_eq!(uom_similarity("Kg", "Pcs"), 0.0);
+    }
+
+    #[test]
+    fn similarity_bound_never_falls_below_similarity() {
+        let words = ["", "bolt", "blot", "hex bolt", "bolt hex", "kitten", "sitting", "café", "cafe", "m6 ss"];
+        for a in words {
+            for b in words {
+                let (pa, pb) = (PreparedText::new(a), PreparedText::new(b));
+                assert!(bag_distance(&pa.letters, &pb.letters) <= levenshtein(a, b), "{:?} {:?}", a, b);
+                assert!(pa.similarity_bound(&pb) >= pa.similarity(&pb), "{:?} {:?}", a, b);
+            }
+        }
+        let letters = |s: &str| PreparedText::new(s).letters;
+        assert_eq!(bag_distance(&letters("bolt"), &letters("blot")), 0);
+        assert_eq!(bag_distance(&letters("kitten"), &letters("sitting")), 3);
+    }
+
+    #[test]
+    fn similarity_at_least_agrees_with_item_similarity() {
+        let pairs = [
+            (item("M6 Bolt SS", "Nos"), item("M6 SS Bolt", "NOS.")),
+            (item("Hex Bolt", "Nos"), item("Hex Blot", "Nos")),
+            (item("Gasket", "Nos"), item("Gaskte", "Kg")),
+            (item("Washer", "Nos"), item("Gasket", "Nos")),
+        ];
+        for (a, b) in &pairs {
+            let score = item_similarity(a, b);
+            let (pa, pb) = (PreparedItem::new(a), PreparedItem::new(b));
+            for threshold in [0.5, 0.75, 0.9] {
+                assert_eq!(pa.similarity_at_least(&pb, threshold), Some(score).filter(|s| *s >= threshold));
+            }
+        }
+    }
+
+    #[test]
+    fn item_similarity_ignores_order_and_unit_spelling() {
+        assert_eq!(item_similarity(&item("M6 Bolt SS", "Nos"), &item("M6 SS Bolt", "NOS.")), 1.0);
+        // The part name carries 0.55 of the weight
+        let different = item_similarity(&item("Washer", "Nos"), &item("Gasket", "Nos"));
+        assert!(different < 0.75, "{}", different);
+    }
+}
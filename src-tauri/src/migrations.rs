@@ -0,0 +1,200 @@
+use sqlx::mysql::MySqlPool;
+
+/// A column added to a table that existing installs already have. `schema.sql`
+/// creates missing tables with every column in place, but its
+/// `CREATE TABLE IF NOT EXISTS` leaves older tables as they were, so these are
+/// added at startup instead.
+struct AddColumn {
+    table: &'static str,
+    column: &'static str,
+    /// Type and options, as written after the column name in `ADD COLUMN`
+    definition: &'static str,
+    /// Table and column the new column is a foreign key to
+    references: Option<(&'static str, &'static str)>,
+    /// Fills the new column in for existing rows; run only when the column is added
+    backfill: Option<&'static str>,
+}
+
+const MIGRATIONS: &[AddColumn] = &[
+    // Goods receipt notes
+    AddColumn {
+        table: "stock_ledger",
+        column: "grn_id",
+        definition: "INT",
+        references: Some(("goods_receipt", "grn_id")),
+        backfill: None,
+    },
+    // Requisitions
+    AddColumn {
+        table: "issue_slip",
+        column: "requisition_id",
+        definition: "INT",
+        references: Some(("requisition", "requisition_id")),
+        backfill: None,
+    },
+    // Reorder points
+    AddColumn {
+        table: "stock_threshold",
+        column: "reorder_point",
+        definition: "DECIMAL(15, 4) DEFAULT 0",
+        references: None,
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_threshold",
+        column: "reorder_quantity",
+        definition: "DECIMAL(15, 4) DEFAULT 0",
+        references: None,
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_threshold",
+        column: "updated_by",
+        definition: "VARCHAR(255)",
+        references: None,
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_threshold",
+        column: "updated_at",
+        definition: "TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP",
+        references: None,
+        backfill: None,
+    },
+    // Movement aging
+    AddColumn {
+        table: "stock_ledger",
+        column: "unit_cost",
+        definition: "DECIMAL(15, 4)",
+        references: None,
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_ledger",
+        column: "reverses_ledger_id",
+        definition: "INT",
+        references: Some(("stock_ledger", "ledger_id")),
+        // Reversals used to be linked only by their reference text
+        backfill: Some(
+            "UPDATE stock_ledger r
+             JOIN stock_ledger o ON o.ledger_id = CAST(SUBSTRING_INDEX(r.reference, 'Ledger ID: ', -1) AS UNSIGNED)
+             SET r.reverses_ledger_id = o.ledger_id
+             WHERE r.transaction_type = 'REVERSAL' AND r.reference LIKE 'Reversal of Ledger ID: %'"
+        ),
+    },
+    // Receipt dates and period locks
+    AddColumn {
+        table: "goods_receipt",
+        column: "receipt_date",
+        definition: "DATETIME",
+        references: None,
+        backfill: None,
+    },
+    // Import batches
+    AddColumn {
+        table: "stock_ledger",
+        column: "batch_id",
+        definition: "INT",
+        references: Some(("import_batch", "batch_id")),
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_ledger",
+        column: "po_line_id",
+        definition: "INT",
+        references: Some(("purchase_order_line", "po_line_id")),
+        backfill: None,
+    },
+    AddColumn {
+        table: "stock_master",
+        column: "batch_id",
+        definition: "INT",
+        references: Some(("import_batch", "batch_id")),
+        backfill: None,
+    },
+    AddColumn {
+        table: "purchase_order_line",
+        column: "closed_by",
+        definition: "ENUM('RECEIPT', 'MANUAL')",
+        references: None,
+        // Lines closed short of their ordered quantity can only have been closed by hand
+        backfill: Some(
+            "UPDATE purchase_order_line
+             SET closed_by = IF(received_qty >= ordered_qty, 'RECEIPT', 'MANUAL')
+             WHERE status = 'CLOSED'"
+        ),
+    },
+    // Item merges
+    AddColumn {
+        table: "stock_master",
+        column: "merged_into",
+        definition: "VARCHAR(36)",
+        references: Some(("stock_master", "stock_id")),
+        backfill: None,
+    },
+    // Item archiving
+    AddColumn {
+        table: "stock_master",
+        column: "status",
+        definition: "ENUM('ACTIVE', 'ARCHIVED') NOT NULL DEFAULT 'ACTIVE'",
+        references: None,
+        backfill: None,
+    },
+];
+
+/// Adds whatever columns and foreign keys from `MIGRATIONS` the database is
+/// missing. Safe to run on every start. The tables involved must already exist,
+/// so the current `schema.sql` has to have been applied to the database.
+pub async fn run(pool: &MySqlPool) -> Result<(), String> {
+    for migration in MIGRATIONS {
+        let exists: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM information_schema.COLUMNS
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?"
+        )
+        .bind(migration.table)
+        .bind(migration.column)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if exists == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                migration.table, migration.column, migration.definition
+            ))
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Could not add {}.{}: {}", migration.table, migration.column, e))?;
+            if let Some(backfill) = migration.backfill {
+                sqlx::query(backfill)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| format!("Could not fill in {}.{}: {}", migration.table, migration.column, e))?;
+            }
+        }
+
+        // Checked on its own, in case an earlier start added the column but not the key
+        let Some((ref_table, ref_column)) = migration.references else {
+            continue;
+        };
+        let has_key: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM information_schema.KEY_COLUMN_USAGE
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ? AND REFERENCED_TABLE_NAME = ?"
+        )
+        .bind(migration.table)
+        .bind(migration.column)
+        .bind(ref_table)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if has_key == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD FOREIGN KEY ({}) REFERENCES {}({})",
+                migration.table, migration.column, ref_table, ref_column
+            ))
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Could not add the foreign key on {}.{}: {}", migration.table, migration.column, e))?;
+        }
+    }
+    Ok(())
+}
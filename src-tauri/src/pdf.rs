@@ -0,0 +1,484 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+// A4 portrait, in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+const ROW_HEIGHT: f32 = 16.0;
+const FOOTER_HEIGHT: f32 = 90.0;
+const LOGO_MAX_HEIGHT: f32 = 50.0;
+const LOGO_MAX_WIDTH: f32 = 120.0;
+
+/// Letterhead printed at the top of the first page, taken from app settings.
+#[derive(Debug, Default, Clone)]
+pub struct CompanyHeader {
+    pub name: String,
+    pub address: Option<String>,
+    pub logo: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub heading: String,
+    pub width: f32,
+    pub align: Align,
+}
+
+impl Column {
+    pub fn left(heading: &str, width: f32) -> Self {
+        Column { heading: heading.to_string(), width, align: Align::Left }
+    }
+
+    pub fn right(heading: &str, width: f32) -> Self {
+        Column { heading: heading.to_string(), width, align: Align::Right }
+    }
+}
+
+/// A stored document (issue slip, GRN, ...) flattened into a printable table.
+#[derive(Debug, Clone)]
+pub struct PrintableDocument {
+    pub title: String,
+    pub fields: Vec<(String, String)>,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<String>>,
+    pub signatures: Vec<String>,
+}
+
+/// Renders `document` to PDF bytes. The output depends only on the inputs: no
+/// timestamps or random document IDs are written, so a reprint is byte-identical.
+pub fn render(document: &PrintableDocument, company: &CompanyHeader) -> Result<Vec<u8>, String> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+
+    let regular_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let bold_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica-Bold",
+        "Encoding" => "WinAnsiEncoding",
+    });
+
+    let mut resources = dictionary! {
+        "Font" => dictionary! {
+            "F1" => regular_id,
+            "F2" => bold_id,
+        },
+    };
+
+    let logo = match company.logo.as_deref() {
+        Some(bytes) => {
+            let (image_id, width, height) = embed_logo(&mut doc, bytes)?;
+            resources.set("XObject", dictionary! { "Logo" => image_id });
+            Some((width, height))
+        }
+        None => None,
+    };
+    let resources_id = doc.add_object(resources);
+
+    // Split rows over pages; the first page loses space to the document header.
+    let first_capacity = rows_per_page(first_page_table_top(document));
+    let other_capacity = rows_per_page(continuation_table_top());
+    let mut chunks: Vec<&[Vec<String>]> = Vec::new();
+    let mut remaining = document.rows.as_slice();
+    let (head, tail) = remaining.split_at(remaining.len().min(first_capacity));
+    chunks.push(head);
+    remaining = tail;
+    while !remaining.is_empty() {
+        let (head, tail) = remaining.split_at(remaining.len().min(other_capacity));
+        chunks.push(head);
+        remaining = tail;
+    }
+
+    let page_count = chunks.len();
+    let mut kids: Vec<Object> = Vec::new();
+    for (page_idx, rows) in chunks.into_iter().enumerate() {
+        let mut page = PageWriter::default();
+        let table_top = if page_idx == 0 {
+            draw_letterhead(&mut page, document, company, logo);
+            first_page_table_top(document)
+        } else {
+            page.text(MARGIN, PAGE_HEIGHT - MARGIN - 12.0, 12.0, true, &document.title);
+            continuation_table_top()
+        };
+
+        draw_table(&mut page, &document.columns, rows, table_top);
+
+        if page_idx + 1 == page_count {
+            draw_signatures(&mut page, &document.signatures);
+        }
+        page.text(
+            PAGE_WIDTH - MARGIN - 60.0,
+            MARGIN - 20.0,
+            8.0,
+            false,
+            &format!("Page {} of {}", page_idx + 1, page_count),
+        );
+
+        let content = Content { operations: page.operations };
+        let content_id = doc.add_object(Stream::new(
+            dictionary! {},
+            content.encode().map_err(|e| e.to_string())?,
+        ));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => page_count as i64,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), PAGE_WIDTH.into(), PAGE_HEIGHT.into()],
+        }),
+    );
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    let info_id = doc.add_object(dictionary! {
+        "Title" => Object::string_literal(encode_text(&document.title)),
+        "Producer" => Object::string_literal("InvPro"),
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.trailer.set("Info", info_id);
+    doc.compress();
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+fn first_page_table_top(document: &PrintableDocument) -> f32 {
+    let field_rows = document.fields.len().div_ceil(2) as f32;
+    PAGE_HEIGHT - MARGIN - LOGO_MAX_HEIGHT - 40.0 - field_rows * 14.0 - 10.0
+}
+
+fn continuation_table_top() -> f32 {
+    PAGE_HEIGHT - MARGIN - 30.0
+}
+
+fn rows_per_page(table_top: f32) -> usize {
+    // One row is taken by the column headings
+    let usable = table_top - MARGIN - FOOTER_HEIGHT;
+    ((usable / ROW_HEIGHT) as usize).saturating_sub(1).max(1)
+}
+
+fn draw_letterhead(
+    page: &mut PageWriter,
+    document: &PrintableDocument,
+    company: &CompanyHeader,
+    logo: Option<(f32, f32)>,
+) {
+    let top = PAGE_HEIGHT - MARGIN;
+    let mut text_x = MARGIN;
+    if let Some((width, height)) = logo {
+        page.image("Logo", MARGIN, top - height, width, height);
+        text_x += width + 12.0;
+    }
+
+    page.text(text_x, top - 16.0, 16.0, true, &company.name);
+    if let Some(address) = &company.address {
+        for (idx, line) in address.lines().take(2).enumerate() {
+            page.text(text_x, top - 30.0 - idx as f32 * 11.0, 9.0, false, line.trim());
+        }
+    }
+
+    let title_y = top - LOGO_MAX_HEIGHT - 20.0;
+    page.text(MARGIN, title_y, 13.0, true, &document.title);
+    page.line(MARGIN, title_y - 6.0, PAGE_WIDTH - MARGIN, title_y - 6.0, 0.8);
+
+    // Header fields in two columns
+    let half = (PAGE_WIDTH - 2.0 * MARGIN) / 2.0;
+    for (idx, (label, value)) in document.fields.iter().enumerate() {
+        let x = MARGIN + (idx % 2) as f32 * half;
+        let y = title_y - 22.0 - (idx / 2) as f32 * 14.0;
+        page.text(x, y, 9.0, true, &format!("{}:", label));
+        page.text(x + 80.0, y, 9.0, false, &fit(value, half - 90.0, 9.0));
+    }
+}
+
+fn draw_table(page: &mut PageWriter, columns: &[Column], rows: &[Vec<String>], top: f32) {
+    let right = PAGE_WIDTH - MARGIN;
+    page.fill_rect(MARGIN, top - ROW_HEIGHT, right - MARGIN, ROW_HEIGHT, 0.9);
+    draw_row(page, columns, columns.iter().map(|c| c.heading.as_str()), top, true);
+
+    let mut y = top - ROW_HEIGHT;
+    for row in rows {
+        draw_row(page, columns, row.iter().map(String::as_str), y, false);
+        y -= ROW_HEIGHT;
+        page.line(MARGIN, y, right, y, 0.3);
+    }
+    page.line(MARGIN, top, right, top, 0.8);
+    page.line(MARGIN, top - ROW_HEIGHT, right, top - ROW_HEIGHT, 0.8);
+}
+
+fn draw_row<'a>(
+    page: &mut PageWriter,
+    columns: &[Column],
+    cells: impl Iterator<Item = &'a str>,
+    top: f32,
+    bold: bool,
+) {
+    let baseline = top - ROW_HEIGHT + 4.5;
+    let mut x = MARGIN;
+    for (column, cell) in columns.iter().zip(cells) {
+        let text = fit(cell, column.width - 6.0, 8.5);
+        let text_x = match column.align {
+            Align::Left => x + 3.0,
+            Align::Right => x + column.width - 3.0 - text_width(&text, 8.5),
+        };
+        page.text(text_x, baseline, 8.5, bold, &text);
+        x += column.width;
+    }
+}
+
+fn draw_signatures(page: &mut PageWriter, signatures: &[String]) {
+    if signatures.is_empty() {
+        return;
+    }
+    let slot = (PAGE_WIDTH - 2.0 * MARGIN) / signatures.len() as f32;
+    let y = MARGIN + 30.0;
+    for (idx, label) in signatures.iter().enumerate() {
+        let x = MARGIN + idx as f32 * slot;
+        page.line(x + 10.0, y, x + slot - 10.0, y, 0.5);
+        page.text(x + 10.0, y - 12.0, 9.0, false, label);
+    }
+}
+
+/// Decodes a PNG/JPEG logo, flattens any transparency onto white and embeds it as
+/// an RGB image XObject. Returns the object id and the drawn size in points.
+fn embed_logo(doc: &mut Document, bytes: &[u8]) -> Result<(ObjectId, f32, f32), String> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to read company logo: {}", e))?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        for channel in [r, g, b] {
+            let blended = (channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255;
+            rgb.push(blended as u8);
+        }
+    }
+
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width as i64,
+            "Height" => height as i64,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        },
+        rgb,
+    );
+    stream.compress().map_err(|e| e.to_string())?;
+    let id = doc.add_object(stream);
+
+    let scale = (LOGO_MAX_HEIGHT / height as f32).min(LOGO_MAX_WIDTH / width as f32);
+    Ok((id, width as f32 * scale, height as f32 * scale))
+}
+
+#[derive(Default)]
+struct PageWriter {
+    operations: Vec<Operation>,
+}
+
+impl PageWriter {
+    fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let font = if bold { "F2" } else { "F1" };
+        self.operations.push(Operation::new("BT", vec![]));
+        self.operations.push(Operation::new("Tf", vec![font.into(), size.into()]));
+        self.operations.push(Operation::new("Td", vec![round(x).into(), round(y).into()]));
+        self.operations.push(Operation::new(
+            "Tj",
+            vec![Object::String(encode_text(text), StringFormat::Literal)],
+        ));
+        self.operations.push(Operation::new("ET", vec![]));
+    }
+
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32) {
+        self.operations.push(Operation::new("w", vec![width.into()]));
+        self.operations.push(Operation::new("m", vec![round(x1).into(), round(y1).into()]));
+        self.operations.push(Operation::new("l", vec![round(x2).into(), round(y2).into()]));
+        self.operations.push(Operation::new("S", vec![]));
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, gray: f32) {
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.push(Operation::new("g", vec![gray.into()]));
+        self.operations.push(Operation::new(
+            "re",
+            vec![round(x).into(), round(y).into(), round(width).into(), round(height).into()],
+        ));
+        self.operations.push(Operation::new("f", vec![]));
+        self.operations.push(Operation::new("Q", vec![]));
+    }
+
+    fn image(&mut self, name: &str, x: f32, y: f32, width: f32, height: f32) {
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.push(Operation::new(
+            "cm",
+            vec![
+                round(width).into(),
+                0.into(),
+                0.into(),
+                round(height).into(),
+                round(x).into(),
+                round(y).into(),
+            ],
+        ));
+        self.operations.push(Operation::new("Do", vec![Object::Name(name.as_bytes().to_vec())]));
+        self.operations.push(Operation::new("Q", vec![]));
+    }
+}
+
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Maps text to WinAnsi bytes for the base-14 fonts; characters outside Latin-1 become `?`.
+fn encode_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7E | 0xA0..=0xFF => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Helvetica advance widths (1/1000 em) for the characters that appear in numbers;
+/// anything else is estimated at an average glyph width.
+fn glyph_width(c: char) -> f32 {
+    match c {
+        '0'..='9' => 556.0,
+        '.' | ',' | ' ' => 278.0,
+        '-' => 333.0,
+        'i' | 'j' | 'l' | 'I' | '\'' | '|' => 250.0,
+        'm' | 'w' | 'M' | 'W' => 833.0,
+        c if c.is_ascii_uppercase() => 667.0,
+        _ => 520.0,
+    }
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().map(glyph_width).sum::<f32>() * size / 1000.0
+}
+
+/// Truncates `text` with an ellipsis so it fits in `width` points.
+fn fit(text: &str, width: f32, size: f32) -> String {
+    if text_width(text, size) <= width {
+        return text.to_string();
+    }
+    let budget = width - text_width("...", size);
+    let mut out = String::new();
+    let mut used = 0.0;
+    for c in text.chars() {
+        let w = glyph_width(c) * size / 1000.0;
+        if used + w > budget {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    out.push_str("...");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Checked-in render of `sample_slip()`. After an intended layout change, run the
+    /// test with `INVPRO_UPDATE_GOLDEN=<path to this file>` to write the new output.
+    const GOLDEN: &[u8] = include_bytes!("testdata/issue_slip.pdf");
+
+    fn sample_slip() -> PrintableDocument {
+        PrintableDocument {
+            title: "MATERIAL ISSUE SLIP - IS-2026-0042".to_string(),
+            fields: vec![
+                ("Slip No".to_string(), "IS-2026-0042".to_string()),
+                ("Date".to_string(), "31-Mar-2026".to_string()),
+                ("Issued To".to_string(), "Site Crew B".to_string()),
+                ("Project".to_string(), "Plant Extension".to_string()),
+                ("Remarks".to_string(), "Café canteen fit-out".to_string()),
+            ],
+            columns: vec![
+                Column::right("#", 25.0),
+                Column::left("Part", 110.0),
+                Column::left("Description", 175.0),
+                Column::left("UOM", 50.0),
+                Column::right("Qty", 75.0),
+            ],
+            // Enough lines to run onto a second page
+            rows: (1..=60)
+                .map(|n| {
+                    vec![
+                        n.to_string(),
+                        format!("M{} Bolt", 4 + n % 8),
+                        format!("Hex bolt, stainless steel, {} mm long with a washer and nut", 10 * n),
+                        "Nos".to_string(),
+                        format!("{}.5", n),
+                    ]
+                })
+                .collect(),
+            signatures: vec!["Issued By".to_string(), "Received By".to_string()],
+        }
+    }
+
+    fn sample_company() -> CompanyHeader {
+        let logo = image::RgbImage::from_fn(16, 8, |x, y| image::Rgb([(x * 16) as u8, (y * 32) as u8, 128]));
+        let mut png = Vec::new();
+        logo.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        CompanyHeader {
+            name: "InvPro Stores".to_string(),
+            address: Some("Plot 7, Industrial Area\nPhase II".to_string()),
+            logo: Some(png),
+        }
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn render_is_byte_identical_to_golden_file() {
+        let first = render(&sample_slip(), &sample_company()).unwrap();
+        let second = render(&sample_slip(), &sample_company()).unwrap();
+        assert!(first == second, "two renders of the same document differ");
+
+        if let Ok(path) = std::env::var("INVPRO_UPDATE_GOLDEN") {
+            std::fs::write(path, &first).unwrap();
+            return;
+        }
+        // Anything that changes per render would make reprints differ
+        for key in [&b"/CreationDate"[..], b"/ModDate", b"/ID"] {
+            assert!(!contains(&first, key), "render wrote {}", String::from_utf8_lossy(key));
+        }
+        assert!(first == GOLDEN, "render no longer matches src/testdata/issue_slip.pdf");
+    }
+}
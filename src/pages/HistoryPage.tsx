@@ -1,9 +1,10 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from "@tauri-apps/api/core";
+import { downloadDir, join } from "@tauri-apps/api/path";
+import { save } from "@tauri-apps/plugin-dialog";
 import { RotateCcw, Search, Download, X, FileText } from 'lucide-react';
 import toast from 'react-hot-toast';
 import { useAuth } from '../AuthContext';
-import * as XLSX from 'xlsx';
 
 interface LedgerEntry {
   ledger_id: number;
@@ -90,45 +91,38 @@ const HistoryPage: React.FC = () => {
 
   const handleExport = async (e: React.FormEvent) => {
      e.preventDefault();
+     const filename = `Audit_Trail_${new Date().toISOString().split('T')[0]}.xlsx`;
+     const filePath = await save({
+       defaultPath: await join(await downloadDir(), filename),
+       filters: [
+         { name: 'Excel Workbook', extensions: ['xlsx'] },
+         { name: 'CSV', extensions: ['csv'] }
+       ]
+     });
+     if (!filePath) return;
+
      setExporting(true);
      try {
-       const data = await invoke<LedgerEntry[]>("get_export_history", {
+       // Written by the backend straight from the database, so large exports
+       // don't have to pass through the page. Nothing is written when no rows match.
+       const count = await invoke<number>("export_history", {
          dateFrom: dateFrom || null,
          dateTo: dateTo || null,
-         status: exportStatus
+         status: exportStatus,
+         filePath
        });
 
-       if (data.length === 0) {
+       if (count === 0) {
          toast.error("No records found for the selected criteria");
          setExporting(false);
          return;
        }
 
-       // Format for Excel
-       const excelData = data.map(item => ({
-         "Transaction Date": new Date(item.transaction_date).toLocaleString(),
-         "Part Name": item.part_name || "N/A",
-         "Description": item.description || "N/A",
-         "Type": item.transaction_type,
-         "Quantity Change": item.quantity_change,
-         "Reference": item.reference,
-         "Reason": item.optional_reason || "",
-         "User": item.created_by,
-         "Reversed": item.is_already_reversed ? "Yes" : "No"
-       }));
-
-       const ws = XLSX.utils.json_to_sheet(excelData);
-       const wb = XLSX.utils.book_new();
-       XLSX.utils.book_append_sheet(wb, ws, "Audit Trail");
-       
-       const filename = `Audit_Trail_${new Date().toISOString().split('T')[0]}.xlsx`;
-       XLSX.writeFile(wb, filename);
-       
-       toast.success("Export downloaded successfully!");
+       toast.success(`Exported ${count} records to ${filePath}`);
        setShowExportModal(false);
      } catch (err) {
        console.error(err);
-       toast.error("Export failed");
+       toast.error("Export failed: " + err);
      } finally {
        setExporting(false);
      }
@@ -1,6 +1,6 @@
 import React, { useState } from 'react';
 import { invoke } from "@tauri-apps/api/core";
-import { FileUp, Download, Info } from 'lucide-react';
+import { FileUp, Download, Info, AlertTriangle } from 'lucide-react';
 import toast from 'react-hot-toast';
 import * as XLSX from 'xlsx';
 import { useAuth } from '../AuthContext';
@@ -24,6 +24,8 @@ interface ImportPreview {
   status: string;
   diff_reason?: string;
   existing_stock_id?: string;
+  override_duplicate?: boolean;
+  override_reason?: string;
 }
 
 const UploadPage: React.FC = () => {
@@ -75,7 +77,12 @@ const UploadPage: React.FC = () => {
         // Get preview from backend
         const previewData = await invoke<ImportPreview[]>("bulk_upload_preview", { rows });
         setPreview(previewData);
-        toast.success(`Excel parsed: ${rows.length} valid rows found`);
+        const errorCount = previewData.filter(p => p.status === 'ERROR').length;
+        if (errorCount > 0) {
+          toast.error(`${errorCount} of ${rows.length} rows have errors and will not be imported`);
+        } else {
+          toast.success(`Excel parsed: ${rows.length} valid rows found`);
+        }
       } catch (err) {
         console.error("Bulk upload preview error:", err);
         toast.error("Error during preview: " + (err as string));
@@ -86,12 +93,22 @@ const UploadPage: React.FC = () => {
     reader.readAsBinaryString(file);
   };
 
+  // Rows with errors are shown for correction but never sent for import, and
+  // invoices already received are only imported again when overridden
+  const importable = preview.filter(p => p.status !== 'ERROR' && (p.status !== 'DUPLICATE' || p.override_duplicate));
+  const skippedDuplicates = preview.filter(p => p.status === 'DUPLICATE' && !p.override_duplicate).length;
+
+  const updatePreview = (index: number, changes: Partial<ImportPreview>) => {
+    setPreview(preview.map((p, i) => i === index ? { ...p, ...changes } : p));
+  };
+
   const handleConfirmImport = async () => {
-    if (preview.length === 0) return;
+    if (importable.length === 0) return;
     setImporting(true);
     try {
-      await invoke("confirm_bulk_upload", { previews: preview, user });
-      toast.success(`Successfully imported ${preview.length} items.`);
+      await invoke("confirm_bulk_upload", { previews: importable, user });
+      toast.success(`Successfully imported ${importable.length} items.` +
+        (skippedDuplicates > 0 ? ` Skipped ${skippedDuplicates} duplicate rows.` : ''));
       setPreview([]);
     } catch (err) {
       toast.error("Import failed: " + err);
@@ -160,7 +177,7 @@ const UploadPage: React.FC = () => {
             <h3 style={{ margin: 0 }}>Import Preview ({preview.length} rows)</h3>
             <div style={{ display: 'flex', gap: '12px' }}>
               <button className="btn btn-secondary" onClick={() => setPreview([])}>Discard</button>
-              <button className="btn btn-primary" onClick={handleConfirmImport} disabled={importing}>
+              <button className="btn btn-primary" onClick={handleConfirmImport} disabled={importing || importable.length === 0}>
                 {importing ? 'Importing...' : 'Confirm & Process Bulk Import'}
               </button>
             </div>
@@ -182,7 +199,37 @@ const UploadPage: React.FC = () => {
                 {preview.map((p, i) => (
                   <tr key={i}>
                     <td>
-                      {p.status === 'MERGED' ? (
+                      {p.status === 'ERROR' ? (
+                        <span style={{ 
+                          background: 'rgba(239, 68, 68, 0.1)', 
+                          color: 'var(--danger)', 
+                          padding: '4px 10px', 
+                          borderRadius: '20px', 
+                          fontSize: '0.7rem', 
+                          fontWeight: 700,
+                          display: 'inline-flex',
+                          alignItems: 'center',
+                          gap: '6px',
+                          border: '1px solid rgba(239, 68, 68, 0.2)'
+                        }}>
+                          <AlertTriangle size={12} /> ERROR
+                        </span>
+                      ) : p.status === 'DUPLICATE' ? (
+                        <span style={{ 
+                          background: 'rgba(245, 158, 11, 0.1)', 
+                          color: 'var(--warning)', 
+                          padding: '4px 10px', 
+                          borderRadius: '20px', 
+                          fontSize: '0.7rem', 
+                          fontWeight: 700,
+                          display: 'inline-flex',
+                          alignItems: 'center',
+                          gap: '6px',
+                          border: '1px solid rgba(245, 158, 11, 0.2)'
+                        }}>
+                          <AlertTriangle size={12} /> DUPLICATE
+                        </span>
+                      ) : p.status === 'MERGED' ? (
                         <span style={{ 
                           background: 'rgba(16, 185, 129, 0.1)', 
                           color: 'var(--success)', 
@@ -214,7 +261,36 @@ const UploadPage: React.FC = () => {
                         </span>
                       )}
                     </td>
-                    <td style={{ fontWeight: 600, color: 'var(--text-white)' }}>{p.row.part_name}</td>
+                    <td style={{ fontWeight: 600, color: 'var(--text-white)' }}>
+                      {p.row.part_name}
+                      {p.diff_reason && (
+                        <div style={{ fontSize: '0.75rem', fontWeight: 400, color: p.status === 'ERROR' ? 'var(--danger)' : 'var(--text-muted)' }}>
+                          {p.diff_reason}
+                        </div>
+                      )}
+                      {p.status === 'DUPLICATE' && (
+                        <div style={{ display: 'flex', alignItems: 'center', gap: '8px', marginTop: '6px', fontSize: '0.75rem', fontWeight: 400 }}>
+                          <label style={{ display: 'inline-flex', alignItems: 'center', gap: '4px', cursor: 'pointer' }}>
+                            <input
+                              type="checkbox"
+                              checked={!!p.override_duplicate}
+                              onChange={(e) => updatePreview(i, { override_duplicate: e.target.checked })}
+                            />
+                            Import anyway
+                          </label>
+                          {p.override_duplicate && (
+                            <input
+                              type="text"
+                              className="form-control"
+                              placeholder="Reason for importing again"
+                              style={{ padding: '4px 8px', fontSize: '0.75rem' }}
+                              value={p.override_reason || ""}
+                              onChange={(e) => updatePreview(i, { override_reason: e.target.value })}
+                            />
+                          )}
+                        </div>
+                      )}
+                    </td>
                     <td>{p.row.project}</td>
                     <td>{p.row.invoice}</td>
                     <td style={{ fontWeight: 700, color: 'var(--primary)' }}>{p.row.quantity}</td>